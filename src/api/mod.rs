@@ -0,0 +1,3 @@
+// Versioned JSON API. Each version keeps its own response types, so the
+// documents it serves stay stable while the internal stats evolve.
+pub mod v1;
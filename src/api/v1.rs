@@ -0,0 +1,1204 @@
+use crate::auth::Session;
+use crate::cards::CardCache;
+use crate::config::AppConfig;
+use crate::consent::{ConsentChange, ConsentRegistry};
+use crate::emoji::EmojiStore;
+use crate::events::EventStore;
+use crate::features::emoji_timeline::load_timeline;
+use crate::features::history::StoredEvents;
+use crate::features::streaks::{ActivityStreaks, Streak};
+use crate::features::summary_card::card_cache;
+use crate::features::workspace_wrapped::WorkspaceWrapped;
+use crate::features::wrapped::{display_name, UserWrapped};
+use crate::jobs::queue::{JobEvent, JobQueue};
+use crate::jobs::{Job, JobStage, JobStatus};
+use crate::mrkdwn::parser::parse;
+use crate::mrkdwn::render::to_plain_text;
+use crate::slack::client::SlackClient;
+use crate::text::tfidf::WeightedTerm;
+use crate::workspaces::TokenStore;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use rocket;
+use rocket::http::{ContentType, Status};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{delete, get, post, Route, Shutdown, State};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::path::PathBuf;
+use utoipa::{OpenApi, ToSchema};
+use zip::result::ZipResult;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+pub const API_VERSION: &str = "1";
+
+// One document with every stat of a user's year
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WrappedDocument {
+    /// Always "1" for this version of the API
+    pub api_version: String,
+    pub generated_at: DateTime<Utc>,
+    pub year: i32,
+    pub user: UserSummary,
+    pub message_count: u32,
+    pub top_channels: Vec<ChannelStat>,
+    /// Reactions the user added, most used first
+    pub reactions: Vec<ReactionStat>,
+    pub collaborators: Vec<CollaboratorStat>,
+    /// Calendar days
+    pub streaks: StreakStats,
+    /// Monday to Friday, skipping weekends
+    pub workday_streaks: StreakStats,
+    pub threads: ThreadStatsDocument,
+    /// Messages per weekday (Monday first) and hour of day, in the user's timezone
+    pub heatmap: Vec<Vec<u32>>,
+    pub word_cloud: WordCloudDocument,
+    pub most_reacted_message: Option<MessageStat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserSummary {
+    pub id: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    /// Seconds east of UTC, used for every date and hour in the document
+    pub tz_offset: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ChannelStat {
+    pub id: String,
+    pub name: String,
+    pub messages: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReactionStat {
+    pub name: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CollaboratorStat {
+    pub user_id: String,
+    pub display_name: String,
+    pub score: u32,
+    pub mentions: u32,
+    pub thread_replies: u32,
+    pub reactions: u32,
+    pub direct_messages: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreakStats {
+    pub active_days: u32,
+    pub longest: Option<StreakRange>,
+    pub current: Option<StreakRange>,
+    pub busiest_day: Option<BusiestDayStat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreakRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BusiestDayStat {
+    pub date: NaiveDate,
+    pub message_count: u32,
+    pub top_channel: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ThreadStatsDocument {
+    pub threads_started: u32,
+    pub threads_replied: u32,
+    pub thread_messages: u32,
+    pub top_level_messages: u32,
+    pub average_reply_latency_secs: Option<f64>,
+    pub longest_thread: Option<ThreadStat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ThreadStat {
+    pub channel: String,
+    pub thread_ts: String,
+    pub reply_count: u32,
+    /// Null when Slack could not link to the thread
+    pub permalink: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WordCloudDocument {
+    pub words: Vec<TermStat>,
+    pub bigrams: Vec<TermStat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TermStat {
+    pub term: String,
+    pub count: u32,
+    /// Relative to the most distinctive term, which has a weight of 1
+    pub weight: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MessageStat {
+    pub channel: String,
+    pub ts: String,
+    /// Rendered as plain text, with mentions resolved to names
+    pub text: String,
+    pub reaction_count: i32,
+}
+
+/// The whole workspace's year, counted in UTC. Lists only include channels,
+/// emoji, people and messages that pass the configured privacy thresholds,
+/// and only public channels are named or quoted.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkspaceWrappedDocument {
+    /// Always "1" for this version of the API
+    pub api_version: String,
+    pub generated_at: DateTime<Utc>,
+    pub year: i32,
+    pub totals: WorkspaceTotals,
+    pub top_channels: Vec<ChannelStat>,
+    pub top_reactors: Vec<ReactorStat>,
+    /// Emoji by reactions across the workspace, most used first
+    pub top_emoji: Vec<ReactionStat>,
+    pub most_reacted_messages: Vec<QuotedMessageStat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkspaceTotals {
+    pub messages: u32,
+    pub reactions: u32,
+    /// Channels created during the year, not counting direct messages
+    pub new_channels: u32,
+    /// Null when the workspace's emoji history is not known
+    pub new_custom_emoji: Option<u32>,
+    /// People who posted at least one message
+    pub active_users: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReactorStat {
+    pub user_id: String,
+    pub display_name: String,
+    /// Reactions added during the year
+    pub reactions: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QuotedMessageStat {
+    pub author_id: String,
+    pub author_name: String,
+    #[serde(flatten)]
+    pub message: MessageStat,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct JobDocument {
+    pub id: String,
+    pub user_id: String,
+    pub year: i32,
+    pub status: JobStatus,
+    pub stage: JobStage,
+    pub progress: JobProgress,
+    /// Why the job failed
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Where to get the wrapped once the job has succeeded
+    pub result_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct JobProgress {
+    pub channels_done: u32,
+    pub channels_total: u32,
+    /// Pages of history and thread replies read from Slack so far
+    pub pages_fetched: u32,
+}
+
+// Everything the service keeps about one person
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserDataExport {
+    pub api_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub user_id: String,
+    pub team_id: String,
+    pub consent: ConsentExport,
+    /// Messages the user posted, as received from the Events API
+    pub messages: Vec<MessageExport>,
+    /// Reactions the user added, as received from the Events API
+    pub reactions: Vec<ReactionExport>,
+    /// Wrapped jobs about the user or that they asked for
+    pub jobs: Vec<JobExport>,
+    /// Installations of the app by the user, without the tokens
+    pub installations: Vec<InstallationExport>,
+    /// Summary cards rendered for the user
+    pub cards: Vec<CardExport>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConsentExport {
+    pub opted_out: bool,
+    /// Changes to the user's consent, or made by them
+    pub changes: Vec<ConsentChange>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MessageExport {
+    pub channel: String,
+    pub ts: String,
+    pub thread_ts: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReactionExport {
+    pub channel: String,
+    /// Of the message reacted to
+    pub ts: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobExport {
+    #[serde(flatten)]
+    pub job: JobDocument,
+    pub requested_by: String,
+    /// Only for jobs about the user
+    pub result: Option<WrappedDocument>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InstallationExport {
+    pub team_id: String,
+    pub team_name: Option<String>,
+    pub app_id: String,
+    pub user_scopes: Vec<String>,
+    pub has_user_token: bool,
+    pub installed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CardExport {
+    pub file_name: String,
+    pub bytes: u64,
+}
+
+// What deleting someone's data removed, checked by looking again afterwards
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeletionReport {
+    pub deleted_at: DateTime<Utc>,
+    pub user_id: String,
+    pub team_id: String,
+    pub removed: StoredData,
+    /// What was found afterwards, all zero when everything was deleted
+    pub remaining: StoredData,
+    pub verified: bool,
+    /// Kept on purpose, and why
+    pub kept: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, ToSchema)]
+pub struct StoredData {
+    pub messages: usize,
+    pub reactions: usize,
+    pub channel_memberships: usize,
+    /// Wrapped jobs, with their results
+    pub jobs: usize,
+    pub cards: usize,
+    pub user_tokens: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiError {
+    pub error: String,
+}
+
+fn api_error(status: Status, error: impl ToString) -> (Status, Json<ApiError>) {
+    (
+        status,
+        Json(ApiError {
+            error: error.to_string(),
+        }),
+    )
+}
+
+impl WrappedDocument {
+    pub fn new(wrapped: &UserWrapped, generated_at: DateTime<Utc>) -> Self {
+        Self {
+            api_version: API_VERSION.to_string(),
+            generated_at,
+            year: wrapped.year,
+            user: UserSummary {
+                id: wrapped.user.id.clone(),
+                display_name: display_name(&wrapped.user).to_string(),
+                avatar_url: wrapped.user.profile.image_192.clone(),
+                tz_offset: wrapped.user.tz_offset,
+            },
+            message_count: wrapped.message_count,
+            top_channels: wrapped
+                .top_channels
+                .iter()
+                .map(|channel| ChannelStat {
+                    id: channel.channel.clone(),
+                    name: channel.name.clone(),
+                    messages: channel.messages,
+                })
+                .collect(),
+            reactions: wrapped
+                .reactions_used
+                .iter()
+                .map(|(name, count)| ReactionStat {
+                    name: name.clone(),
+                    count: *count,
+                })
+                .collect(),
+            collaborators: wrapped
+                .collaborators
+                .iter()
+                .map(|collaborator| CollaboratorStat {
+                    user_id: collaborator.user_id.clone(),
+                    display_name: wrapped.name_of(&collaborator.user_id),
+                    score: collaborator.interactions.score(),
+                    mentions: collaborator.interactions.mentions,
+                    thread_replies: collaborator.interactions.thread_replies,
+                    reactions: collaborator.interactions.reactions,
+                    direct_messages: collaborator.interactions.direct_messages,
+                })
+                .collect(),
+            streaks: StreakStats::from(&wrapped.streaks),
+            workday_streaks: StreakStats::from(&wrapped.workday_streaks),
+            threads: ThreadStatsDocument {
+                threads_started: wrapped.threads.threads_started,
+                threads_replied: wrapped.threads.threads_replied,
+                thread_messages: wrapped.threads.thread_messages,
+                top_level_messages: wrapped.threads.top_level_messages,
+                average_reply_latency_secs: wrapped.threads.average_reply_latency_secs,
+                longest_thread: wrapped
+                    .threads
+                    .longest_thread
+                    .as_ref()
+                    .map(|thread| ThreadStat {
+                        channel: thread.channel.clone(),
+                        thread_ts: thread.thread_ts.clone(),
+                        reply_count: thread.reply_count,
+                        permalink: thread.permalink.clone(),
+                    }),
+            },
+            heatmap: wrapped
+                .heatmap
+                .counts
+                .iter()
+                .map(|hours| hours.to_vec())
+                .collect(),
+            word_cloud: WordCloudDocument {
+                words: terms(&wrapped.word_cloud.words),
+                bigrams: terms(&wrapped.word_cloud.bigrams),
+            },
+            most_reacted_message: wrapped.most_reacted_message.as_ref().map(|message| {
+                MessageStat {
+                    channel: message.channel.clone(),
+                    ts: message.message.ts.clone(),
+                    text: to_plain_text(&parse(&message.message.text), &wrapped.directory),
+                    reaction_count: message
+                        .message
+                        .reactions
+                        .iter()
+                        .map(|reaction| reaction.count)
+                        .sum(),
+                }
+            }),
+        }
+    }
+}
+
+impl WorkspaceWrappedDocument {
+    pub fn new(wrapped: &WorkspaceWrapped, generated_at: DateTime<Utc>) -> Self {
+        Self {
+            api_version: API_VERSION.to_string(),
+            generated_at,
+            year: wrapped.year,
+            totals: WorkspaceTotals {
+                messages: wrapped.message_count,
+                reactions: wrapped.reaction_count,
+                new_channels: wrapped.new_channels,
+                new_custom_emoji: wrapped.new_custom_emoji,
+                active_users: wrapped.active_users,
+            },
+            top_channels: wrapped
+                .top_channels
+                .iter()
+                .map(|channel| ChannelStat {
+                    id: channel.channel.clone(),
+                    name: channel.name.clone(),
+                    messages: channel.messages,
+                })
+                .collect(),
+            top_reactors: wrapped
+                .top_reactors
+                .iter()
+                .map(|(user_id, reactions)| ReactorStat {
+                    user_id: user_id.clone(),
+                    display_name: wrapped.name_of(user_id),
+                    reactions: *reactions,
+                })
+                .collect(),
+            top_emoji: wrapped
+                .top_emoji
+                .iter()
+                .map(|(name, count)| ReactionStat {
+                    name: name.clone(),
+                    count: *count,
+                })
+                .collect(),
+            most_reacted_messages: wrapped
+                .most_reacted_messages
+                .iter()
+                .map(|message| QuotedMessageStat {
+                    author_id: message.message.user.clone(),
+                    author_name: wrapped.name_of(&message.message.user),
+                    message: MessageStat {
+                        channel: message.channel.clone(),
+                        ts: message.message.ts.clone(),
+                        text: to_plain_text(&parse(&message.message.text), &wrapped.directory),
+                        reaction_count: message
+                            .message
+                            .reactions
+                            .iter()
+                            .map(|reaction| reaction.count)
+                            .sum(),
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&Job> for JobDocument {
+    fn from(job: &Job) -> Self {
+        Self {
+            id: job.id.clone(),
+            user_id: job.user_id.clone(),
+            year: job.year,
+            status: job.status,
+            stage: job.stage,
+            progress: JobProgress {
+                channels_done: job.progress.channels_done,
+                channels_total: job.progress.channels_total,
+                pages_fetched: job.progress.pages_fetched,
+            },
+            error: job.error.clone(),
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+            result_url: (job.status == JobStatus::Succeeded)
+                .then(|| format!("/api/v1/wrapped/{}/jobs/{}/result", job.user_id, job.id)),
+        }
+    }
+}
+
+impl From<&ActivityStreaks> for StreakStats {
+    fn from(streaks: &ActivityStreaks) -> Self {
+        Self {
+            active_days: streaks.active_days,
+            longest: streaks.longest.as_ref().map(StreakRange::from),
+            current: streaks.current.as_ref().map(StreakRange::from),
+            busiest_day: streaks.busiest_day.as_ref().map(|day| BusiestDayStat {
+                date: day.date,
+                message_count: day.message_count,
+                top_channel: day.top_channel.clone(),
+            }),
+        }
+    }
+}
+
+impl From<&Streak> for StreakRange {
+    fn from(streak: &Streak) -> Self {
+        Self {
+            start: streak.start,
+            end: streak.end,
+            days: streak.days,
+        }
+    }
+}
+
+fn terms(terms: &[WeightedTerm]) -> Vec<TermStat> {
+    terms
+        .iter()
+        .map(|term| TermStat {
+            term: term.term.clone(),
+            count: term.count,
+            weight: term.weight,
+        })
+        .collect()
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Slackify Wrapped", version = "1"),
+    servers((url = "/api/v1")),
+    paths(
+        wrapped_route,
+        create_job_route,
+        job_route,
+        job_result_route,
+        job_events_route,
+        workspace_wrapped_route,
+        export_data_route,
+        export_data_zip_route,
+        delete_data_route,
+        openapi_route
+    ),
+    components(schemas(JobEvent))
+)]
+pub struct ApiDoc;
+
+/// Every wrapped stat of a user for a year
+#[utoipa::path(
+    get,
+    path = "/wrapped/{user_id}",
+    params(
+        ("user_id" = String, Path, description = "Slack user ID"),
+        ("year" = Option<i32>, Query, description = "Defaults to the current year")
+    ),
+    responses(
+        (status = 200, description = "The user's wrapped", body = WrappedDocument),
+        (status = 401, description = "Not signed in, or the workspace has not installed the app"),
+        (status = 403, description = "Only admins can see other people's wrapped, and not of people who opted out", body = ApiError),
+        (status = 502, description = "Slack could not be reached or returned an error", body = ApiError)
+    )
+)]
+#[get("/wrapped/<user_id>?<year>")]
+pub async fn wrapped_route(
+    user_id: &str,
+    year: Option<i32>,
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<Json<WrappedDocument>, (Status, Json<ApiError>)> {
+    authorize(registry, &session, user_id)?;
+    let opted_out = opted_out(registry, &session)?;
+    let year = year.unwrap_or(Utc::now().year());
+    let stored = StoredEvents {
+        store: events,
+        team_id: &session.team_id.0,
+    };
+    match UserWrapped::fetch(&slack_client, Some(stored), user_id, year).await {
+        Ok(mut wrapped) => {
+            wrapped.anonymise(&opted_out);
+            Ok(Json(WrappedDocument::new(&wrapped, Utc::now())))
+        }
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            Err(api_error(Status::BadGateway, error))
+        }
+    }
+}
+
+const NOT_YOURS: &str = "only admins can see other people's wrapped";
+
+// Admins can see other people's wrapped, unless they opted out
+fn authorize(
+    registry: &ConsentRegistry,
+    session: &Session,
+    user_id: &str,
+) -> Result<(), (Status, Json<ApiError>)> {
+    if let Err(status) = session.authorize(user_id) {
+        return Err(api_error(status, NOT_YOURS));
+    }
+    match registry.authorize(session, user_id) {
+        Ok(()) => Ok(()),
+        Err(status) => Err(api_error(status, "this user opted out of wrapped")),
+    }
+}
+
+// People to show anonymously in the signed in workspace
+fn opted_out(
+    registry: &ConsentRegistry,
+    session: &Session,
+) -> Result<HashSet<String>, (Status, Json<ApiError>)> {
+    registry
+        .opted_out(&session.team_id.0)
+        .map_err(|error| api_error(Status::InternalServerError, error))
+}
+
+/// Starts computing a user's wrapped in the background. A job that is still
+/// running, or that finished within the wrapped cache lifetime, is returned
+/// instead of starting another.
+#[utoipa::path(
+    post,
+    path = "/wrapped/{user_id}/jobs",
+    params(
+        ("user_id" = String, Path, description = "Slack user ID"),
+        ("year" = Option<i32>, Query, description = "Defaults to the current year")
+    ),
+    responses(
+        (status = 202, description = "A new job was queued", body = JobDocument),
+        (status = 200, description = "An existing job can be used", body = JobDocument),
+        (status = 401, description = "Not signed in, or the workspace has not installed the app"),
+        (status = 403, description = "Only admins can see other people's wrapped, and not of people who opted out", body = ApiError),
+        (status = 500, description = "The job could not be saved", body = ApiError)
+    )
+)]
+#[post("/wrapped/<user_id>/jobs?<year>")]
+pub fn create_job_route(
+    user_id: &str,
+    year: Option<i32>,
+    session: Session,
+    queue: &State<JobQueue>,
+    config: &State<AppConfig>,
+    registry: &State<ConsentRegistry>,
+    slack_client: SlackClient,
+) -> Result<(Status, Json<JobDocument>), (Status, Json<ApiError>)> {
+    authorize(registry, &session, user_id)?;
+    let opted_out = opted_out(registry, &session)?;
+    let year = year.unwrap_or(Utc::now().year());
+    let team_id = &session.team_id.0;
+    let (job, created) = queue
+        .store
+        .reusable_or_create(
+            team_id,
+            user_id,
+            year,
+            &session.user_id.0,
+            config.cache.fresh_since(Utc::now()),
+            Utc::now(),
+        )
+        .map_err(|error| api_error(Status::InternalServerError, error))?;
+    if !created {
+        return Ok((Status::Ok, Json(JobDocument::from(&job))));
+    }
+    queue.submit(&job, slack_client, opted_out);
+    Ok((Status::Accepted, Json(JobDocument::from(&job))))
+}
+
+// The job, if it exists and is for this user in the signed in workspace
+fn find_job(
+    queue: &JobQueue,
+    session: &Session,
+    user_id: &str,
+    job_id: &str,
+) -> Result<Job, (Status, Json<ApiError>)> {
+    if let Err(status) = session.authorize(user_id) {
+        return Err(api_error(status, NOT_YOURS));
+    }
+    match queue.store.get(job_id) {
+        Ok(Some(job)) if job.user_id == user_id && job.team_id == session.team_id.0 => Ok(job),
+        Ok(_) => Err(api_error(Status::NotFound, "no such job")),
+        Err(error) => Err(api_error(Status::InternalServerError, error)),
+    }
+}
+
+/// Status and progress of a wrapped job
+#[utoipa::path(
+    get,
+    path = "/wrapped/{user_id}/jobs/{job_id}",
+    params(
+        ("user_id" = String, Path, description = "Slack user ID"),
+        ("job_id" = String, Path, description = "As returned when the job was created")
+    ),
+    responses(
+        (status = 200, description = "The job", body = JobDocument),
+        (status = 401, description = "Not signed in"),
+        (status = 403, description = "Only admins can see other people's wrapped", body = ApiError),
+        (status = 404, description = "No such job for this user", body = ApiError)
+    )
+)]
+#[get("/wrapped/<user_id>/jobs/<job_id>")]
+pub fn job_route(
+    user_id: &str,
+    job_id: &str,
+    session: Session,
+    queue: &State<JobQueue>,
+) -> Result<Json<JobDocument>, (Status, Json<ApiError>)> {
+    let job = find_job(queue, &session, user_id, job_id)?;
+    Ok(Json(JobDocument::from(&job)))
+}
+
+/// The wrapped computed by a job that succeeded
+#[utoipa::path(
+    get,
+    path = "/wrapped/{user_id}/jobs/{job_id}/result",
+    params(
+        ("user_id" = String, Path, description = "Slack user ID"),
+        ("job_id" = String, Path, description = "As returned when the job was created")
+    ),
+    responses(
+        (status = 200, description = "The user's wrapped", body = WrappedDocument),
+        (status = 401, description = "Not signed in"),
+        (status = 403, description = "Only admins can see other people's wrapped", body = ApiError),
+        (status = 404, description = "No such job for this user", body = ApiError),
+        (status = 409, description = "The job has not succeeded", body = ApiError)
+    )
+)]
+#[get("/wrapped/<user_id>/jobs/<job_id>/result")]
+pub fn job_result_route(
+    user_id: &str,
+    job_id: &str,
+    session: Session,
+    queue: &State<JobQueue>,
+) -> Result<Json<WrappedDocument>, (Status, Json<ApiError>)> {
+    let job = find_job(queue, &session, user_id, job_id)?;
+    match queue.store.result(&job.id) {
+        Ok(Some(document)) => Ok(Json(document)),
+        Ok(None) => Err(api_error(
+            Status::Conflict,
+            format!("the job is {}", job.status.as_str()),
+        )),
+        Err(error) => Err(api_error(Status::InternalServerError, error)),
+    }
+}
+
+/// Progress of a wrapped job as Server-Sent Events. The first event is where
+/// the job is now. `progress` and `rate_limited` events follow while it
+/// runs, and the stream ends after the `finished` event. Each event's data
+/// is a `JobEvent`.
+#[utoipa::path(
+    get,
+    path = "/wrapped/{user_id}/jobs/{job_id}/events",
+    params(
+        ("user_id" = String, Path, description = "Slack user ID"),
+        ("job_id" = String, Path, description = "As returned when the job was created")
+    ),
+    responses(
+        (status = 200, description = "A stream of job events", content_type = "text/event-stream"),
+        (status = 401, description = "Not signed in"),
+        (status = 403, description = "Only admins can see other people's wrapped", body = ApiError),
+        (status = 404, description = "No such job for this user", body = ApiError)
+    )
+)]
+#[get("/wrapped/<user_id>/jobs/<job_id>/events")]
+pub fn job_events_route(
+    user_id: &str,
+    job_id: &str,
+    session: Session,
+    queue: &State<JobQueue>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], (Status, Json<ApiError>)> {
+    // Subscribed before reading the job, so no event falls in between
+    let events = queue.subscribe(job_id);
+    let job = find_job(queue, &session, user_id, job_id)?;
+    let snapshot = JobEvent::snapshot(&job);
+    Ok(EventStream! {
+        let finished = matches!(snapshot, JobEvent::Finished { .. });
+        yield Event::json(&snapshot).event(snapshot.name());
+        // Only jobs submitted in this process have events, others are
+        // reported as they are
+        let (false, Some(mut events)) = (finished, events) else {
+            return;
+        };
+        loop {
+            let event = select! {
+                event = events.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            yield Event::json(&event).event(event.name());
+            if let JobEvent::Finished { .. } = event {
+                break;
+            }
+        }
+    })
+}
+
+/// Workspace totals and leaderboards for a year, for workspace admins
+#[utoipa::path(
+    get,
+    path = "/workspace-wrapped",
+    params(
+        ("year" = Option<i32>, Query, description = "Defaults to the current year")
+    ),
+    responses(
+        (status = 200, description = "The workspace's wrapped", body = WorkspaceWrappedDocument),
+        (status = 401, description = "Not signed in, or the workspace has not installed the app"),
+        (status = 403, description = "Only admins can see the workspace's wrapped", body = ApiError),
+        (status = 500, description = "The emoji history could not be read", body = ApiError),
+        (status = 502, description = "Slack could not be reached or returned an error", body = ApiError)
+    )
+)]
+#[get("/workspace-wrapped?<year>")]
+pub async fn workspace_wrapped_route(
+    year: Option<i32>,
+    session: Session,
+    config: &State<AppConfig>,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    emoji: &State<EmojiStore>,
+    slack_client: SlackClient,
+) -> Result<Json<WorkspaceWrappedDocument>, (Status, Json<ApiError>)> {
+    if let Err(status) = session.authorize_admin() {
+        return Err(api_error(
+            status,
+            "only admins can see the workspace's wrapped",
+        ));
+    }
+    let opted_out = opted_out(registry, &session)?;
+    let timeline = load_timeline(emoji, events, &session.team_id.0)
+        .map_err(|status| api_error(status, "the emoji history could not be read"))?;
+    let year = year.unwrap_or(Utc::now().year());
+    let stored = StoredEvents {
+        store: events,
+        team_id: &session.team_id.0,
+    };
+    match WorkspaceWrapped::fetch(
+        &slack_client,
+        Some(stored),
+        year,
+        &config.privacy,
+        &opted_out,
+    )
+    .await
+    {
+        Ok(mut wrapped) => {
+            wrapped.count_new_emoji(&timeline);
+            Ok(Json(WorkspaceWrappedDocument::new(&wrapped, Utc::now())))
+        }
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            Err(api_error(Status::BadGateway, error))
+        }
+    }
+}
+
+/// Everything stored about the signed in user: their consent, the messages
+/// and reactions received from the Events API, wrapped jobs and results, app
+/// installations (without tokens) and rendered cards
+#[utoipa::path(
+    get,
+    path = "/me/data",
+    responses(
+        (status = 200, description = "The user's data", body = UserDataExport),
+        (status = 401, description = "Not signed in"),
+        (status = 500, description = "A store could not be read", body = ApiError)
+    )
+)]
+#[get("/me/data")]
+pub fn export_data_route(
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    queue: &State<JobQueue>,
+    store: &State<TokenStore>,
+    config: &State<AppConfig>,
+) -> Result<Json<UserDataExport>, (Status, Json<ApiError>)> {
+    user_data(&session, registry, events, queue, store, config).map(Json)
+}
+
+/// The same data as a ZIP archive: `data.json`, the messages and reactions
+/// as CSV files, and the rendered cards under `cards/`
+#[utoipa::path(
+    get,
+    path = "/me/data.zip",
+    responses(
+        (status = 200, description = "The user's data", content_type = "application/zip", body = Vec<u8>),
+        (status = 401, description = "Not signed in"),
+        (status = 500, description = "A store could not be read", body = ApiError)
+    )
+)]
+#[get("/me/data.zip")]
+pub fn export_data_zip_route(
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    queue: &State<JobQueue>,
+    store: &State<TokenStore>,
+    config: &State<AppConfig>,
+) -> Result<(ContentType, Vec<u8>), (Status, Json<ApiError>)> {
+    let export = user_data(&session, registry, events, queue, store, config)?;
+    let cards = card_cache(config)
+        .user_cards(&session.user_id.0)
+        .map_err(|error| api_error(Status::InternalServerError, error))?;
+    match data_archive(&export, &cards) {
+        Ok(archive) => Ok((ContentType::ZIP, archive)),
+        Err(error) => Err(api_error(Status::InternalServerError, error)),
+    }
+}
+
+fn user_data(
+    session: &Session,
+    registry: &ConsentRegistry,
+    events: &EventStore,
+    queue: &JobQueue,
+    store: &TokenStore,
+    config: &AppConfig,
+) -> Result<UserDataExport, (Status, Json<ApiError>)> {
+    let internal = |error: &dyn std::fmt::Display| api_error(Status::InternalServerError, error);
+    let (user_id, team_id) = (&session.user_id.0, &session.team_id.0);
+
+    let opted_out = registry
+        .is_opted_out(team_id, user_id)
+        .map_err(|error| internal(&error))?;
+    let changes = registry
+        .audit_log(team_id)
+        .map_err(|error| internal(&error))?
+        .into_iter()
+        .filter(|change| change.user_id == *user_id || change.actor == *user_id)
+        .collect();
+
+    let messages = events
+        .user_messages(team_id, user_id)
+        .map_err(|error| internal(&error))?
+        .into_iter()
+        .map(|message| MessageExport {
+            channel: message.channel,
+            ts: message.message.ts,
+            thread_ts: message.message.thread_ts,
+            text: message.message.text,
+        })
+        .collect();
+    let reactions = events
+        .user_reactions(team_id, user_id)
+        .map_err(|error| internal(&error))?
+        .into_iter()
+        .map(|reaction| ReactionExport {
+            channel: reaction.channel,
+            ts: reaction.ts,
+            name: reaction.name,
+        })
+        .collect();
+
+    let mut jobs = Vec::new();
+    for job in queue
+        .store
+        .for_user(team_id, user_id)
+        .map_err(|error| internal(&error))?
+    {
+        // Wrapped the user asked for about others is theirs, not the user's
+        let result = match job.user_id == *user_id {
+            true => queue
+                .store
+                .result(&job.id)
+                .map_err(|error| internal(&error))?,
+            false => None,
+        };
+        jobs.push(JobExport {
+            job: JobDocument::from(&job),
+            requested_by: job.requested_by.clone(),
+            result,
+        });
+    }
+
+    let installations = store
+        .all()
+        .into_iter()
+        .filter(|installation| {
+            installation.team_id == *team_id && installation.installer_user_id == *user_id
+        })
+        .map(|installation| InstallationExport {
+            team_id: installation.team_id,
+            team_name: installation.team_name,
+            app_id: installation.app_id,
+            user_scopes: installation.user_scopes,
+            has_user_token: installation.user_token.is_some(),
+            installed_at: installation.installed_at,
+        })
+        .collect();
+
+    let cards = card_cache(config)
+        .user_cards(user_id)
+        .map_err(|error| internal(&error))?
+        .into_iter()
+        .map(|path| CardExport {
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            bytes: fs::metadata(&path).map_or(0, |metadata| metadata.len()),
+        })
+        .collect();
+
+    Ok(UserDataExport {
+        api_version: API_VERSION.to_string(),
+        exported_at: Utc::now(),
+        user_id: user_id.clone(),
+        team_id: team_id.clone(),
+        consent: ConsentExport { opted_out, changes },
+        messages,
+        reactions,
+        jobs,
+        installations,
+        cards,
+    })
+}
+
+fn data_archive(export: &UserDataExport, cards: &[PathBuf]) -> ZipResult<Vec<u8>> {
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+
+    archive.start_file("data.json", options)?;
+    serde_json::to_writer_pretty(&mut archive, export).map_err(io::Error::from)?;
+
+    let mut messages = String::from("channel,ts,thread_ts,text\n");
+    for message in &export.messages {
+        messages += &csv_row(&[
+            &message.channel,
+            &message.ts,
+            message.thread_ts.as_deref().unwrap_or_default(),
+            &message.text,
+        ]);
+    }
+    archive.start_file("messages.csv", options)?;
+    archive.write_all(messages.as_bytes())?;
+
+    let mut reactions = String::from("channel,ts,name\n");
+    for reaction in &export.reactions {
+        reactions += &csv_row(&[&reaction.channel, &reaction.ts, &reaction.name]);
+    }
+    archive.start_file("reactions.csv", options)?;
+    archive.write_all(reactions.as_bytes())?;
+
+    for card in cards {
+        let Some(file_name) = card.file_name() else {
+            continue;
+        };
+        archive.start_file(format!("cards/{}", file_name.to_string_lossy()), options)?;
+        archive.write_all(&fs::read(card)?)?;
+    }
+    Ok(archive.finish()?.into_inner())
+}
+
+// Quotes the fields that need it, message text can hold anything
+fn csv_row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match field.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field.to_string(),
+        })
+        .collect();
+    fields.join(",") + "\n"
+}
+
+// How much of the user's data is stored right now
+fn stored_data(
+    events: &EventStore,
+    queue: &JobQueue,
+    store: &TokenStore,
+    cards: &CardCache,
+    team_id: &str,
+    user_id: &str,
+) -> Result<StoredData, (Status, Json<ApiError>)> {
+    let activity = events
+        .user_activity(team_id, user_id)
+        .map_err(|error| api_error(Status::InternalServerError, error))?;
+    Ok(StoredData {
+        messages: activity.messages,
+        reactions: activity.reactions,
+        channel_memberships: activity.channel_memberships,
+        jobs: queue
+            .store
+            .for_user(team_id, user_id)
+            .map_err(|error| api_error(Status::InternalServerError, error))?
+            .len(),
+        cards: cards
+            .user_cards(user_id)
+            .map_err(|error| api_error(Status::InternalServerError, error))?
+            .len(),
+        user_tokens: store
+            .get(team_id)
+            .filter(|installation| {
+                installation.installer_user_id == user_id && installation.user_token.is_some()
+            })
+            .map_or(0, |_| 1),
+    })
+}
+
+/// Deletes what is stored about the signed in user: their messages, the
+/// reactions they added and the channels they joined, wrapped jobs about them
+/// and their results, rendered cards, and the user token of an installation
+/// they made. Their consent record is kept, so an opt-out keeps applying, and
+/// so is the wrapped they asked for about other people, without their name.
+#[utoipa::path(
+    delete,
+    path = "/me/data",
+    responses(
+        (status = 200, description = "What was removed", body = DeletionReport),
+        (status = 401, description = "Not signed in"),
+        (status = 500, description = "A store could not be changed", body = ApiError)
+    )
+)]
+#[delete("/me/data")]
+pub fn delete_data_route(
+    session: Session,
+    events: &State<EventStore>,
+    queue: &State<JobQueue>,
+    store: &State<TokenStore>,
+    config: &State<AppConfig>,
+) -> Result<Json<DeletionReport>, (Status, Json<ApiError>)> {
+    let internal = |error: &dyn std::fmt::Display| api_error(Status::InternalServerError, error);
+    let (user_id, team_id) = (&session.user_id.0, &session.team_id.0);
+    let cards = card_cache(config);
+
+    let activity = events
+        .delete_user(team_id, user_id)
+        .map_err(|error| internal(&error))?;
+    let removed = StoredData {
+        messages: activity.messages,
+        reactions: activity.reactions,
+        channel_memberships: activity.channel_memberships,
+        jobs: queue
+            .store
+            .delete_for_user(team_id, user_id)
+            .map_err(|error| internal(&error))?,
+        cards: cards
+            .remove_user(user_id)
+            .map_err(|error| internal(&error))?,
+        user_tokens: store
+            .forget_user(team_id, user_id)
+            .map_err(|error| internal(&error))?,
+    };
+    let remaining = stored_data(events, queue, store, &cards, team_id, user_id)?;
+    Ok(Json(DeletionReport {
+        deleted_at: Utc::now(),
+        user_id: user_id.clone(),
+        team_id: team_id.clone(),
+        removed,
+        verified: remaining == StoredData::default(),
+        remaining,
+        kept: vec![
+            "consent record and audit log, so an opt-out keeps applying".to_string(),
+            "the workspace's bot token, which the rest of the workspace uses".to_string(),
+            "wrapped the user asked for about other people, without saying who asked".to_string(),
+        ],
+    }))
+}
+
+/// This document
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    responses((status = 200, description = "OpenAPI 3 description of this API"))
+)]
+#[get("/openapi.json")]
+pub fn openapi_route() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        wrapped_route,
+        create_job_route,
+        job_route,
+        job_result_route,
+        job_events_route,
+        workspace_wrapped_route,
+        export_data_route,
+        export_data_zip_route,
+        delete_data_route,
+        openapi_route
+    ]
+}
@@ -0,0 +1,139 @@
+// Who is making a request, from the session set by "Sign in with Slack".
+// Sessions live in a private cookie, so they are encrypted and cannot be
+// forged without the server's secret key.
+use chrono::{DateTime, Duration, Utc};
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const SESSION_COOKIE: &str = "session";
+pub const SESSION_DAYS: i64 = 7;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserId(pub String);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeamId(pub String);
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for TeamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub user_id: UserId,
+    pub team_id: TeamId,
+    // Workspace admins and owners can see everyone's wrapped
+    pub is_admin: bool,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn new(user_id: UserId, team_id: TeamId, is_admin: bool, now: DateTime<Utc>) -> Self {
+        Self {
+            user_id,
+            team_id,
+            is_admin,
+            expires_at: now + Duration::days(SESSION_DAYS),
+        }
+    }
+
+    pub fn can_view(&self, user_id: &str) -> bool {
+        self.is_admin || self.user_id.0 == user_id
+    }
+
+    // For routes that show a single user's stats
+    pub fn authorize(&self, user_id: &str) -> Result<(), Status> {
+        match self.can_view(user_id) {
+            true => Ok(()),
+            false => Err(Status::Forbidden),
+        }
+    }
+
+    pub fn authorize_admin(&self) -> Result<(), Status> {
+        match self.is_admin {
+            true => Ok(()),
+            false => Err(Status::Forbidden),
+        }
+    }
+
+    pub fn from_cookies(cookies: &CookieJar<'_>, now: DateTime<Utc>) -> Option<Self> {
+        let cookie = cookies.get_private(SESSION_COOKIE)?;
+        serde_json::from_str::<Session>(cookie.value())
+            .ok()
+            .filter(|session| session.expires_at > now)
+    }
+
+    pub fn store(&self, cookies: &CookieJar<'_>) {
+        cookies.add_private(
+            Cookie::build((SESSION_COOKIE, serde_json::to_string(self).unwrap()))
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .max_age(rocket::time::Duration::days(SESSION_DAYS)),
+        );
+    }
+
+    pub fn clear(cookies: &CookieJar<'_>) {
+        cookies.remove_private(SESSION_COOKIE);
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Session {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match Session::from_cookies(request.cookies(), Utc::now()) {
+            Some(session) => Outcome::Success(session),
+            None => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UserId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Session::from_request(request)
+            .await
+            .map(|session| session.user_id)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TeamId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Session::from_request(request)
+            .await
+            .map(|session| session.team_id)
+    }
+}
+
+// Random hex, for OAuth state parameters
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).expect("Unable to generate random token");
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Compares without exiting early, so timing does not reveal the expected value
+pub fn tokens_match(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
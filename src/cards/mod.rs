@@ -0,0 +1,306 @@
+// Social-sized summary cards, drawn as SVG and rasterised to PNG in-process
+// with bundled fonts, so the output looks the same on every host.
+pub mod standard_emoji;
+
+use crate::mrkdwn::render::escape_html;
+use crate::story::charts::truncate;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use resvg::{tiny_skia, usvg};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const WIDTH: u32 = 1200;
+pub const HEIGHT: u32 = 630;
+
+const FONT_FAMILY: &str = "DejaVu Sans";
+const FONTS: [&[u8]; 2] = [
+    include_bytes!("../../assets/fonts/DejaVuSans.ttf"),
+    include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf"),
+];
+
+pub struct EmojiImage {
+    pub mime_type: String,
+    pub bytes: Vec<u8>,
+}
+
+pub struct SummaryCard {
+    pub user_id: String,
+    pub name: String,
+    pub year: i32,
+    pub favourite_emoji: Option<String>,
+    // Where emoji_image comes from. Cards are cached by it, so a cached card
+    // is found without fetching the image.
+    pub emoji_url: Option<String>,
+    pub emoji_image: Option<EmojiImage>,
+    pub top_channel: Option<String>,
+    pub message_count: u32,
+    pub longest_streak: u32,
+}
+
+#[derive(Debug)]
+pub enum CardError {
+    Svg(usvg::Error),
+    Render,
+    Io(io::Error),
+}
+
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CardError::Svg(error) => write!(f, "invalid card svg: {}", error),
+            CardError::Render => write!(f, "could not rasterise card"),
+            CardError::Io(error) => write!(f, "card cache error: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for CardError {
+    fn from(error: io::Error) -> Self {
+        CardError::Io(error)
+    }
+}
+
+impl SummaryCard {
+    // Everything the SVG is drawn from, the emoji image by its URL
+    pub fn cache_key(&self) -> String {
+        content_hash(&format!(
+            "{}\n{}\n{}\n{:?}\n{:?}\n{:?}\n{}\n{}",
+            self.user_id,
+            self.name,
+            self.year,
+            self.favourite_emoji,
+            self.emoji_url,
+            self.top_channel,
+            self.message_count,
+            self.longest_streak
+        ))
+    }
+
+    // The image could not be fetched, so the card is drawn without it
+    fn is_missing_image(&self) -> bool {
+        self.emoji_url.is_some() && self.emoji_image.is_none()
+    }
+
+    // Self-contained: the emoji image is embedded as a data URI
+    pub fn svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+             font-family=\"{font}, sans-serif\">\
+             <defs><linearGradient id=\"bg\" x1=\"0\" y1=\"0\" x2=\"1\" y2=\"1\">\
+             <stop offset=\"0\" stop-color=\"#4a154b\"/><stop offset=\"1\" stop-color=\"#1264a3\"/>\
+             </linearGradient></defs>\
+             <rect width=\"{w}\" height=\"{h}\" fill=\"url(#bg)\"/>\
+             <text x=\"64\" y=\"112\" font-size=\"56\" font-weight=\"bold\" fill=\"#ffffff\">{title}</text>",
+            w = WIDTH,
+            h = HEIGHT,
+            font = FONT_FAMILY,
+            title = escape_html(&format!("{}'s {} in Slack", self.name, self.year)),
+        );
+
+        match (&self.emoji_image, &self.favourite_emoji) {
+            (Some(image), _) => {
+                svg += &format!(
+                    "<image x=\"64\" y=\"180\" width=\"240\" height=\"240\" href=\"data:{};base64,{}\"/>",
+                    escape_html(&image.mime_type),
+                    BASE64.encode(&image.bytes)
+                );
+            }
+            (None, Some(_)) => {
+                svg += "<circle cx=\"184\" cy=\"300\" r=\"120\" fill=\"#ecb22e\" fill-opacity=\"0.3\"/>";
+            }
+            (None, None) => {}
+        }
+        if let Some(emoji) = &self.favourite_emoji {
+            svg += &format!(
+                "<text x=\"184\" y=\"480\" font-size=\"28\" text-anchor=\"middle\" fill=\"#ffffff\">:{}:</text>",
+                escape_html(emoji)
+            );
+        }
+
+        let stats = [
+            (self.message_count.to_string(), "messages sent".to_string()),
+            (
+                format!("{} days", self.longest_streak),
+                "longest streak".to_string(),
+            ),
+            (
+                self.top_channel
+                    .as_ref()
+                    .map_or("-".to_string(), |channel| format!("#{}", channel)),
+                "top channel".to_string(),
+            ),
+        ];
+        for (index, (value, label)) in stats.iter().enumerate() {
+            let y = 220 + index as u32 * 120;
+            svg += &format!(
+                "<text x=\"380\" y=\"{}\" font-size=\"64\" font-weight=\"bold\" fill=\"#ecb22e\">{}</text>\
+                 <text x=\"380\" y=\"{}\" font-size=\"28\" fill=\"#d1c4d3\">{}</text>",
+                y,
+                escape_html(&truncate(value, 22)),
+                y + 40,
+                escape_html(label)
+            );
+        }
+        svg + "<text x=\"1136\" y=\"590\" font-size=\"24\" text-anchor=\"end\" fill=\"#d1c4d3\">Slackify Wrapped</text></svg>"
+    }
+}
+
+pub fn rasterise(svg: &str) -> Result<Vec<u8>, CardError> {
+    let mut options = usvg::Options {
+        font_family: FONT_FAMILY.to_string(),
+        ..Default::default()
+    };
+    for font in FONTS {
+        options.fontdb_mut().load_font_data(font.to_vec());
+    }
+    let tree = usvg::Tree::from_str(svg, &options).map_err(CardError::Svg)?;
+    let mut pixmap = tiny_skia::Pixmap::new(WIDTH, HEIGHT).ok_or(CardError::Render)?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|_| CardError::Render)
+}
+
+// Rendered cards on disk, named by the user and the card's cache key. Files
+// older than the TTL, if any, are rendered again, and deleted whenever a new
+// card is written. Cards missing their emoji image are not kept.
+pub struct CardCache {
+    pub dir: PathBuf,
+    pub ttl: Option<Duration>,
+}
+
+impl CardCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl: None,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn is_fresh(&self, path: &Path) -> bool {
+        let Ok(modified) = fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+            return false;
+        };
+        match self.ttl {
+            Some(ttl) => modified.elapsed().is_ok_and(|age| age < ttl),
+            None => true,
+        }
+    }
+
+    fn path(&self, card: &SummaryCard, extension: &str) -> PathBuf {
+        self.dir.join(format!(
+            "{}-{}.{}",
+            card.user_id,
+            card.cache_key(),
+            extension
+        ))
+    }
+
+    // The fresh cached file, `svg` or `png`
+    pub fn cached(&self, card: &SummaryCard, extension: &str) -> Option<Vec<u8>> {
+        let path = self.path(card, extension);
+        match self.is_fresh(&path) {
+            true => fs::read(&path).ok(),
+            false => None,
+        }
+    }
+
+    fn write(&self, path: &Path, content: &[u8]) -> Result<(), CardError> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, content)?;
+        self.evict_stale()?;
+        Ok(())
+    }
+
+    // Deletes the cards past the TTL, returning how many there were
+    pub fn evict_stale(&self) -> Result<usize, CardError> {
+        if self.ttl.is_none() {
+            return Ok(0);
+        }
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error.into()),
+        };
+        let mut evicted = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if self.is_fresh(&path) {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => evicted += 1,
+                // Another request got to it first
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(evicted)
+    }
+
+    // The user's cached cards, in no particular order
+    pub fn user_cards(&self, user_id: &str) -> Result<Vec<PathBuf>, CardError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        let prefix = format!("{}-", user_id);
+        let mut cards = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                cards.push(entry.path());
+            }
+        }
+        Ok(cards)
+    }
+
+    // Deletes every card of the user, returning how many there were
+    pub fn remove_user(&self, user_id: &str) -> Result<usize, CardError> {
+        let cards = self.user_cards(user_id)?;
+        for card in &cards {
+            fs::remove_file(card)?;
+        }
+        Ok(cards.len())
+    }
+
+    pub fn svg(&self, card: &SummaryCard) -> Result<String, CardError> {
+        if let Some(svg) = self.cached(card, "svg") {
+            if let Ok(svg) = String::from_utf8(svg) {
+                return Ok(svg);
+            }
+        }
+        let svg = card.svg();
+        if !card.is_missing_image() {
+            self.write(&self.path(card, "svg"), svg.as_bytes())?;
+        }
+        Ok(svg)
+    }
+
+    pub fn png(&self, card: &SummaryCard) -> Result<Vec<u8>, CardError> {
+        if let Some(png) = self.cached(card, "png") {
+            return Ok(png);
+        }
+        let png = rasterise(&card.svg())?;
+        if !card.is_missing_image() {
+            self.write(&self.path(card, "png"), &png)?;
+        }
+        Ok(png)
+    }
+}
+
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
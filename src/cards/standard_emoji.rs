@@ -0,0 +1,139 @@
+// Unicode code points of common standard emoji, by Slack short name. Slack
+// serves their images from its CDN, named after the code points.
+pub const STANDARD_EMOJI_URL: &str =
+    "https://a.slack-edge.com/production-standard-emoji-assets/14.0/google-medium";
+
+pub fn code_points(name: &str) -> Option<&'static str> {
+    STANDARD_EMOJI
+        .iter()
+        .find(|(short_name, _)| *short_name == name)
+        .map(|(_, code_points)| *code_points)
+}
+
+pub fn image_url(name: &str) -> Option<String> {
+    code_points(name).map(|code_points| format!("{}/{}.png", STANDARD_EMOJI_URL, code_points))
+}
+
+const STANDARD_EMOJI: &[(&str, &str)] = &[
+    ("+1", "1f44d"),
+    ("thumbsup", "1f44d"),
+    ("-1", "1f44e"),
+    ("thumbsdown", "1f44e"),
+    ("100", "1f4af"),
+    ("clap", "1f44f"),
+    ("pray", "1f64f"),
+    ("raised_hands", "1f64c"),
+    ("muscle", "1f4aa"),
+    ("wave", "1f44b"),
+    ("ok_hand", "1f44c"),
+    ("point_up", "261d-fe0f"),
+    ("eyes", "1f440"),
+    ("heart", "2764-fe0f"),
+    ("heart_eyes", "1f60d"),
+    ("blue_heart", "1f499"),
+    ("green_heart", "1f49a"),
+    ("purple_heart", "1f49c"),
+    ("yellow_heart", "1f49b"),
+    ("sparkling_heart", "1f496"),
+    ("joy", "1f602"),
+    ("rolling_on_the_floor_laughing", "1f923"),
+    ("laughing", "1f606"),
+    ("smile", "1f604"),
+    ("smiley", "1f603"),
+    ("grinning", "1f600"),
+    ("slightly_smiling_face", "1f642"),
+    ("wink", "1f609"),
+    ("blush", "1f60a"),
+    ("sweat_smile", "1f605"),
+    ("upside_down_face", "1f643"),
+    ("thinking_face", "1f914"),
+    ("face_with_monocle", "1f9d0"),
+    ("exploding_head", "1f92f"),
+    ("scream", "1f631"),
+    ("cry", "1f622"),
+    ("sob", "1f62d"),
+    ("disappointed", "1f61e"),
+    ("pensive", "1f614"),
+    ("grimacing", "1f62c"),
+    ("skull", "1f480"),
+    ("sunglasses", "1f60e"),
+    ("star_struck", "1f929"),
+    ("partying_face", "1f973"),
+    ("hugging_face", "1f917"),
+    ("facepalm", "1f926"),
+    ("shrug", "1f937"),
+    ("see_no_evil", "1f648"),
+    ("hear_no_evil", "1f649"),
+    ("speak_no_evil", "1f64a"),
+    ("tada", "1f389"),
+    ("confetti_ball", "1f38a"),
+    ("fire", "1f525"),
+    ("rocket", "1f680"),
+    ("star", "2b50"),
+    ("star2", "1f31f"),
+    ("sparkles", "2728"),
+    ("zap", "26a1"),
+    ("boom", "1f4a5"),
+    ("white_check_mark", "2705"),
+    ("heavy_check_mark", "2714-fe0f"),
+    ("ballot_box_with_check", "2611-fe0f"),
+    ("x", "274c"),
+    ("warning", "26a0-fe0f"),
+    ("no_entry", "26d4"),
+    ("question", "2753"),
+    ("exclamation", "2757"),
+    ("bangbang", "203c-fe0f"),
+    ("memo", "1f4dd"),
+    ("bulb", "1f4a1"),
+    ("mag", "1f50d"),
+    ("link", "1f517"),
+    ("pushpin", "1f4cc"),
+    ("calendar", "1f4c6"),
+    ("hourglass", "231b"),
+    ("stopwatch", "23f1-fe0f"),
+    ("trophy", "1f3c6"),
+    ("medal", "1f3c5"),
+    ("dart", "1f3af"),
+    ("gift", "1f381"),
+    ("balloon", "1f388"),
+    ("birthday", "1f382"),
+    ("coffee", "2615"),
+    ("beer", "1f37a"),
+    ("beers", "1f37b"),
+    ("pizza", "1f355"),
+    ("taco", "1f32e"),
+    ("cake", "1f370"),
+    ("cookie", "1f36a"),
+    ("sun_with_face", "1f31e"),
+    ("rainbow", "1f308"),
+    ("snowflake", "2744-fe0f"),
+    ("ship", "1f6a2"),
+    ("rotating_light", "1f6a8"),
+    ("bug", "1f41b"),
+    ("robot_face", "1f916"),
+    ("computer", "1f4bb"),
+    ("wrench", "1f527"),
+    ("hammer_and_wrench", "1f6e0-fe0f"),
+    ("gear", "2699-fe0f"),
+    ("lock", "1f512"),
+    ("key", "1f511"),
+    ("chart_with_upwards_trend", "1f4c8"),
+    ("chart_with_downwards_trend", "1f4c9"),
+    ("moneybag", "1f4b0"),
+    ("crown", "1f451"),
+    ("gem", "1f48e"),
+    ("unicorn_face", "1f984"),
+    ("dog", "1f436"),
+    ("cat", "1f431"),
+    ("panda_face", "1f43c"),
+    ("monkey_face", "1f435"),
+    ("wave_dash", "3030-fe0f"),
+    ("heavy_plus_sign", "2795"),
+    ("arrow_up", "2b06-fe0f"),
+    ("arrow_right", "27a1-fe0f"),
+    ("raising_hand", "1f64b"),
+    ("woman-raising-hand", "1f64b-200d-2640-fe0f"),
+    ("man-raising-hand", "1f64b-200d-2642-fe0f"),
+    ("saluting_face", "1fae1"),
+    ("melting_face", "1fae0"),
+];
@@ -0,0 +1,395 @@
+// Settings for the whole service, read once at launch. Values come from, in
+// increasing priority:
+//
+// - `Rocket.toml`, next to Rocket's own settings and with the same profiles
+// - a secrets file, `Secrets.toml` unless `SLACKIFY_SECRETS_FILE` says otherwise
+// - the variables this service used to read, like `SLACK_TOKEN`
+// - `SLACKIFY_` variables, with `__` between nested keys, for example
+//   `SLACKIFY_RATE_LIMIT__MAX_RETRIES=5`
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Url;
+use rocket::figment::providers::{Env, Format, Toml};
+use rocket::figment::Figment;
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+
+const DEFAULT_SECRETS_FILE: &str = "Secrets.toml";
+
+const DEFAULT_BOT_SCOPES: &str = "channels:history,channels:read,groups:history,groups:read,\
+    im:history,mpim:history,reactions:read,users:read,emoji:read,chat:write";
+const DEFAULT_USER_SCOPES: &str = "channels:history,groups:history,im:history,mpim:history";
+
+// Earlier versions were configured with these variables alone
+const LEGACY_VARIABLES: [(&str, &str); 11] = [
+    ("SLACK_TOKEN", "slack.token"),
+    ("SLACK_CLIENT_ID", "slack.client_id"),
+    ("SLACK_CLIENT_SECRET", "slack.client_secret"),
+    ("SLACK_SIGNING_SECRET", "slack.signing_secret"),
+    ("SLACK_REDIRECT_URI", "slack.install_redirect_uri"),
+    ("SLACK_SIGN_IN_REDIRECT_URI", "slack.sign_in_redirect_uri"),
+    ("SLACK_BOT_SCOPES", "slack.bot_scopes"),
+    ("SLACK_USER_SCOPES", "slack.user_scopes"),
+    ("BASE_URL", "base_url"),
+    ("TOKEN_STORE_PATH", "token_store_path"),
+    ("CARD_CACHE_DIR", "card_cache_dir"),
+];
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub slack: SlackConfig,
+    // Where the service is reachable, for links and images sent to Slack
+    pub base_url: Option<String>,
+    pub token_store_path: PathBuf,
+    pub database_path: PathBuf,
+    pub card_cache_dir: PathBuf,
+    // Signs the card URLs sent to Slack, which fetches them without signing
+    // in. A random one is made at launch when unset, and links made before
+    // a restart stop working.
+    pub card_secret: Option<String>,
+    pub cache: CacheConfig,
+    pub http: HttpConfig,
+    pub rate_limit: RateLimitConfig,
+    pub jobs: JobsConfig,
+    pub privacy: PrivacyConfig,
+    pub events: EventsConfig,
+    pub emoji: EmojiConfig,
+    pub features: FeatureToggles,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SlackConfig {
+    // For requests that name no workspace, such as when none has installed
+    // the app
+    pub token: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    // Checks that slash commands and events really come from Slack
+    pub signing_secret: Option<String>,
+    // App-level token (xapp-), for receiving events over Socket Mode
+    pub app_token: Option<String>,
+    pub install_redirect_uri: Option<String>,
+    pub sign_in_redirect_uri: Option<String>,
+    // Comma separated, as Slack expects them
+    pub bot_scopes: String,
+    pub user_scopes: String,
+    // Where the share button of a DM'd wrapped posts, by name
+    pub share_channel: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    // How long a computed wrapped is reused before crawling Slack again
+    pub wrapped_ttl_secs: u64,
+    // Rendered cards older than this are drawn again
+    pub card_ttl_secs: u64,
+}
+
+// The HTTP client shared by all calls to Slack
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub user_agent: String,
+    // For a whole request, including reading the response
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    pub pool_idle_timeout_secs: u64,
+    pub pool_max_idle_per_host: usize,
+}
+
+// How to behave when Slack answers 429 Too Many Requests
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub max_retries: u32,
+    // Longer `Retry-After` waits than this fail the request instead
+    pub max_wait_secs: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct JobsConfig {
+    // Wrapped computations running at the same time
+    pub workers: usize,
+}
+
+// Limits on what workspace-wide stats reveal about individuals
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PrivacyConfig {
+    // Channels and emoji need this many different people behind them to be listed
+    pub min_group_size: usize,
+    // Reactions a person or message needs to make a leaderboard
+    pub min_leaderboard_count: u32,
+}
+
+// How events reach the service
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventsTransport {
+    // Slack posts them to /slack/events, which must be reachable from the
+    // internet
+    #[default]
+    Http,
+    // The service connects out to Slack over a WebSocket
+    Socket,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EventsConfig {
+    pub transport: EventsTransport,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct EmojiConfig {
+    // How often each workspace's custom emoji are listed, to see what changed
+    pub snapshot_interval_secs: u64,
+}
+
+// Route groups that can be switched off
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FeatureToggles {
+    pub api: bool,
+    pub story: bool,
+    pub cards: bool,
+    pub dm_wrapped: bool,
+    pub text_reports: bool,
+    pub install: bool,
+    // The /wrapped slash command at /slack/commands
+    pub commands: bool,
+    // Receiving the Events API, over the transport in `events`
+    pub events: bool,
+    // Emoji snapshots, /emoji-timeline and the /emoji-usage report
+    pub emoji: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            slack: SlackConfig::default(),
+            base_url: None,
+            token_store_path: PathBuf::from("data/installations.json"),
+            database_path: PathBuf::from("data/slackify.db"),
+            card_cache_dir: PathBuf::from("cache/cards"),
+            card_secret: None,
+            cache: CacheConfig::default(),
+            http: HttpConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            jobs: JobsConfig::default(),
+            privacy: PrivacyConfig::default(),
+            events: EventsConfig::default(),
+            emoji: EmojiConfig::default(),
+            features: FeatureToggles::default(),
+        }
+    }
+}
+
+impl Default for SlackConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            client_id: None,
+            client_secret: None,
+            signing_secret: None,
+            app_token: None,
+            install_redirect_uri: None,
+            sign_in_redirect_uri: None,
+            bot_scopes: DEFAULT_BOT_SCOPES.to_string(),
+            user_scopes: DEFAULT_USER_SCOPES.to_string(),
+            share_channel: "random".to_string(),
+        }
+    }
+}
+
+impl CacheConfig {
+    // Wrapped computed since then are still used
+    pub fn fresh_since(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::seconds(self.wrapped_ttl_secs as i64)
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            wrapped_ttl_secs: 6 * 60 * 60,
+            card_ttl_secs: 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: format!("slackify-wrapped/{}", env!("CARGO_PKG_VERSION")),
+            timeout_secs: 30,
+            connect_timeout_secs: 10,
+            pool_idle_timeout_secs: 90,
+            pool_max_idle_per_host: 16,
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            max_wait_secs: 60,
+        }
+    }
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self { workers: 2 }
+    }
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            min_group_size: 3,
+            min_leaderboard_count: 5,
+        }
+    }
+}
+
+impl Default for EmojiConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_interval_secs: 6 * 60 * 60,
+        }
+    }
+}
+
+impl Default for FeatureToggles {
+    fn default() -> Self {
+        Self {
+            api: true,
+            story: true,
+            cards: true,
+            dm_wrapped: true,
+            text_reports: true,
+            install: true,
+            commands: true,
+            events: true,
+            emoji: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl AppConfig {
+    // Rocket's own figment, with this service's sources merged on top
+    pub fn figment() -> Figment {
+        let secrets_file =
+            std::env::var("SLACKIFY_SECRETS_FILE").unwrap_or(DEFAULT_SECRETS_FILE.to_string());
+        rocket::Config::figment()
+            .merge(Toml::file(secrets_file).nested())
+            .merge(Env::raw().filter_map(|key| {
+                LEGACY_VARIABLES
+                    .iter()
+                    .find(|(variable, _)| key == *variable)
+                    .map(|(_, path)| (*path).into())
+            }))
+            .merge(Env::prefixed("SLACKIFY_").split("__").global())
+    }
+
+    pub fn from_figment(figment: &Figment) -> Result<Self, ConfigError> {
+        let config: AppConfig = figment.extract().map_err(|errors| ConfigError {
+            problems: errors.into_iter().map(|error| error.to_string()).collect(),
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let urls = [
+            ("base_url", &self.base_url),
+            (
+                "slack.install_redirect_uri",
+                &self.slack.install_redirect_uri,
+            ),
+            (
+                "slack.sign_in_redirect_uri",
+                &self.slack.sign_in_redirect_uri,
+            ),
+        ];
+        for (key, url) in urls {
+            let Some(url) = url else {
+                continue;
+            };
+            match Url::parse(url) {
+                Ok(url) if ["http", "https"].contains(&url.scheme()) => {}
+                _ => problems.push(format!("{} must be an http(s) URL, got {:?}", key, url)),
+            }
+        }
+        if self.slack.client_id.is_some() != self.slack.client_secret.is_some() {
+            problems
+                .push("slack.client_id and slack.client_secret must be set together".to_string());
+        }
+        if self.slack.bot_scopes.trim().is_empty() {
+            problems.push("slack.bot_scopes must not be empty".to_string());
+        }
+        if self.http.timeout_secs == 0 || self.http.connect_timeout_secs == 0 {
+            problems.push("http timeouts must be at least 1 second".to_string());
+        }
+        if self.rate_limit.max_wait_secs == 0 {
+            problems.push("rate_limit.max_wait_secs must be at least 1".to_string());
+        }
+        if self.privacy.min_group_size < 2 {
+            problems.push("privacy.min_group_size must be at least 2".to_string());
+        }
+        if self.features.events && self.events.transport == EventsTransport::Socket {
+            match &self.slack.app_token {
+                Some(token) if token.starts_with("xapp-") => {}
+                Some(_) => problems.push("slack.app_token must be an xapp- token".to_string()),
+                None => {
+                    problems.push("events.transport = \"socket\" needs slack.app_token".to_string())
+                }
+            }
+        }
+        if self.emoji.snapshot_interval_secs < 60 {
+            problems.push("emoji.snapshot_interval_secs must be at least 60".to_string());
+        }
+        if self.jobs.workers == 0 {
+            problems.push("jobs.workers must be at least 1".to_string());
+        }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError { problems }),
+        }
+    }
+
+    pub fn oauth(&self) -> Option<OauthCredentials> {
+        Some(OauthCredentials {
+            client_id: self.slack.client_id.clone()?,
+            client_secret: self.slack.client_secret.clone()?,
+        })
+    }
+}
+
+pub struct OauthCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
@@ -0,0 +1,241 @@
+// Who has opted out of Slackify Wrapped, and every change to that, per
+// workspace. Kept in SQLite next to the jobs.
+use crate::auth::Session;
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS consent_opt_outs (
+        team_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        opted_out_at TEXT NOT NULL,
+        PRIMARY KEY (team_id, user_id)
+    );
+    CREATE TABLE IF NOT EXISTS consent_audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        team_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        action TEXT NOT NULL,
+        actor TEXT NOT NULL,
+        source TEXT NOT NULL,
+        at TEXT NOT NULL
+    );
+";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsentAction {
+    OptOut,
+    OptIn,
+}
+
+// Where a change came from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsentSource {
+    Web,
+    SlashCommand,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct ConsentChange {
+    pub user_id: String,
+    pub action: ConsentAction,
+    // Who made the change, the user themselves unless an admin did
+    pub actor: String,
+    pub source: ConsentSource,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum ConsentError {
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for ConsentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsentError::Io(error) => write!(f, "consent registry error: {}", error),
+            ConsentError::Sqlite(error) => write!(f, "consent registry error: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for ConsentError {
+    fn from(error: io::Error) -> Self {
+        ConsentError::Io(error)
+    }
+}
+
+impl From<rusqlite::Error> for ConsentError {
+    fn from(error: rusqlite::Error) -> Self {
+        ConsentError::Sqlite(error)
+    }
+}
+
+impl ConsentAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConsentAction::OptOut => "opt_out",
+            ConsentAction::OptIn => "opt_in",
+        }
+    }
+}
+
+impl ConsentSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConsentSource::Web => "web",
+            ConsentSource::SlashCommand => "slash_command",
+        }
+    }
+}
+
+pub struct ConsentRegistry {
+    connection: Mutex<Connection>,
+}
+
+impl ConsentRegistry {
+    pub fn open(path: &Path) -> Result<Self, ConsentError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn is_opted_out(&self, team_id: &str, user_id: &str) -> Result<bool, ConsentError> {
+        let connection = self.connection.lock().unwrap();
+        let found = connection
+            .query_row(
+                "SELECT 1 FROM consent_opt_outs WHERE team_id = ?1 AND user_id = ?2",
+                [team_id, user_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    // For requests that do not know the workspace, such as cards. Slack
+    // user IDs do not repeat across workspaces.
+    pub fn opted_out_anywhere(&self, user_id: &str) -> Result<bool, ConsentError> {
+        let connection = self.connection.lock().unwrap();
+        let found = connection
+            .query_row(
+                "SELECT 1 FROM consent_opt_outs WHERE user_id = ?1 LIMIT 1",
+                [user_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    pub fn opted_out(&self, team_id: &str) -> Result<HashSet<String>, ConsentError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare("SELECT user_id FROM consent_opt_outs WHERE team_id = ?1")?;
+        let users = statement
+            .query_map([team_id], |row| row.get(0))?
+            .collect::<Result<HashSet<String>, _>>()?;
+        Ok(users)
+    }
+
+    // Records the change, returning false when it changes nothing. Only
+    // actual changes are logged.
+    pub fn set(&self, team_id: &str, change: &ConsentChange) -> Result<bool, ConsentError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let changed = match change.action {
+            ConsentAction::OptOut => transaction.execute(
+                "INSERT OR IGNORE INTO consent_opt_outs (team_id, user_id, opted_out_at)
+                    VALUES (?1, ?2, ?3)",
+                params![team_id, change.user_id, change.at],
+            )?,
+            ConsentAction::OptIn => transaction.execute(
+                "DELETE FROM consent_opt_outs WHERE team_id = ?1 AND user_id = ?2",
+                params![team_id, change.user_id],
+            )?,
+        };
+        if changed > 0 {
+            transaction.execute(
+                "INSERT INTO consent_audit_log (team_id, user_id, action, actor, source, at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    team_id,
+                    change.user_id,
+                    change.action.as_str(),
+                    change.actor,
+                    change.source.as_str(),
+                    change.at
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(changed > 0)
+    }
+
+    // Every change in the workspace, oldest first
+    pub fn audit_log(&self, team_id: &str) -> Result<Vec<ConsentChange>, ConsentError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT user_id, action, actor, source, at FROM consent_audit_log
+                WHERE team_id = ?1 ORDER BY id",
+        )?;
+        let changes = statement
+            .query_map([team_id], |row| {
+                let action: String = row.get(1)?;
+                let source: String = row.get(3)?;
+                Ok(ConsentChange {
+                    user_id: row.get(0)?,
+                    action: match action.as_str() {
+                        "opt_in" => ConsentAction::OptIn,
+                        _ => ConsentAction::OptOut,
+                    },
+                    actor: row.get(2)?,
+                    source: match source.as_str() {
+                        "slash_command" => ConsentSource::SlashCommand,
+                        _ => ConsentSource::Web,
+                    },
+                    at: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(changes)
+    }
+
+    // The people to show anonymously in the signed in workspace
+    pub fn anonymised(&self, session: &Session) -> Result<HashSet<String>, Status> {
+        self.opted_out(&session.team_id.0).map_err(|error| {
+            println!("Encountered error: {}", error);
+            Status::InternalServerError
+        })
+    }
+
+    // Stats about someone who opted out are only for themselves
+    pub fn authorize(&self, session: &Session, user_id: &str) -> Result<(), Status> {
+        if session.user_id.0 == user_id {
+            return Ok(());
+        }
+        match self.is_opted_out(&session.team_id.0, user_id) {
+            Ok(false) => Ok(()),
+            Ok(true) => Err(Status::Forbidden),
+            Err(error) => {
+                println!("Encountered error: {}", error);
+                Err(Status::InternalServerError)
+            }
+        }
+    }
+}
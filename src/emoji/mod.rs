@@ -0,0 +1,420 @@
+// When each custom emoji was added, renamed and removed. Slack keeps no such
+// history, so it is pieced together from snapshots of emoji.list, each
+// diffed against the one before, and from emoji_changed events, which say
+// exactly when a change happened if the Events API is set up.
+pub mod snapshots;
+pub mod usage;
+
+use chrono::{DateTime, Datelike, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS emoji_snapshots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        team_id TEXT NOT NULL,
+        cache_ts TEXT NOT NULL,
+        taken_at TEXT NOT NULL,
+        emoji TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS emoji_snapshot_changes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        team_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        name TEXT NOT NULL,
+        old_name TEXT,
+        value TEXT,
+        since TEXT NOT NULL,
+        seen_at TEXT NOT NULL
+    );
+";
+
+#[derive(Debug)]
+pub enum EmojiStoreError {
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for EmojiStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmojiStoreError::Io(error) => write!(f, "emoji store error: {}", error),
+            EmojiStoreError::Sqlite(error) => write!(f, "emoji store error: {}", error),
+            EmojiStoreError::Json(error) => write!(f, "invalid emoji snapshot: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for EmojiStoreError {
+    fn from(error: io::Error) -> Self {
+        EmojiStoreError::Io(error)
+    }
+}
+
+impl From<rusqlite::Error> for EmojiStoreError {
+    fn from(error: rusqlite::Error) -> Self {
+        EmojiStoreError::Sqlite(error)
+    }
+}
+
+impl From<serde_json::Error> for EmojiStoreError {
+    fn from(error: serde_json::Error) -> Self {
+        EmojiStoreError::Json(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmojiChangeKind {
+    Added,
+    Removed,
+    Renamed,
+}
+
+impl EmojiChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmojiChangeKind::Added => "added",
+            EmojiChangeKind::Removed => "removed",
+            EmojiChangeKind::Renamed => "renamed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "added" => Some(EmojiChangeKind::Added),
+            "removed" => Some(EmojiChangeKind::Removed),
+            "renamed" => Some(EmojiChangeKind::Renamed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeSource {
+    Snapshot,
+    Event,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EmojiChange {
+    pub kind: EmojiChangeKind,
+    // The new name, for renames
+    pub name: String,
+    pub old_name: Option<String>,
+    // The image URL, or `alias:name` for aliases
+    pub value: Option<String>,
+    // When it happened, or for snapshots when it was first seen
+    pub at: DateTime<Utc>,
+    // For snapshots, the one before: the change happened in between
+    pub since: Option<DateTime<Utc>>,
+    pub source: ChangeSource,
+}
+
+// What changed from one emoji map to the next. A name that went away while
+// another came with the same image was renamed.
+pub fn diff(
+    previous: &HashMap<String, String>,
+    current: &HashMap<String, String>,
+    since: DateTime<Utc>,
+    at: DateTime<Utc>,
+) -> Vec<EmojiChange> {
+    let change = |kind, name: &str, old_name: Option<&str>, value: &str| EmojiChange {
+        kind,
+        name: name.to_string(),
+        old_name: old_name.map(str::to_string),
+        value: Some(value.to_string()),
+        at,
+        since: Some(since),
+        source: ChangeSource::Snapshot,
+    };
+    let mut removed: Vec<(&String, &String)> = previous
+        .iter()
+        .filter(|(name, _)| !current.contains_key(*name))
+        .collect();
+    let mut added: Vec<(&String, &String)> = current
+        .iter()
+        .filter(|(name, _)| !previous.contains_key(*name))
+        .collect();
+    removed.sort();
+    added.sort();
+
+    let mut changes = Vec::new();
+    let mut renamed = Vec::new();
+    for (old_name, value) in &removed {
+        let same_image = added
+            .iter()
+            .find(|(name, new_value)| new_value == value && !renamed.contains(name));
+        if let Some((name, _)) = same_image {
+            renamed.push(*name);
+            changes.push(change(
+                EmojiChangeKind::Renamed,
+                name,
+                Some(old_name),
+                value,
+            ));
+        } else {
+            changes.push(change(EmojiChangeKind::Removed, old_name, None, value));
+        }
+    }
+    for (name, value) in &added {
+        if !renamed.contains(name) {
+            changes.push(change(EmojiChangeKind::Added, name, None, value));
+        }
+    }
+    changes
+}
+
+// An emoji that was added and later removed, under the name it had last
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EmojiLifetime {
+    pub name: String,
+    pub added_at: DateTime<Utc>,
+    pub removed_at: DateTime<Utc>,
+    pub lifetime_secs: i64,
+}
+
+// Every change known for a workspace, oldest first
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct EmojiTimeline {
+    // When tracking started, nothing before is known
+    pub tracked_since: Option<DateTime<Utc>>,
+    pub changes: Vec<EmojiChange>,
+}
+
+impl EmojiTimeline {
+    // Snapshots only tell the window a change happened in, so a change that
+    // an event in that window already explains is dropped
+    pub fn new(
+        snapshot_changes: Vec<EmojiChange>,
+        event_changes: Vec<EmojiChange>,
+        first_snapshot: Option<DateTime<Utc>>,
+    ) -> Self {
+        let explained = |change: &EmojiChange| {
+            event_changes.iter().any(|event| {
+                event.kind == change.kind
+                    && event.name == change.name
+                    && event.at <= change.at
+                    && change.since.is_none_or(|since| event.at > since)
+            })
+        };
+        let mut changes: Vec<EmojiChange> = snapshot_changes
+            .iter()
+            .filter(|change| !explained(change))
+            .cloned()
+            .collect();
+        let tracked_since = first_snapshot
+            .into_iter()
+            .chain(event_changes.iter().map(|change| change.at))
+            .min();
+        changes.extend(event_changes);
+        changes.sort_by(|a, b| a.at.cmp(&b.at).then(a.name.cmp(&b.name)));
+        Self {
+            tracked_since,
+            changes,
+        }
+    }
+
+    pub fn in_year(&self, year: i32) -> impl Iterator<Item = &EmojiChange> + '_ {
+        self.changes
+            .iter()
+            .filter(move |change| change.at.year() == year)
+    }
+
+    // None when tracking had not started by the end of the year. Counts
+    // from when it started otherwise.
+    pub fn added_in(&self, year: i32) -> Option<u32> {
+        self.tracked_since.filter(|since| since.year() <= year)?;
+        Some(
+            self.in_year(year)
+                .filter(|change| change.kind == EmojiChangeKind::Added)
+                .count() as u32,
+        )
+    }
+
+    // Emoji seen being both added and removed, following renames
+    pub fn lifetimes(&self) -> Vec<EmojiLifetime> {
+        let mut alive: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        let mut lifetimes = Vec::new();
+        for change in &self.changes {
+            match change.kind {
+                EmojiChangeKind::Added => {
+                    alive.insert(&change.name, change.at);
+                }
+                EmojiChangeKind::Renamed => {
+                    let old_name = change.old_name.as_deref().unwrap_or_default();
+                    if let Some(added_at) = alive.remove(old_name) {
+                        alive.insert(&change.name, added_at);
+                    }
+                }
+                EmojiChangeKind::Removed => {
+                    if let Some(added_at) = alive.remove(change.name.as_str()) {
+                        lifetimes.push(EmojiLifetime {
+                            name: change.name.clone(),
+                            added_at,
+                            removed_at: change.at,
+                            lifetime_secs: (change.at - added_at).num_seconds(),
+                        });
+                    }
+                }
+            }
+        }
+        lifetimes
+    }
+
+    // Shortest first, of the emoji removed within the year
+    pub fn shortest_lived(&self, year: i32, count: usize) -> Vec<EmojiLifetime> {
+        let mut lifetimes: Vec<EmojiLifetime> = self
+            .lifetimes()
+            .into_iter()
+            .filter(|lifetime| lifetime.removed_at.year() == year)
+            .collect();
+        lifetimes.sort_by(|a, b| {
+            a.lifetime_secs
+                .cmp(&b.lifetime_secs)
+                .then(a.name.cmp(&b.name))
+        });
+        lifetimes.truncate(count);
+        lifetimes
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmojiSnapshot {
+    pub cache_ts: String,
+    pub taken_at: DateTime<Utc>,
+    pub emoji: HashMap<String, String>,
+}
+
+pub struct EmojiStore {
+    connection: Mutex<Connection>,
+}
+
+impl EmojiStore {
+    pub fn open(path: &Path) -> Result<Self, EmojiStoreError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn latest(&self, team_id: &str) -> Result<Option<EmojiSnapshot>, EmojiStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let row: Option<(String, DateTime<Utc>, String)> = connection
+            .query_row(
+                "SELECT cache_ts, taken_at, emoji FROM emoji_snapshots
+                    WHERE team_id = ?1 ORDER BY id DESC LIMIT 1",
+                [team_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        match row {
+            Some((cache_ts, taken_at, emoji)) => Ok(Some(EmojiSnapshot {
+                cache_ts,
+                taken_at,
+                emoji: serde_json::from_str(&emoji)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    pub fn first_snapshot(&self, team_id: &str) -> Result<Option<DateTime<Utc>>, EmojiStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let taken_at = connection.query_row(
+            "SELECT MIN(taken_at) FROM emoji_snapshots WHERE team_id = ?1",
+            [team_id],
+            |row| row.get(0),
+        )?;
+        Ok(taken_at)
+    }
+
+    // Keeps the emoji map and what changed since the last one, returning
+    // the changes. A map with the same cache_ts as the last is not kept, and
+    // the first one only sets the baseline.
+    pub fn record(
+        &self,
+        team_id: &str,
+        cache_ts: &str,
+        emoji: &HashMap<String, String>,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<EmojiChange>, EmojiStoreError> {
+        let previous = self.latest(team_id)?;
+        if previous
+            .as_ref()
+            .is_some_and(|previous| previous.cache_ts == cache_ts)
+        {
+            return Ok(Vec::new());
+        }
+        let changes = match &previous {
+            Some(previous) => diff(&previous.emoji, emoji, previous.taken_at, now),
+            None => Vec::new(),
+        };
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO emoji_snapshots (team_id, cache_ts, taken_at, emoji)
+                VALUES (?1, ?2, ?3, ?4)",
+            params![team_id, cache_ts, now, serde_json::to_string(emoji)?],
+        )?;
+        for change in &changes {
+            transaction.execute(
+                "INSERT INTO emoji_snapshot_changes
+                    (team_id, kind, name, old_name, value, since, seen_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    team_id,
+                    change.kind.as_str(),
+                    change.name,
+                    change.old_name,
+                    change.value,
+                    change.since,
+                    change.at
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(changes)
+    }
+
+    // The changes seen between snapshots, oldest first
+    pub fn changes(&self, team_id: &str) -> Result<Vec<EmojiChange>, EmojiStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT kind, name, old_name, value, since, seen_at FROM emoji_snapshot_changes
+                WHERE team_id = ?1 ORDER BY id",
+        )?;
+        let changes = statement
+            .query_map([team_id], |row| {
+                let kind: String = row.get(0)?;
+                Ok(EmojiChange {
+                    kind: EmojiChangeKind::parse(&kind).ok_or_else(|| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            0,
+                            rusqlite::types::Type::Text,
+                            format!("unknown value {}", kind).into(),
+                        )
+                    })?,
+                    name: row.get(1)?,
+                    old_name: row.get(2)?,
+                    value: row.get(3)?,
+                    since: row.get(4)?,
+                    at: row.get(5)?,
+                    source: ChangeSource::Snapshot,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(changes)
+    }
+}
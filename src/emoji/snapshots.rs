@@ -0,0 +1,117 @@
+// Lists every installed workspace's custom emoji every few hours and keeps
+// what changed. Snapshots catch changes made while no events were arriving,
+// at the cost of only knowing roughly when they happened.
+use super::{EmojiChange, EmojiStore, EmojiStoreError};
+use crate::config::AppConfig;
+use crate::slack::client::{SlackClient, SlackClients};
+use crate::slack::emoji::EmojiListResponse;
+use crate::workspaces::TokenStore;
+use chrono::{DateTime, Utc};
+use rocket::fairing::AdHoc;
+use rocket::tokio::select;
+use rocket::tokio::time::sleep;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Http(reqwest::Error),
+    // emoji.list answered with an error, such as missing_scope
+    Slack(String),
+    Store(EmojiStoreError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Http(error) => write!(f, "could not list emoji: {}", error),
+            SnapshotError::Slack(error) => write!(f, "could not list emoji: {}", error),
+            SnapshotError::Store(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for SnapshotError {
+    fn from(error: reqwest::Error) -> Self {
+        SnapshotError::Http(error)
+    }
+}
+
+impl From<EmojiStoreError> for SnapshotError {
+    fn from(error: EmojiStoreError) -> Self {
+        SnapshotError::Store(error)
+    }
+}
+
+pub async fn take_snapshot(
+    client: &SlackClient,
+    store: &EmojiStore,
+    team_id: &str,
+    now: DateTime<Utc>,
+) -> Result<Vec<EmojiChange>, SnapshotError> {
+    match client.emoji().list(None).await? {
+        EmojiListResponse::Success(list) => {
+            Ok(store.record(team_id, &list.cache_ts, &list.emoji, now)?)
+        }
+        EmojiListResponse::Error(error) => Err(SnapshotError::Slack(error.error)),
+    }
+}
+
+// One snapshot of each installed workspace. Installations are read again
+// each time, so workspaces installed since are picked up. The fallback token
+// is left out, its workspace is not known.
+async fn snapshot_all(config: &AppConfig, clients: &SlackClients, store: &EmojiStore) {
+    let installations = match TokenStore::open(&config.token_store_path) {
+        Ok(tokens) => tokens.all(),
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return;
+        }
+    };
+    for installation in installations {
+        let client = clients.get(&installation.bot_token);
+        if let Err(error) = take_snapshot(&client, store, &installation.team_id, Utc::now()).await {
+            println!(
+                "Emoji snapshot of {} failed: {}",
+                installation.team_id, error
+            );
+        }
+    }
+}
+
+// Takes snapshots once the server is up, until it shuts down. They are
+// written through a connection of its own to the database.
+pub fn schedule() -> AdHoc {
+    AdHoc::on_liftoff("Emoji snapshots", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(clients)) =
+                (rocket.state::<AppConfig>(), rocket.state::<SlackClients>())
+            else {
+                return;
+            };
+            let store = match EmojiStore::open(&config.database_path) {
+                Ok(store) => store,
+                Err(error) => {
+                    println!("Encountered error: {}", error);
+                    return;
+                }
+            };
+            let config = config.clone();
+            let clients = clients.clone();
+            let interval = Duration::from_secs(config.emoji.snapshot_interval_secs);
+            let shutdown = rocket.shutdown();
+            rocket::tokio::spawn(async move {
+                let snapshots = async {
+                    loop {
+                        snapshot_all(&config, &clients, &store).await;
+                        sleep(interval).await;
+                    }
+                };
+                select! {
+                    _ = snapshots => {}
+                    _ = shutdown => {}
+                }
+            });
+        })
+    })
+}
@@ -0,0 +1,244 @@
+// How much each custom emoji was used over the year, as a reaction or in
+// message text, and by whom. For admins tidying up the emoji list: the ones
+// nobody used are candidates for removal.
+use crate::features::history::YearHistory;
+use crate::mrkdwn::parser::parse;
+use crate::mrkdwn::Node;
+use crate::slack::util::parse_slack_ts;
+use chrono::{DateTime, Datelike};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+// People listed under each emoji
+const TOP_USERS: usize = 5;
+// Entries in the rising and falling lists
+const TRENDS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EmojiUsage {
+    pub name: String,
+    // The image URL
+    pub url: String,
+    pub reactions: u32,
+    pub in_text: u32,
+    // Uses in each month, January first
+    pub monthly: [u32; 12],
+    // Who used it most, people who opted out are left out
+    pub top_users: Vec<(String, u32)>,
+}
+
+impl EmojiUsage {
+    pub fn total(&self) -> u32 {
+        self.reactions + self.in_text
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EmojiTrend {
+    pub name: String,
+    pub previous: u32,
+    pub current: u32,
+}
+
+impl EmojiTrend {
+    pub fn change(&self) -> i64 {
+        self.current as i64 - self.previous as i64
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EmojiUsageReport {
+    pub year: i32,
+    // The month compared with the one before for the trends, 1 to 12
+    pub month: Option<u32>,
+    // Every custom emoji, most used first
+    pub emoji: Vec<EmojiUsage>,
+    pub never_used: Vec<String>,
+    pub rising: Vec<EmojiTrend>,
+    pub falling: Vec<EmojiTrend>,
+}
+
+// Emoji written in the text, including inside formatting
+fn emoji_in(nodes: &[Node], names: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Node::Emoji(name) => names.push(name.clone()),
+            Node::Bold(children)
+            | Node::Italic(children)
+            | Node::Strike(children)
+            | Node::Quote(children) => emoji_in(children, names),
+            _ => {}
+        }
+    }
+}
+
+// `thumbsup::skin-tone-2` is a use of `thumbsup`
+fn base_name(name: &str) -> &str {
+    name.split("::").next().unwrap_or(name)
+}
+
+impl EmojiUsageReport {
+    // `custom` is emoji.list's map. Aliases count towards the emoji they
+    // point at rather than being listed themselves.
+    pub fn compute(
+        history: &YearHistory,
+        custom: &HashMap<String, String>,
+        opted_out: &HashSet<String>,
+        month: Option<u32>,
+    ) -> Self {
+        let canonical = |name: &str| -> Option<String> {
+            let name = base_name(name);
+            match custom.get(name)?.strip_prefix("alias:") {
+                Some(target) => custom
+                    .get(target)
+                    .filter(|value| !value.starts_with("alias:"))
+                    .map(|_| target.to_string()),
+                None => Some(name.to_string()),
+            }
+        };
+        let mut usage: HashMap<String, EmojiUsage> = custom
+            .iter()
+            .filter(|(_, value)| !value.starts_with("alias:"))
+            .map(|(name, url)| {
+                let usage = EmojiUsage {
+                    name: name.clone(),
+                    url: url.clone(),
+                    reactions: 0,
+                    in_text: 0,
+                    monthly: [0; 12],
+                    top_users: Vec::new(),
+                };
+                (name.clone(), usage)
+            })
+            .collect();
+        let mut users: HashMap<String, HashMap<&str, u32>> = HashMap::new();
+
+        for (_, message) in history.messages_in_year(0) {
+            let Some(posted) =
+                parse_slack_ts(&message.message.ts).and_then(|ts| DateTime::from_timestamp(ts, 0))
+            else {
+                continue;
+            };
+            let month = posted.month0() as usize;
+            for reaction in &message.message.reactions {
+                let Some(name) = canonical(&reaction.name) else {
+                    continue;
+                };
+                let Some(entry) = usage.get_mut(&name) else {
+                    continue;
+                };
+                let count = reaction.count.max(0) as u32;
+                entry.reactions += count;
+                entry.monthly[month] += count;
+                let counts = users.entry(name).or_default();
+                for user in &reaction.users {
+                    *counts.entry(user.as_str()).or_default() += 1;
+                }
+            }
+            let mut written = Vec::new();
+            emoji_in(&parse(&message.message.text), &mut written);
+            for name in written {
+                let Some(name) = canonical(&name) else {
+                    continue;
+                };
+                let Some(entry) = usage.get_mut(&name) else {
+                    continue;
+                };
+                entry.in_text += 1;
+                entry.monthly[month] += 1;
+                let author = message.message.user.as_str();
+                if !author.is_empty() {
+                    *users.entry(name).or_default().entry(author).or_default() += 1;
+                }
+            }
+        }
+
+        for (name, counts) in users {
+            let Some(entry) = usage.get_mut(&name) else {
+                continue;
+            };
+            let mut top: Vec<(String, u32)> = counts
+                .into_iter()
+                .filter(|(user, _)| !opted_out.contains(*user))
+                .map(|(user, count)| (user.to_string(), count))
+                .collect();
+            top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            top.truncate(TOP_USERS);
+            entry.top_users = top;
+        }
+
+        let mut emoji: Vec<EmojiUsage> = usage.into_values().collect();
+        emoji.sort_by(|a, b| b.total().cmp(&a.total()).then(a.name.cmp(&b.name)));
+        let mut never_used: Vec<String> = emoji
+            .iter()
+            .filter(|usage| usage.total() == 0)
+            .map(|usage| usage.name.clone())
+            .collect();
+        never_used.sort();
+
+        // January has nothing before it within the year
+        let month = month.filter(|month| (2..=12).contains(month));
+        let trends: Vec<EmojiTrend> = match month {
+            Some(month) => emoji
+                .iter()
+                .map(|usage| EmojiTrend {
+                    name: usage.name.clone(),
+                    previous: usage.monthly[month as usize - 2],
+                    current: usage.monthly[month as usize - 1],
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut rising: Vec<EmojiTrend> = trends
+            .iter()
+            .filter(|trend| trend.change() > 0)
+            .cloned()
+            .collect();
+        rising.sort_by(|a, b| b.change().cmp(&a.change()).then(a.name.cmp(&b.name)));
+        rising.truncate(TRENDS);
+        let mut falling: Vec<EmojiTrend> = trends
+            .into_iter()
+            .filter(|trend| trend.change() < 0)
+            .collect();
+        falling.sort_by(|a, b| a.change().cmp(&b.change()).then(a.name.cmp(&b.name)));
+        falling.truncate(TRENDS);
+
+        Self {
+            year: history.year,
+            month,
+            emoji,
+            never_used,
+            rising,
+            falling,
+        }
+    }
+
+    // One row per custom emoji, most used first
+    pub fn to_csv(&self) -> String {
+        const MONTHS: [&str; 12] = [
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+        ];
+        let mut csv = format!(
+            "name,reactions,in_text,total,{},top_users\n",
+            MONTHS.join(",")
+        );
+        for usage in &self.emoji {
+            let monthly: Vec<String> = usage.monthly.iter().map(u32::to_string).collect();
+            let top_users: Vec<String> = usage
+                .top_users
+                .iter()
+                .map(|(user, count)| format!("{}:{}", user, count))
+                .collect();
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                usage.name,
+                usage.reactions,
+                usage.in_text,
+                usage.total(),
+                monthly.join(","),
+                top_users.join(" ")
+            ));
+        }
+        csv
+    }
+}
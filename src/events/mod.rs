@@ -0,0 +1,740 @@
+// What Slack tells us as it happens, kept in SQLite next to the jobs, so a
+// year's history builds up without crawling every channel. Deliveries are
+// recorded by event ID, so the retries Slack makes are only applied once.
+use crate::emoji::{ChangeSource, EmojiChange, EmojiChangeKind};
+use crate::features::history::{year_bounds, ChannelMessage, YearHistory};
+use crate::slack::conversations::Channel;
+use crate::slack::events::{EventCallback, MessageEvent, ReactionEvent, SlackEvent};
+use crate::slack::reactions::{MessageData, Reaction};
+use crate::slack::util::parse_slack_ts;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub mod socket;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS slack_event_ids (
+        team_id TEXT NOT NULL,
+        event_id TEXT NOT NULL,
+        received_at TEXT NOT NULL,
+        PRIMARY KEY (team_id, event_id)
+    );
+    CREATE TABLE IF NOT EXISTS slack_channels (
+        team_id TEXT NOT NULL,
+        id TEXT NOT NULL,
+        name TEXT,
+        channel_type TEXT NOT NULL,
+        created INTEGER NOT NULL,
+        creator TEXT,
+        PRIMARY KEY (team_id, id)
+    );
+    CREATE TABLE IF NOT EXISTS slack_messages (
+        team_id TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        ts TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        subtype TEXT,
+        text TEXT NOT NULL,
+        thread_ts TEXT,
+        posted_at INTEGER NOT NULL,
+        reply_count INTEGER,
+        PRIMARY KEY (team_id, channel, ts)
+    );
+    CREATE INDEX IF NOT EXISTS slack_messages_posted_at
+        ON slack_messages (team_id, posted_at);
+    CREATE TABLE IF NOT EXISTS slack_reactions (
+        team_id TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        ts TEXT NOT NULL,
+        name TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        PRIMARY KEY (team_id, channel, ts, name, user_id)
+    );
+    CREATE TABLE IF NOT EXISTS slack_emoji_changes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        team_id TEXT NOT NULL,
+        subtype TEXT NOT NULL,
+        name TEXT NOT NULL,
+        old_name TEXT,
+        value TEXT,
+        event_ts TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS slack_channel_members (
+        team_id TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        inviter TEXT,
+        joined_at TEXT NOT NULL,
+        PRIMARY KEY (team_id, channel, user_id)
+    );
+";
+
+#[derive(Debug)]
+pub enum EventStoreError {
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for EventStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventStoreError::Io(error) => write!(f, "event store error: {}", error),
+            EventStoreError::Sqlite(error) => write!(f, "event store error: {}", error),
+            EventStoreError::Json(error) => write!(f, "invalid event: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for EventStoreError {
+    fn from(error: io::Error) -> Self {
+        EventStoreError::Io(error)
+    }
+}
+
+impl From<rusqlite::Error> for EventStoreError {
+    fn from(error: rusqlite::Error) -> Self {
+        EventStoreError::Sqlite(error)
+    }
+}
+
+impl From<serde_json::Error> for EventStoreError {
+    fn from(error: serde_json::Error) -> Self {
+        EventStoreError::Json(error)
+    }
+}
+
+// How much is stored about one person
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct StoredActivity {
+    pub messages: usize,
+    pub reactions: usize,
+    pub channel_memberships: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StoredReaction {
+    pub channel: String,
+    pub ts: String,
+    pub name: String,
+}
+
+pub struct EventStore {
+    connection: Mutex<Connection>,
+}
+
+impl EventStore {
+    pub fn open(path: &Path) -> Result<Self, EventStoreError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        add_column(&connection, "slack_messages", "reply_count", "INTEGER")?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    // Stores what the callback says happened, returning false when it was
+    // delivered before. Events we do not handle, and what people who opted
+    // out did, are only recorded as seen.
+    pub fn handle(
+        &self,
+        callback: &EventCallback,
+        opted_out: &HashSet<String>,
+        now: DateTime<Utc>,
+    ) -> Result<bool, EventStoreError> {
+        let mut event: SlackEvent = serde_json::from_value(callback.event.clone())?;
+        withhold(&mut event, opted_out);
+        self.ingest(&callback.team_id, &callback.event_id, &event, now)
+    }
+
+    pub fn ingest(
+        &self,
+        team_id: &str,
+        event_id: &str,
+        event: &SlackEvent,
+        now: DateTime<Utc>,
+    ) -> Result<bool, EventStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let first = transaction.execute(
+            "INSERT OR IGNORE INTO slack_event_ids (team_id, event_id, received_at)
+                VALUES (?1, ?2, ?3)",
+            params![team_id, event_id, now],
+        )? > 0;
+        if first {
+            apply(&transaction, team_id, event, now)?;
+        }
+        transaction.commit()?;
+        Ok(first)
+    }
+
+    // When the first event arrived, in seconds. Nothing from before it is
+    // stored.
+    pub fn covered_since(&self, team_id: &str) -> Result<Option<i64>, EventStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let first: Option<DateTime<Utc>> = connection.query_row(
+            "SELECT MIN(received_at) FROM slack_event_ids WHERE team_id = ?1",
+            [team_id],
+            |row| row.get(0),
+        )?;
+        Ok(first.map(|first| first.timestamp()))
+    }
+
+    // The stored messages of the year, with their reactions. Only covers
+    // what happened since events started arriving.
+    pub fn history(&self, team_id: &str, year: i32) -> Result<YearHistory, EventStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, name, channel_type, created, creator FROM slack_channels
+                WHERE team_id = ?1 ORDER BY id",
+        )?;
+        let channels = statement
+            .query_map([team_id], |row| {
+                let channel_type: String = row.get(2)?;
+                Ok(Channel {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    is_channel: channel_type == "channel",
+                    is_group: channel_type == "group",
+                    is_im: channel_type == "im",
+                    is_mpim: channel_type == "mpim",
+                    is_private: channel_type != "channel",
+                    is_archived: false,
+                    created: row.get(3)?,
+                    creator: row.get(4)?,
+                    user: None,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (oldest, latest) = year_bounds(year);
+        let mut reactions: HashMap<(String, String), Vec<Reaction>> = HashMap::new();
+        let mut statement = connection.prepare(
+            "SELECT r.channel, r.ts, r.name, r.user_id FROM slack_reactions r
+                JOIN slack_messages m
+                    ON m.team_id = r.team_id AND m.channel = r.channel AND m.ts = r.ts
+                WHERE r.team_id = ?1 AND m.posted_at BETWEEN ?2 AND ?3
+                ORDER BY r.rowid",
+        )?;
+        let mut rows = statement.query(params![team_id, oldest, latest])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(2)?;
+            let user: String = row.get(3)?;
+            let message = reactions.entry((row.get(0)?, row.get(1)?)).or_default();
+            match message.iter_mut().find(|reaction| reaction.name == name) {
+                Some(reaction) => {
+                    reaction.users.push(user);
+                    reaction.count += 1;
+                }
+                None => message.push(Reaction {
+                    name,
+                    users: vec![user],
+                    count: 1,
+                }),
+            }
+        }
+
+        let mut statement = connection.prepare(
+            "SELECT channel, ts, user_id, subtype, text, thread_ts, reply_count
+                FROM slack_messages
+                WHERE team_id = ?1 AND posted_at BETWEEN ?2 AND ?3
+                ORDER BY posted_at, ts",
+        )?;
+        let messages = statement
+            .query_map(params![team_id, oldest, latest], |row| {
+                let channel: String = row.get(0)?;
+                let ts: String = row.get(1)?;
+                let reactions = reactions
+                    .remove(&(channel.clone(), ts.clone()))
+                    .unwrap_or_default();
+                Ok(ChannelMessage {
+                    channel,
+                    message: MessageData {
+                        r#type: "message".to_string(),
+                        subtype: row.get(3)?,
+                        text: row.get(4)?,
+                        user: row.get(2)?,
+                        ts,
+                        team: Some(team_id.to_string()),
+                        thread_ts: row.get(5)?,
+                        reply_count: row.get(6)?,
+                        reply_users: None,
+                        reactions,
+                        permalink: None,
+                    },
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(YearHistory {
+            year,
+            channels,
+            messages,
+        })
+    }
+
+    // Names channels first known from their messages, from a list of the
+    // workspace's channels
+    pub fn name_channels(
+        &self,
+        team_id: &str,
+        channels: &[Channel],
+    ) -> Result<(), EventStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for channel in channels {
+            if let Some(name) = &channel.name {
+                transaction.execute(
+                    "UPDATE slack_channels SET name = ?3
+                        WHERE team_id = ?1 AND id = ?2 AND name IS NULL",
+                    params![team_id, channel.id, name],
+                )?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn user_messages(
+        &self,
+        team_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<ChannelMessage>, EventStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT channel, ts, subtype, text, thread_ts, reply_count FROM slack_messages
+                WHERE team_id = ?1 AND user_id = ?2 ORDER BY posted_at, ts",
+        )?;
+        let messages = statement
+            .query_map([team_id, user_id], |row| {
+                Ok(ChannelMessage {
+                    channel: row.get(0)?,
+                    message: MessageData {
+                        r#type: "message".to_string(),
+                        subtype: row.get(2)?,
+                        text: row.get(3)?,
+                        user: user_id.to_string(),
+                        ts: row.get(1)?,
+                        team: Some(team_id.to_string()),
+                        thread_ts: row.get(4)?,
+                        reply_count: row.get(5)?,
+                        reply_users: None,
+                        reactions: Vec::new(),
+                        permalink: None,
+                    },
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(messages)
+    }
+
+    // Reactions the user added
+    pub fn user_reactions(
+        &self,
+        team_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<StoredReaction>, EventStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT channel, ts, name FROM slack_reactions
+                WHERE team_id = ?1 AND user_id = ?2 ORDER BY rowid",
+        )?;
+        let reactions = statement
+            .query_map([team_id, user_id], |row| {
+                Ok(StoredReaction {
+                    channel: row.get(0)?,
+                    ts: row.get(1)?,
+                    name: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(reactions)
+    }
+
+    pub fn user_activity(
+        &self,
+        team_id: &str,
+        user_id: &str,
+    ) -> Result<StoredActivity, EventStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let count = |table: &str| {
+            connection.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {} WHERE team_id = ?1 AND user_id = ?2",
+                    table
+                ),
+                [team_id, user_id],
+                |row| row.get::<_, i64>(0),
+            )
+        };
+        Ok(StoredActivity {
+            messages: count("slack_messages")? as usize,
+            reactions: count("slack_reactions")? as usize,
+            channel_memberships: count("slack_channel_members")? as usize,
+        })
+    }
+
+    // Custom emoji added, removed and renamed, oldest first
+    pub fn emoji_changes(&self, team_id: &str) -> Result<Vec<EmojiChange>, EventStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT subtype, name, old_name, value, event_ts FROM slack_emoji_changes
+                WHERE team_id = ?1 ORDER BY id",
+        )?;
+        let rows = statement
+            .query_map([team_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let changes = rows
+            .into_iter()
+            .filter_map(|(subtype, name, old_name, value, event_ts)| {
+                let kind = match subtype.as_str() {
+                    "add" => EmojiChangeKind::Added,
+                    "remove" => EmojiChangeKind::Removed,
+                    "rename" => EmojiChangeKind::Renamed,
+                    _ => return None,
+                };
+                Some(EmojiChange {
+                    kind,
+                    name,
+                    old_name,
+                    value,
+                    at: DateTime::from_timestamp(parse_slack_ts(&event_ts)?, 0)?,
+                    since: None,
+                    source: ChangeSource::Event,
+                })
+            })
+            .collect();
+        Ok(changes)
+    }
+
+    // Forgets the user's messages, with the reactions on them, the
+    // reactions they added and the channels they joined. Channels they
+    // created are kept, without them as the creator.
+    pub fn delete_user(
+        &self,
+        team_id: &str,
+        user_id: &str,
+    ) -> Result<StoredActivity, EventStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let reactions = transaction.execute(
+            "DELETE FROM slack_reactions WHERE team_id = ?1 AND user_id = ?2",
+            [team_id, user_id],
+        )?;
+        // Reactions on their messages go with the messages
+        transaction.execute(
+            "DELETE FROM slack_reactions WHERE team_id = ?1 AND EXISTS (
+                SELECT 1 FROM slack_messages m
+                    WHERE m.team_id = slack_reactions.team_id
+                        AND m.channel = slack_reactions.channel
+                        AND m.ts = slack_reactions.ts
+                        AND m.user_id = ?2
+            )",
+            [team_id, user_id],
+        )?;
+        // The threads they replied to, to count again without them
+        let threads = transaction
+            .prepare(
+                "SELECT DISTINCT channel, thread_ts FROM slack_messages
+                    WHERE team_id = ?1 AND user_id = ?2 AND thread_ts != ts",
+            )?
+            .query_map([team_id, user_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let deleted = StoredActivity {
+            messages: transaction.execute(
+                "DELETE FROM slack_messages WHERE team_id = ?1 AND user_id = ?2",
+                [team_id, user_id],
+            )?,
+            reactions,
+            channel_memberships: transaction.execute(
+                "DELETE FROM slack_channel_members WHERE team_id = ?1 AND user_id = ?2",
+                [team_id, user_id],
+            )?,
+        };
+        for (channel, thread_ts) in threads {
+            refresh_thread(&transaction, team_id, &channel, &thread_ts)?;
+        }
+        transaction.execute(
+            "UPDATE slack_channels SET creator = NULL WHERE team_id = ?1 AND creator = ?2",
+            [team_id, user_id],
+        )?;
+        transaction.commit()?;
+        Ok(deleted)
+    }
+}
+
+// Leaves out what people who opted out did, and who created a channel when
+// they did
+fn withhold(event: &mut SlackEvent, opted_out: &HashSet<String>) {
+    let actor = match &*event {
+        SlackEvent::Message(message) => match &message.message {
+            Some(changed) if message.user.is_empty() => Some(&changed.user),
+            _ => Some(&message.user),
+        },
+        SlackEvent::ReactionAdded(reaction) => Some(&reaction.user),
+        SlackEvent::MemberJoinedChannel(joined) => Some(&joined.user),
+        _ => None,
+    };
+    if actor.is_some_and(|user| opted_out.contains(user)) {
+        *event = SlackEvent::Other;
+    }
+    if let SlackEvent::ChannelCreated { channel } = event {
+        if channel
+            .creator
+            .as_ref()
+            .is_some_and(|creator| opted_out.contains(creator))
+        {
+            channel.creator = None;
+        }
+    }
+}
+
+fn apply(
+    transaction: &Transaction,
+    team_id: &str,
+    event: &SlackEvent,
+    now: DateTime<Utc>,
+) -> Result<(), EventStoreError> {
+    match event {
+        SlackEvent::Message(message) => apply_message(transaction, team_id, message)?,
+        SlackEvent::ReactionAdded(reaction) => {
+            if let Some((channel, ts)) = reacted_message(reaction) {
+                transaction.execute(
+                    "INSERT OR IGNORE INTO slack_reactions (team_id, channel, ts, name, user_id)
+                        VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![team_id, channel, ts, reaction.reaction, reaction.user],
+                )?;
+            }
+        }
+        SlackEvent::ReactionRemoved(reaction) => {
+            if let Some((channel, ts)) = reacted_message(reaction) {
+                transaction.execute(
+                    "DELETE FROM slack_reactions
+                        WHERE team_id = ?1 AND channel = ?2 AND ts = ?3 AND name = ?4
+                            AND user_id = ?5",
+                    params![team_id, channel, ts, reaction.reaction, reaction.user],
+                )?;
+            }
+        }
+        SlackEvent::EmojiChanged(change) => {
+            // One row per emoji, a removal can name several
+            let rows: Vec<(&str, Option<&str>)> = match change.subtype.as_str() {
+                "remove" => change
+                    .names
+                    .iter()
+                    .map(|name| (name.as_str(), None))
+                    .collect(),
+                "rename" => change
+                    .new_name
+                    .iter()
+                    .map(|name| (name.as_str(), change.old_name.as_deref()))
+                    .collect(),
+                _ => change
+                    .name
+                    .iter()
+                    .map(|name| (name.as_str(), None))
+                    .collect(),
+            };
+            for (name, old_name) in rows {
+                transaction.execute(
+                    "INSERT INTO slack_emoji_changes
+                        (team_id, subtype, name, old_name, value, event_ts)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        team_id,
+                        change.subtype,
+                        name,
+                        old_name,
+                        change.value,
+                        change.event_ts
+                    ],
+                )?;
+            }
+        }
+        SlackEvent::ChannelCreated { channel } => {
+            transaction.execute(
+                "INSERT OR REPLACE INTO slack_channels
+                    (team_id, id, name, channel_type, created, creator)
+                    VALUES (?1, ?2, ?3, 'channel', ?4, ?5)",
+                params![
+                    team_id,
+                    channel.id,
+                    channel.name,
+                    channel.created,
+                    channel.creator
+                ],
+            )?;
+        }
+        SlackEvent::ChannelRename { channel } => {
+            transaction.execute(
+                "INSERT INTO slack_channels (team_id, id, name, channel_type, created, creator)
+                    VALUES (?1, ?2, ?3, 'channel', 0, NULL)
+                    ON CONFLICT (team_id, id) DO UPDATE SET name = excluded.name",
+                params![team_id, channel.id, channel.name],
+            )?;
+        }
+        SlackEvent::MemberJoinedChannel(joined) => {
+            transaction.execute(
+                "INSERT OR REPLACE INTO slack_channel_members
+                    (team_id, channel, user_id, inviter, joined_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![team_id, joined.channel, joined.user, joined.inviter, now],
+            )?;
+        }
+        SlackEvent::Other => {}
+    }
+    Ok(())
+}
+
+fn reacted_message(reaction: &ReactionEvent) -> Option<(&str, &str)> {
+    match reaction.item.r#type.as_str() {
+        "message" => Some((
+            reaction.item.channel.as_deref()?,
+            reaction.item.ts.as_deref()?,
+        )),
+        _ => None,
+    }
+}
+
+fn apply_message(
+    transaction: &Transaction,
+    team_id: &str,
+    message: &MessageEvent,
+) -> Result<(), EventStoreError> {
+    match message.subtype.as_deref() {
+        Some("message_changed") => {
+            if let Some(changed) = &message.message {
+                transaction.execute(
+                    "UPDATE slack_messages SET text = ?4
+                        WHERE team_id = ?1 AND channel = ?2 AND ts = ?3",
+                    params![team_id, message.channel, changed.ts, changed.text],
+                )?;
+            }
+        }
+        Some("message_deleted") => {
+            if let Some(ts) = &message.deleted_ts {
+                let thread_ts: Option<String> = transaction
+                    .query_row(
+                        "SELECT thread_ts FROM slack_messages
+                            WHERE team_id = ?1 AND channel = ?2 AND ts = ?3 AND thread_ts != ts",
+                        params![team_id, message.channel, ts],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                for table in ["slack_messages", "slack_reactions"] {
+                    transaction.execute(
+                        &format!(
+                            "DELETE FROM {} WHERE team_id = ?1 AND channel = ?2 AND ts = ?3",
+                            table
+                        ),
+                        params![team_id, message.channel, ts],
+                    )?;
+                }
+                if let Some(thread_ts) = thread_ts {
+                    refresh_thread(transaction, team_id, &message.channel, &thread_ts)?;
+                }
+            }
+        }
+        subtype => {
+            // Channels are first known from their messages, without a name
+            // until a rename or `name_channels`
+            transaction.execute(
+                "INSERT OR IGNORE INTO slack_channels
+                    (team_id, id, name, channel_type, created, creator)
+                    VALUES (?1, ?2, NULL, ?3, 0, NULL)",
+                params![team_id, message.channel, message.channel_type],
+            )?;
+            transaction.execute(
+                "INSERT OR REPLACE INTO slack_messages
+                    (team_id, channel, ts, user_id, subtype, text, thread_ts, posted_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    team_id,
+                    message.channel,
+                    message.ts,
+                    message.user,
+                    subtype,
+                    message.text,
+                    message.thread_ts,
+                    parse_slack_ts(&message.ts).unwrap_or_default()
+                ],
+            )?;
+            // Either side of a thread can arrive first
+            refresh_thread(transaction, team_id, &message.channel, &message.ts)?;
+            if let Some(thread_ts) = message.thread_ts.as_ref().filter(|ts| **ts != message.ts) {
+                refresh_thread(transaction, team_id, &message.channel, thread_ts)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Counts the replies to a message and, as conversations.history does, gives
+// a parent with replies its own ts as thread_ts
+fn refresh_thread(
+    transaction: &Transaction,
+    team_id: &str,
+    channel: &str,
+    ts: &str,
+) -> Result<(), EventStoreError> {
+    let replies: i64 = transaction.query_row(
+        "SELECT COUNT(*) FROM slack_messages
+            WHERE team_id = ?1 AND channel = ?2 AND thread_ts = ?3 AND ts != ?3",
+        params![team_id, channel, ts],
+        |row| row.get(0),
+    )?;
+    transaction.execute(
+        "UPDATE slack_messages SET
+            reply_count = ?4,
+            thread_ts = CASE
+                WHEN ?4 IS NOT NULL THEN ts
+                WHEN thread_ts = ts THEN NULL
+                ELSE thread_ts
+            END
+            WHERE team_id = ?1 AND channel = ?2 AND ts = ?3",
+        params![team_id, channel, ts, (replies > 0).then_some(replies)],
+    )?;
+    Ok(())
+}
+
+// For columns added since the table was first created
+fn add_column(
+    connection: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), EventStoreError> {
+    let exists = connection
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+            table
+        ))?
+        .exists([column])?;
+    if !exists {
+        connection.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))?;
+    }
+    Ok(())
+}
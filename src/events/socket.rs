@@ -0,0 +1,366 @@
+// Socket Mode, for servers Slack cannot reach. The service asks for a
+// WebSocket URL with apps.connections.open, and Slack sends events, slash
+// commands and interactions down the socket instead of posting them to
+// /slack/events, /slack/commands and /slack/interactions. They are handled by
+// the same functions as the HTTP endpoints'.
+// https://api.slack.com/apis/connections/socket
+use super::{EventStore, EventStoreError};
+use crate::config::AppConfig;
+use crate::consent::ConsentRegistry;
+use crate::features::privacy::privacy_command;
+use crate::features::slack_commands::wrapped_command;
+use crate::features::slack_interactions::interaction;
+use crate::jobs::queue::JobQueue;
+use crate::slack::apps::AppsConnectionsOpenResponse;
+use crate::slack::client::{SlackClient, SlackClients};
+use crate::slack::commands::{CommandResponse, SlashCommand};
+use crate::slack::events::EventPayload;
+use crate::workspaces::TokenStore;
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use rocket::fairing::AdHoc;
+use rocket::tokio::select;
+use rocket::tokio::task::spawn_blocking;
+use rocket::tokio::time::sleep;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::{self, Message};
+
+// Waits between failed connections double from this, up to the maximum
+const FIRST_BACKOFF_SECS: u64 = 1;
+const MAX_BACKOFF_SECS: u64 = 60;
+
+#[derive(Debug)]
+pub enum SocketError {
+    Http(reqwest::Error),
+    // apps.connections.open answered with an error, such as invalid_auth
+    Slack(String),
+    WebSocket(tungstenite::Error),
+}
+
+impl fmt::Display for SocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocketError::Http(error) => write!(f, "could not open a connection: {}", error),
+            SocketError::Slack(error) => write!(f, "could not open a connection: {}", error),
+            SocketError::WebSocket(error) => write!(f, "connection failed: {}", error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for SocketError {
+    fn from(error: reqwest::Error) -> Self {
+        SocketError::Http(error)
+    }
+}
+
+impl From<tungstenite::Error> for SocketError {
+    fn from(error: tungstenite::Error) -> Self {
+        SocketError::WebSocket(error)
+    }
+}
+
+// Everything Slack sends down the socket. Events, slash commands and
+// interactions come in envelopes to acknowledge.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    // hello, events_api, disconnect, slash_commands, interactive
+    #[serde(rename = "type")]
+    kind: String,
+    envelope_id: Option<String>,
+    #[serde(default)]
+    payload: Value,
+    // Why Slack is disconnecting, for disconnect
+    reason: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionEnd {
+    // Slack is moving us to a fresh connection, or the socket closed
+    Reconnect,
+    // Socket Mode was turned off for the app
+    LinkDisabled,
+}
+
+// What envelopes are handled with, as the HTTP endpoints have in managed
+// state. Handling reads and writes SQLite, so it runs on the blocking pool,
+// away from the socket.
+pub struct SocketContext {
+    pub store: EventStore,
+    pub registry: ConsentRegistry,
+    pub queue: JobQueue,
+    pub clients: SlackClients,
+    // Shared with the server's, so workspaces installed since are seen
+    pub tokens: TokenStore,
+    pub config: AppConfig,
+}
+
+// One WebSocket connection
+pub struct SocketSession {
+    context: Arc<SocketContext>,
+    // Whether Slack said hello, so the connection was good
+    pub greeted: bool,
+}
+
+impl SocketSession {
+    pub fn new(context: Arc<SocketContext>) -> Self {
+        Self {
+            context,
+            greeted: false,
+        }
+    }
+
+    pub async fn run(&mut self, url: &str) -> Result<SessionEnd, SocketError> {
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await?;
+        while let Some(message) = socket.next().await {
+            let text = match message? {
+                Message::Text(text) => text,
+                Message::Close(_) => return Ok(SessionEnd::Reconnect),
+                // Pings are answered by tungstenite
+                _ => continue,
+            };
+            let envelope: Envelope = match serde_json::from_str(&text) {
+                Ok(envelope) => envelope,
+                Err(error) => {
+                    println!("Ignoring Socket Mode message: {}", error);
+                    continue;
+                }
+            };
+            match (envelope.kind.as_str(), envelope.reason.as_deref()) {
+                ("hello", _) => self.greeted = true,
+                ("disconnect", Some("link_disabled")) => return Ok(SessionEnd::LinkDisabled),
+                ("disconnect", _) => return Ok(SessionEnd::Reconnect),
+                _ => {}
+            }
+            if envelope.envelope_id.is_none() {
+                continue;
+            }
+            if let Some(ack) = self.handle(envelope).await {
+                socket.send(Message::Text(ack.to_string())).await?;
+            }
+        }
+        Ok(SessionEnd::Reconnect)
+    }
+
+    // A slow write keeps the runtime's threads free for the socket
+    async fn handle(&self, envelope: Envelope) -> Option<Value> {
+        let context = self.context.clone();
+        match spawn_blocking(move || context.handle(&envelope)).await {
+            Ok(ack) => ack,
+            Err(error) => {
+                println!("Encountered error: {}", error);
+                None
+            }
+        }
+    }
+}
+
+impl SocketContext {
+    // The acknowledgement to send, carrying the answer to a slash command as
+    // the HTTP endpoint's response would. None leaves the envelope for Slack
+    // to deliver again.
+    fn handle(&self, envelope: &Envelope) -> Option<Value> {
+        let mut ack = json!({ "envelope_id": envelope.envelope_id.as_deref()? });
+        match envelope.kind.as_str() {
+            "events_api" if !self.store_event(&envelope.payload) => return None,
+            "slash_commands" => {
+                if let Some(answer) = self.command(&envelope.payload) {
+                    ack["payload"] = serde_json::to_value(answer).ok()?;
+                }
+            }
+            "interactive" => self.interact(&envelope.payload),
+            _ => {}
+        }
+        Some(ack)
+    }
+
+    // Whether the event was dealt with. As with the HTTP endpoint, events
+    // that could not be stored are left for Slack to deliver again.
+    fn store_event(&self, payload: &Value) -> bool {
+        let callback = match serde_json::from_value(payload.clone()) {
+            Ok(EventPayload::EventCallback(callback)) => callback,
+            Ok(_) => return true,
+            Err(error) => {
+                println!("Ignoring event: {}", error);
+                return true;
+            }
+        };
+        let opted_out = match self.registry.opted_out(&callback.team_id) {
+            Ok(opted_out) => opted_out,
+            Err(error) => {
+                println!("Encountered error: {}", error);
+                return false;
+            }
+        };
+        match self.store.handle(&callback, &opted_out, Utc::now()) {
+            Ok(_) => true,
+            Err(error @ EventStoreError::Json(_)) => {
+                println!("Ignoring event {}: {}", callback.event_id, error);
+                true
+            }
+            Err(error) => {
+                println!("Encountered error: {}", error);
+                false
+            }
+        }
+    }
+
+    // `/wrapped-privacy` is always answered, as its endpoint is always
+    // mounted. Other commands go to `/wrapped` when it is switched on.
+    fn command(&self, payload: &Value) -> Option<CommandResponse> {
+        let command: SlashCommand = match serde_json::from_value(payload.clone()) {
+            Ok(command) => command,
+            Err(error) => {
+                println!("Ignoring slash command: {}", error);
+                return None;
+            }
+        };
+        if command.command.ends_with("-privacy") {
+            return Some(privacy_command(
+                &command,
+                &self.registry,
+                &self.queue,
+                &self.store,
+                &self.config,
+            ));
+        }
+        if !self.config.features.commands {
+            return None;
+        }
+        Some(wrapped_command(
+            command,
+            &self.tokens,
+            &self.clients,
+            &self.registry,
+            &self.queue,
+            &self.store,
+            &self.config,
+        ))
+    }
+
+    // The wrapped DMs' buttons, when they are switched on
+    fn interact(&self, payload: &Value) {
+        if !self.config.features.dm_wrapped {
+            return;
+        }
+        let payload = match serde_json::from_value(payload.clone()) {
+            Ok(payload) => payload,
+            Err(error) => {
+                println!("Ignoring interaction: {}", error);
+                return;
+            }
+        };
+        interaction(
+            payload,
+            &self.tokens,
+            &self.clients,
+            &self.registry,
+            &self.queue,
+            &self.config,
+        );
+    }
+}
+
+// How long to wait after `failures` connections in a row went wrong
+pub fn backoff(failures: u32) -> Duration {
+    let secs = FIRST_BACKOFF_SECS.saturating_mul(2u64.saturating_pow(failures));
+    Duration::from_secs(secs.min(MAX_BACKOFF_SECS))
+}
+
+// Keeps a connection open until Socket Mode is turned off. `open` gives the
+// URL to connect to, a new one each time. Slack asks for a reconnect every
+// few hours, which is done straight away, failures back off.
+pub async fn run<F, Fut>(mut open: F, context: Arc<SocketContext>)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<String, SocketError>>,
+{
+    let mut failures = 0;
+    loop {
+        let mut session = SocketSession::new(context.clone());
+        let result = match open().await {
+            Ok(url) => session.run(&url).await,
+            Err(error) => Err(error),
+        };
+        if session.greeted {
+            failures = 0;
+        }
+        match result {
+            Ok(SessionEnd::LinkDisabled) => {
+                println!("Socket Mode was turned off for the app, no longer receiving events");
+                return;
+            }
+            Ok(SessionEnd::Reconnect) if session.greeted => continue,
+            Ok(SessionEnd::Reconnect) => {}
+            Err(error) => println!("Socket Mode {}", error),
+        }
+        sleep(backoff(failures)).await;
+        failures += 1;
+    }
+}
+
+pub async fn open_connection(client: &SlackClient) -> Result<String, SocketError> {
+    match client.apps().connections_open().await? {
+        AppsConnectionsOpenResponse::Success(success) => Ok(success.url),
+        AppsConnectionsOpenResponse::Error(error) => Err(SocketError::Slack(error.error)),
+    }
+}
+
+// Connects once the server is up, until it shuts down. Events are written,
+// and privacy settings read, through connections of their own to the
+// database.
+pub fn connect() -> AdHoc {
+    AdHoc::on_liftoff("Socket Mode", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(clients), Some(queue), Some(tokens)) = (
+                rocket.state::<AppConfig>(),
+                rocket.state::<SlackClients>(),
+                rocket.state::<JobQueue>(),
+                rocket.state::<TokenStore>(),
+            ) else {
+                return;
+            };
+            let Some(token) = config.slack.app_token.as_deref() else {
+                return;
+            };
+            let store = match EventStore::open(&config.database_path) {
+                Ok(store) => store,
+                Err(error) => {
+                    println!("Encountered error: {}", error);
+                    return;
+                }
+            };
+            let registry = match ConsentRegistry::open(&config.database_path) {
+                Ok(registry) => registry,
+                Err(error) => {
+                    println!("Encountered error: {}", error);
+                    return;
+                }
+            };
+            let context = Arc::new(SocketContext {
+                store,
+                registry,
+                queue: queue.clone(),
+                clients: clients.clone(),
+                tokens: tokens.clone(),
+                config: config.clone(),
+            });
+            let client = clients.get(token);
+            let shutdown = rocket.shutdown();
+            rocket::tokio::spawn(async move {
+                let open = || {
+                    let client = client.clone();
+                    async move { open_connection(&client).await }
+                };
+                select! {
+                    _ = run(open, context) => {}
+                    _ = shutdown => {}
+                }
+            });
+        })
+    })
+}
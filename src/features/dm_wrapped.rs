@@ -0,0 +1,333 @@
+use crate::api::v1::WrappedDocument;
+use crate::auth::{random_token, Session};
+use crate::config::AppConfig;
+use crate::consent::ConsentRegistry;
+use crate::features::history::YearHistory;
+use crate::features::slack_interactions::wrapped_actions;
+use crate::features::summary_card::card_url;
+use crate::features::wrapped::{UserWrapped, ANONYMOUS};
+use crate::jobs::queue::JobQueue;
+use crate::jobs::JobStore;
+use crate::slack::blocks::{escape_mrkdwn, Block, ContextElement, Text};
+use crate::slack::chat::{ChatPostMessageParams, ChatPostMessageResponse, ChatPostMessageSuccess};
+use crate::slack::client::SlackClient;
+use crate::slack::users::{User, UsersListParams, UsersListResponse};
+use chrono::Utc;
+use rocket;
+use rocket::http::Status;
+use rocket::{post, Route, State};
+use std::collections::{HashMap, HashSet};
+
+// Links and the card image need `base_url`, where this service is reachable.
+// The card's URL is signed for the team, as Slack fetches it without signing
+// in, and is drawn from the stored wrapped rather than a new crawl.
+pub fn wrapped_blocks(wrapped: &WrappedDocument, config: &AppConfig, team_id: &str) -> Vec<Block> {
+    let mut blocks = vec![
+        Block::header(format!("Your {} in Slack :sparkles:", wrapped.year)),
+        Block::section(Text::mrkdwn(format!(
+            "You sent *{} messages* across *{} active days*.",
+            wrapped.message_count, wrapped.streaks.active_days
+        ))),
+    ];
+
+    let favourite = wrapped
+        .reactions
+        .first()
+        .map_or("No reactions this year".to_string(), |reaction| {
+            format!(":{}: × {}", reaction.name, reaction.count)
+        });
+    let top_channel = wrapped
+        .top_channels
+        .first()
+        .map_or("-".to_string(), |channel| {
+            format!("#{} ({})", escape_mrkdwn(&channel.name), channel.messages)
+        });
+    let longest_streak = wrapped
+        .streaks
+        .longest
+        .as_ref()
+        .map_or(0, |streak| streak.days);
+    blocks.push(Block::fields(vec![
+        Text::mrkdwn(format!("*Favourite reaction*\n{}", favourite)),
+        Text::mrkdwn(format!("*Top channel*\n{}", top_channel)),
+        Text::mrkdwn(format!("*Longest streak*\n{} days", longest_streak)),
+        Text::mrkdwn(format!(
+            "*Threads*\n{} started, {} joined",
+            wrapped.threads.threads_started, wrapped.threads.threads_replied
+        )),
+    ]));
+
+    if !wrapped.collaborators.is_empty() {
+        let people: Vec<String> = wrapped
+            .collaborators
+            .iter()
+            .map(|collaborator| match collaborator.user_id.as_str() {
+                ANONYMOUS => ANONYMOUS.to_string(),
+                user_id => format!("<@{}>", user_id),
+            })
+            .collect();
+        blocks.push(Block::section(Text::mrkdwn(format!(
+            "*Your people*\n{}",
+            people.join(", ")
+        ))));
+    }
+
+    let card = card_url(config, team_id, &wrapped.user.id, wrapped.year);
+    if let (Some(base_url), Some(card)) = (config.base_url.as_deref(), card) {
+        let page = format!(
+            "{}/wrapped/{}/{}",
+            base_url.trim_end_matches('/'),
+            wrapped.user.id,
+            wrapped.year
+        );
+        blocks.push(Block::image(
+            card,
+            format!("{} wrapped summary card", wrapped.year),
+        ));
+        blocks.push(Block::context(vec![ContextElement::Text(Text::mrkdwn(
+            format!("<{}|See your full wrapped>", page),
+        ))]));
+    }
+    blocks
+}
+
+// Keeps the wrapped as a finished job, for the message's buttons to page
+// through and its card to be drawn from. Without it the message is sent
+// without buttons.
+pub fn record_wrapped(
+    store: &JobStore,
+    team_id: &str,
+    wrapped: &WrappedDocument,
+    requested_by: &str,
+) -> Option<String> {
+    let now = Utc::now();
+    let recorded = store
+        .create(
+            &random_token(),
+            team_id,
+            &wrapped.user.id,
+            wrapped.year,
+            requested_by,
+            now,
+        )
+        .and_then(|job| store.succeed(&job.id, wrapped, now).map(|_| job.id));
+    match recorded {
+        Ok(job_id) => Some(job_id),
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            None
+        }
+    }
+}
+
+// Posting to a user ID delivers the message to the app's DM with them.
+// `actions` goes under the summary, see `slack_interactions`.
+pub async fn send_wrapped(
+    slack_client: &SlackClient,
+    wrapped: &WrappedDocument,
+    config: &AppConfig,
+    team_id: &str,
+    actions: Option<Block>,
+) -> Result<ChatPostMessageSuccess, String> {
+    let mut blocks = wrapped_blocks(wrapped, config, team_id);
+    blocks.extend(actions);
+    let params = ChatPostMessageParams {
+        channel: wrapped.user.id.clone(),
+        text: format!(
+            "Your {} in Slack: {} messages",
+            wrapped.year, wrapped.message_count
+        ),
+        blocks: Some(blocks),
+        unfurl_links: Some(false),
+        ..Default::default()
+    };
+    match slack_client.chat().post_message(params).await {
+        Ok(ChatPostMessageResponse::Success(success)) => Ok(success),
+        Ok(ChatPostMessageResponse::Error(error)) => Err(error.error),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+async fn list_members(slack_client: &SlackClient) -> Result<Vec<User>, String> {
+    let mut members = Vec::new();
+    let mut cursor = None;
+    loop {
+        let params = UsersListParams {
+            cursor,
+            limit: Some(200),
+            ..Default::default()
+        };
+        let page = match slack_client.users().list(params).await {
+            Ok(UsersListResponse::Success(page)) => page,
+            Ok(UsersListResponse::Error(error)) => return Err(error.error),
+            Err(error) => return Err(error.to_string()),
+        };
+        members.extend(page.members);
+        if page.response_metadata.next_cursor.is_empty() {
+            return Ok(members);
+        }
+        cursor = Some(page.response_metadata.next_cursor);
+    }
+}
+
+// Reuses a fresh wrapped when there is one, or queues it, and sends the DM
+// once it is done
+#[post("/wrapped/<user_id>/<year>/dm")]
+pub fn dm_wrapped_route(
+    user_id: &str,
+    year: i32,
+    session: Session,
+    slack_client: SlackClient,
+    config: &State<AppConfig>,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+) -> Result<String, Status> {
+    session.authorize(user_id)?;
+    registry.authorize(&session, user_id)?;
+    let opted_out = registry.anonymised(&session)?;
+    let now = Utc::now();
+    let (job, created) = queue
+        .store
+        .reusable_or_create(
+            &session.team_id.0,
+            user_id,
+            year,
+            &session.user_id.0,
+            config.cache.fresh_since(now),
+            now,
+        )
+        .map_err(|error| {
+            println!("Encountered error: {}", error);
+            Status::InternalServerError
+        })?;
+    if created {
+        queue.submit(&job, slack_client.clone(), opted_out);
+    }
+    let queue = queue.inner().clone();
+    let config = config.inner().clone();
+    rocket::tokio::spawn(async move {
+        let wrapped = match queue.result(&job.id).await {
+            Ok(wrapped) => wrapped,
+            Err(error) => {
+                println!("Could not build wrapped: {}", error);
+                return;
+            }
+        };
+        let actions = wrapped_actions(&job.id, 0, &config.slack.share_channel);
+        if let Err(error) = send_wrapped(
+            &slack_client,
+            &wrapped,
+            &config,
+            &job.team_id,
+            Some(actions),
+        )
+        .await
+        {
+            println!("Error: {:?}", error);
+        }
+    });
+    Ok("Wrapped is on its way".to_string())
+}
+
+// Sends every active member their wrapped, from a single load of the year
+async fn dm_all(
+    slack_client: SlackClient,
+    config: AppConfig,
+    queue: JobQueue,
+    session: Session,
+    year: i32,
+    opted_out: HashSet<String>,
+) -> Result<(u32, u32), String> {
+    let members = list_members(&slack_client).await?;
+    let stored = queue.stored_events(&session.team_id.0);
+    let history = YearHistory::load(&slack_client, stored, year)
+        .await
+        .map_err(|error| error.to_string())?;
+
+    let profiles: HashMap<&str, &User> = members
+        .iter()
+        .map(|member| (member.id.as_str(), member))
+        .collect();
+    let today = Utc::now().date_naive();
+    let (mut sent, mut failed) = (0, 0);
+    for member in &members {
+        if member.deleted
+            || member.is_bot
+            || member.id == "USLACKBOT"
+            || opted_out.contains(&member.id)
+        {
+            continue;
+        }
+        let mut wrapped = UserWrapped::compute(&history, member.clone(), today);
+        if wrapped.message_count == 0 {
+            continue;
+        }
+        for collaborator in &wrapped.collaborators {
+            if let Some(profile) = profiles.get(collaborator.user_id.as_str()) {
+                wrapped
+                    .profiles
+                    .insert(collaborator.user_id.clone(), (*profile).clone());
+            }
+        }
+        wrapped.resolve_profiles(&slack_client).await;
+        wrapped.anonymise(&opted_out);
+        let document = WrappedDocument::new(&wrapped, Utc::now());
+        let actions = record_wrapped(
+            &queue.store,
+            &session.team_id.0,
+            &document,
+            &session.user_id.0,
+        )
+        .map(|job_id| wrapped_actions(&job_id, 0, &config.slack.share_channel));
+        match send_wrapped(
+            &slack_client,
+            &document,
+            &config,
+            &session.team_id.0,
+            actions,
+        )
+        .await
+        {
+            Ok(_) => sent += 1,
+            Err(error) => {
+                println!("Could not send wrapped to {}: {}", member.id, error);
+                failed += 1;
+            }
+        }
+    }
+    Ok((sent, failed))
+}
+
+// Queues sending every active member their wrapped. Only workspace admins
+// can do this.
+#[post("/wrapped/<year>/dm-all")]
+pub fn dm_all_wrapped_route(
+    year: i32,
+    session: Session,
+    slack_client: SlackClient,
+    config: &State<AppConfig>,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+) -> Result<String, Status> {
+    session.authorize_admin()?;
+    let opted_out = registry.anonymised(&session)?;
+    let task = dm_all(
+        slack_client,
+        config.inner().clone(),
+        queue.inner().clone(),
+        session,
+        year,
+        opted_out,
+    );
+    queue.spawn(async move {
+        match task.await {
+            Ok((sent, failed)) => println!("Sent {} wrapped messages, {} failed", sent, failed),
+            Err(error) => println!("Could not send wrapped messages: {}", error),
+        }
+    });
+    Ok("Sending wrapped messages to every member".to_string())
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![dm_wrapped_route, dm_all_wrapped_route]
+}
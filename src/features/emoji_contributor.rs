@@ -2,12 +2,9 @@ use crate::slack::client::SlackClient;
 use crate::slack::emoji::{EmojiListParams, EmojiListResponse::Error, EmojiListResponse::Success};
 use rocket;
 use rocket::{get, Route};
-use std::env;
 
 #[get("/emoji-contributor")]
-pub async fn emoji_contributor_route() -> &'static str {
-    let token = env::var("SLACK_TOKEN").expect("Please set SLACK_TOKEN");
-    let slack_client = SlackClient::new(&token);
+pub async fn emoji_contributor_route(slack_client: SlackClient) -> &'static str {
     let params = Some(EmojiListParams {
         include_categories: false,
     });
@@ -18,16 +15,16 @@ pub async fn emoji_contributor_route() -> &'static str {
                 let emoji_list = emoji_list_response.emoji;
                 let emoji_list_keys = emoji_list.keys();
                 println!("{:?}", emoji_list_keys);
-                return "Emoji Contributor - success";
+                "Emoji Contributor - success"
             }
             Error(emoji_list_response) => {
                 println!("Error: {:?}", emoji_list_response.error);
-                return "Emoji Contributor - encountered error";
+                "Emoji Contributor - encountered error"
             }
         },
         Err(error) => {
             println!("Encountered error: {:?}", error);
-            return "Could not get emoji list";
+            "Could not get emoji list"
         }
     }
 }
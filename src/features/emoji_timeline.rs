@@ -0,0 +1,83 @@
+// The workspace's custom emoji over the year: which were added, renamed and
+// removed, and which did not last long.
+use crate::auth::Session;
+use crate::emoji::{EmojiChange, EmojiChangeKind, EmojiLifetime, EmojiStore, EmojiTimeline};
+use crate::events::EventStore;
+use chrono::{DateTime, Datelike, Utc};
+use rocket;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{get, Route, State};
+use serde::Serialize;
+
+// Entries in the shortest-lived list
+const SHORTEST_LIVED: usize = 5;
+
+#[derive(Debug, Serialize)]
+pub struct EmojiTimelineDocument {
+    pub year: i32,
+    // Changes before this are not known
+    pub tracked_since: Option<DateTime<Utc>>,
+    // None when tracking started after the year
+    pub added: Option<u32>,
+    pub removed: u32,
+    pub renamed: u32,
+    pub changes: Vec<EmojiChange>,
+    pub shortest_lived: Vec<EmojiLifetime>,
+}
+
+impl EmojiTimelineDocument {
+    pub fn new(timeline: &EmojiTimeline, year: i32) -> Self {
+        let count = |kind| {
+            timeline
+                .in_year(year)
+                .filter(|change| change.kind == kind)
+                .count() as u32
+        };
+        Self {
+            year,
+            tracked_since: timeline.tracked_since,
+            added: timeline.added_in(year),
+            removed: count(EmojiChangeKind::Removed),
+            renamed: count(EmojiChangeKind::Renamed),
+            changes: timeline.in_year(year).cloned().collect(),
+            shortest_lived: timeline.shortest_lived(year, SHORTEST_LIVED),
+        }
+    }
+}
+
+// Snapshot changes and emoji_changed events together
+pub fn load_timeline(
+    emoji: &EmojiStore,
+    events: &EventStore,
+    team_id: &str,
+) -> Result<EmojiTimeline, Status> {
+    let log = |error: &dyn std::fmt::Display| {
+        println!("Encountered error: {}", error);
+        Status::InternalServerError
+    };
+    let snapshot_changes = emoji.changes(team_id).map_err(|error| log(&error))?;
+    let first_snapshot = emoji.first_snapshot(team_id).map_err(|error| log(&error))?;
+    let event_changes = events.emoji_changes(team_id).map_err(|error| log(&error))?;
+    Ok(EmojiTimeline::new(
+        snapshot_changes,
+        event_changes,
+        first_snapshot,
+    ))
+}
+
+#[get("/emoji-timeline?<year>")]
+pub fn emoji_timeline_route(
+    year: Option<i32>,
+    session: Session,
+    emoji: &State<EmojiStore>,
+    events: &State<EventStore>,
+) -> Result<Json<EmojiTimelineDocument>, Status> {
+    let timeline = load_timeline(emoji, events, &session.team_id.0)?;
+    let year = year.unwrap_or(Utc::now().year());
+    Ok(Json(EmojiTimelineDocument::new(&timeline, year)))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![emoji_timeline_route]
+}
@@ -0,0 +1,94 @@
+// The custom emoji usage report, for admins, as JSON or as CSV for a
+// spreadsheet.
+use crate::auth::Session;
+use crate::consent::ConsentRegistry;
+use crate::emoji::usage::EmojiUsageReport;
+use crate::events::EventStore;
+use crate::features::history::{StoredEvents, YearHistory};
+use crate::slack::client::SlackClient;
+use crate::slack::emoji::EmojiListResponse;
+use chrono::{DateTime, Datelike, Utc};
+use rocket;
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Json;
+use rocket::{get, Route, State};
+
+// The trends compare the last full month of the year with the one before
+pub fn trend_month(year: i32, now: DateTime<Utc>) -> Option<u32> {
+    match year.cmp(&now.year()) {
+        std::cmp::Ordering::Less => Some(12),
+        std::cmp::Ordering::Equal => Some(now.month() - 1).filter(|month| *month > 0),
+        std::cmp::Ordering::Greater => None,
+    }
+}
+
+async fn usage_report(
+    session: &Session,
+    registry: &ConsentRegistry,
+    events: &EventStore,
+    slack_client: &SlackClient,
+    year: Option<i32>,
+) -> Result<EmojiUsageReport, Status> {
+    session.authorize_admin()?;
+    let opted_out = registry.opted_out(&session.team_id.0).map_err(|error| {
+        println!("Encountered error: {}", error);
+        Status::InternalServerError
+    })?;
+    let custom = match slack_client.emoji().list(None).await {
+        Ok(EmojiListResponse::Success(list)) => list.emoji,
+        Ok(EmojiListResponse::Error(error)) => {
+            println!("Error: {:?}", error.error);
+            return Err(Status::BadGateway);
+        }
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return Err(Status::BadGateway);
+        }
+    };
+    let now = Utc::now();
+    let year = year.unwrap_or(now.year());
+    let stored = StoredEvents {
+        store: events,
+        team_id: &session.team_id.0,
+    };
+    let history = YearHistory::load(slack_client, Some(stored), year)
+        .await
+        .map_err(|error| {
+            println!("Encountered error: {}", error);
+            Status::BadGateway
+        })?;
+    Ok(EmojiUsageReport::compute(
+        &history,
+        &custom,
+        &opted_out,
+        trend_month(year, now),
+    ))
+}
+
+#[get("/emoji-usage?<year>")]
+pub async fn emoji_usage_route(
+    year: Option<i32>,
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<Json<EmojiUsageReport>, Status> {
+    let report = usage_report(&session, registry, events, &slack_client, year).await?;
+    Ok(Json(report))
+}
+
+#[get("/emoji-usage.csv?<year>")]
+pub async fn emoji_usage_csv_route(
+    year: Option<i32>,
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<(ContentType, String), Status> {
+    let report = usage_report(&session, registry, events, &slack_client, year).await?;
+    Ok((ContentType::CSV, report.to_csv()))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![emoji_usage_route, emoji_usage_csv_route]
+}
@@ -1,14 +1,95 @@
+use crate::auth::Session;
+use crate::events::{EventStore, StoredReaction};
+use crate::features::history::{local_date, YearHistory};
+use crate::features::wrapped::fetch_user;
 use crate::slack::client::SlackClient;
+use chrono::{Datelike, Utc};
 use rocket;
-use rocket::{get, Route};
-use std::env;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{get, Route, State};
+use serde::Serialize;
+use std::collections::HashMap;
 
-#[get("/favourite-reaction")]
-pub async fn favourite_reaction() -> &'static str {
-    let token = env::var("SLACK_TOKEN").expect("Please set SLACK_TOKEN");
-    let _slack_client = SlackClient::new(&token);
-    // This requires reactions.list to be implemented first
-    return "Not implemented yet";
+// Reactions listed after the favourite
+const TOP_REACTIONS: usize = 5;
+
+// Emoji the user reacted with during the year, most used first
+pub fn reactions_used(history: &YearHistory, user_id: &str, tz_offset: i32) -> Vec<(String, u32)> {
+    let mut counts: HashMap<&str, u32> = HashMap::new();
+    for (_, message) in history.messages_in_year(tz_offset) {
+        for reaction in &message.message.reactions {
+            if reaction.users.iter().any(|user| user == user_id) {
+                *counts.entry(reaction.name.as_str()).or_default() += 1;
+            }
+        }
+    }
+    ranked(counts)
+}
+
+// The same from the reactions the user added as stored events. They count
+// towards the year, in the user's timezone, of the message they were added
+// to.
+pub fn stored_reactions_used(
+    stored: &[StoredReaction],
+    year: i32,
+    tz_offset: i32,
+) -> Vec<(String, u32)> {
+    let mut counts: HashMap<&str, u32> = HashMap::new();
+    for reaction in stored {
+        if local_date(&reaction.ts, tz_offset).is_some_and(|date| date.year() == year) {
+            *counts.entry(reaction.name.as_str()).or_default() += 1;
+        }
+    }
+    ranked(counts)
+}
+
+fn ranked(counts: HashMap<&str, u32>) -> Vec<(String, u32)> {
+    let mut counts: Vec<(String, u32)> = counts
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+#[derive(Debug, Serialize)]
+pub struct FavouriteReaction {
+    pub user_id: String,
+    pub year: i32,
+    // None when the user added no reactions this year
+    pub favourite: Option<String>,
+    pub reactions: Vec<(String, u32)>,
+}
+
+// The signed in user's most used reaction, from the reactions the Events API
+// delivered, so only counts those added since events started arriving
+#[get("/favourite-reaction?<year>")]
+pub async fn favourite_reaction(
+    year: Option<i32>,
+    session: Session,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<Json<FavouriteReaction>, Status> {
+    let log = |error: &dyn std::fmt::Display| {
+        println!("Encountered error: {}", error);
+        Status::InternalServerError
+    };
+    let year = year.unwrap_or(Utc::now().year());
+    let user = fetch_user(&slack_client, &session.user_id.0)
+        .await
+        .map_err(|error| log(&error))?;
+    let stored = events
+        .user_reactions(&session.team_id.0, &session.user_id.0)
+        .map_err(|error| log(&error))?;
+    let mut reactions = stored_reactions_used(&stored, year, user.tz_offset);
+    reactions.truncate(TOP_REACTIONS);
+    Ok(Json(FavouriteReaction {
+        user_id: session.user_id.0,
+        year,
+        favourite: reactions.first().map(|(name, _)| name.clone()),
+        reactions,
+    }))
 }
 
 pub fn routes() -> Vec<Route> {
@@ -0,0 +1,33 @@
+use crate::features::history::YearHistory;
+use crate::slack::util::parse_slack_ts;
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
+
+// Messages per weekday (Monday first) and hour of day, in the user's timezone
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Heatmap {
+    pub counts: [[u32; 24]; 7],
+}
+
+impl Heatmap {
+    pub fn compute(history: &YearHistory, user_id: &str, tz_offset: i32) -> Self {
+        let mut heatmap = Heatmap::default();
+        let Some(offset) = FixedOffset::east_opt(tz_offset) else {
+            return heatmap;
+        };
+        for (_, message) in history.user_messages(user_id, tz_offset) {
+            let Some(time) = parse_slack_ts(&message.message.ts)
+                .and_then(|ts| DateTime::from_timestamp(ts, 0))
+                .map(|time| time.with_timezone(&offset))
+            else {
+                continue;
+            };
+            heatmap.counts[time.weekday().num_days_from_monday() as usize][time.hour() as usize] +=
+                1;
+        }
+        heatmap
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().flatten().copied().max().unwrap_or(0)
+    }
+}
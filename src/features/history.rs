@@ -0,0 +1,391 @@
+use crate::events::{EventStore, EventStoreError};
+use crate::slack::client::SlackClient;
+use crate::slack::conversations::{
+    Channel, ConversationsHistoryParams, ConversationsListParams, ConversationsListResponse,
+    ConversationsMessagesResponse, ConversationsRepliesParams,
+};
+use crate::slack::reactions::MessageData;
+use crate::slack::util::parse_slack_ts;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+// Timezones range from UTC-12 to UTC+14, so a year in any of them fits in
+// the UTC year widened by this margin on both ends.
+const TIMEZONE_MARGIN_SECS: i64 = 14 * 60 * 60;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChannelMessage {
+    pub channel: String,
+    pub message: MessageData,
+}
+
+// Every message (including thread replies) visible to the token, posted in
+// or around the given year. Serializable, so redacted copies can be kept as
+// fixtures.
+#[derive(Deserialize, Serialize)]
+pub struct YearHistory {
+    pub year: i32,
+    pub channels: Vec<Channel>,
+    pub messages: Vec<ChannelMessage>,
+}
+
+// How far a crawl has got, reported after every page
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FetchProgress {
+    pub channels_done: u32,
+    pub channels_total: u32,
+    pub pages_fetched: u32,
+}
+
+// The events stored for a workspace, read instead of crawling for the time
+// since they started arriving
+#[derive(Clone, Copy)]
+pub struct StoredEvents<'a> {
+    pub store: &'a EventStore,
+    pub team_id: &'a str,
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    Request(reqwest::Error),
+    Slack(String),
+    Store(EventStoreError),
+}
+
+impl From<reqwest::Error> for HistoryError {
+    fn from(error: reqwest::Error) -> Self {
+        HistoryError::Request(error)
+    }
+}
+
+impl From<EventStoreError> for HistoryError {
+    fn from(error: EventStoreError) -> Self {
+        HistoryError::Store(error)
+    }
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::Request(error) => write!(f, "request failed: {}", error),
+            HistoryError::Slack(error) => write!(f, "slack returned an error: {}", error),
+            HistoryError::Store(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl YearHistory {
+    pub async fn load(
+        slack_client: &SlackClient,
+        stored: Option<StoredEvents<'_>>,
+        year: i32,
+    ) -> Result<Self, HistoryError> {
+        Self::load_with_progress(slack_client, stored, year, |_| {}).await
+    }
+
+    // From the stored events when they cover the whole year. When events
+    // started arriving during the year, the time before is crawled and put
+    // together with them. Otherwise the year is crawled.
+    pub async fn load_with_progress(
+        slack_client: &SlackClient,
+        stored: Option<StoredEvents<'_>>,
+        year: i32,
+        mut on_progress: impl FnMut(FetchProgress),
+    ) -> Result<Self, HistoryError> {
+        let (oldest, latest) = year_bounds(year);
+        let Some(StoredEvents { store, team_id }) = stored else {
+            return Self::fetch_with_progress(slack_client, year, on_progress).await;
+        };
+        let since = match store.covered_since(team_id)? {
+            Some(since) if since < latest => since,
+            _ => return Self::fetch_with_progress(slack_client, year, on_progress).await,
+        };
+        let mut history = store.history(team_id, year)?;
+        if since > oldest {
+            let earlier =
+                Self::fetch_between(slack_client, year, (oldest, since), on_progress).await?;
+            store.name_channels(team_id, &earlier.channels)?;
+            return Ok(earlier.merge(history));
+        }
+
+        // Channels first known from their messages have no name yet
+        let unnamed = |channel: &Channel| channel.name.is_none() && !channel.is_im;
+        if history.channels.iter().any(unnamed) {
+            let listed = list_channels(slack_client).await?;
+            store.name_channels(team_id, &listed)?;
+            for channel in history
+                .channels
+                .iter_mut()
+                .filter(|channel| unnamed(channel))
+            {
+                channel.name = listed
+                    .iter()
+                    .find(|found| found.id == channel.id)
+                    .and_then(|found| found.name.clone());
+            }
+        }
+        let channels = history.channels.len() as u32;
+        on_progress(FetchProgress {
+            channels_done: channels,
+            channels_total: channels,
+            pages_fetched: 0,
+        });
+        Ok(history)
+    }
+
+    pub async fn fetch(slack_client: &SlackClient, year: i32) -> Result<Self, HistoryError> {
+        Self::fetch_with_progress(slack_client, year, |_| {}).await
+    }
+
+    pub async fn fetch_with_progress(
+        slack_client: &SlackClient,
+        year: i32,
+        on_progress: impl FnMut(FetchProgress),
+    ) -> Result<Self, HistoryError> {
+        Self::fetch_between(slack_client, year, year_bounds(year), on_progress).await
+    }
+
+    // The messages between the bounds, in seconds, which are within the year
+    async fn fetch_between(
+        slack_client: &SlackClient,
+        year: i32,
+        bounds: (i64, i64),
+        mut on_progress: impl FnMut(FetchProgress),
+    ) -> Result<Self, HistoryError> {
+        let channels = list_channels(slack_client).await?;
+        let mut progress = FetchProgress {
+            channels_total: channels.len() as u32,
+            ..Default::default()
+        };
+        on_progress(progress);
+
+        let mut messages = Vec::new();
+        for channel in &channels {
+            let channel_messages = fetch_channel_messages(
+                slack_client,
+                &channel.id,
+                bounds,
+                &mut progress,
+                &mut on_progress,
+            )
+            .await?;
+            messages.extend(channel_messages);
+            progress.channels_done += 1;
+            on_progress(progress);
+        }
+
+        Ok(Self {
+            year,
+            channels,
+            messages,
+        })
+    }
+
+    // Adds the stored events of the year to what was crawled from before
+    // they started arriving. Replies to earlier threads can be in both.
+    pub fn merge(mut self, stored: YearHistory) -> Self {
+        for channel in stored.channels {
+            if self.channel(&channel.id).is_none() {
+                self.channels.push(channel);
+            }
+        }
+        let crawled: HashSet<(String, String)> = self
+            .messages
+            .iter()
+            .map(|message| (message.channel.clone(), message.message.ts.clone()))
+            .collect();
+        self.messages
+            .extend(stored.messages.into_iter().filter(|message| {
+                !crawled.contains(&(message.channel.clone(), message.message.ts.clone()))
+            }));
+        self
+    }
+
+    // Only the one channel's year, for stats about it. Channels the token
+    // cannot see are not found.
+    pub async fn fetch_channel(
+        slack_client: &SlackClient,
+        channel_id: &str,
+        year: i32,
+    ) -> Result<Self, HistoryError> {
+        let channels: Vec<Channel> = list_channels(slack_client)
+            .await?
+            .into_iter()
+            .filter(|channel| channel.id == channel_id)
+            .collect();
+        if channels.is_empty() {
+            return Err(HistoryError::Slack("channel_not_found".to_string()));
+        }
+        let messages = fetch_channel_messages(
+            slack_client,
+            channel_id,
+            year_bounds(year),
+            &mut FetchProgress::default(),
+            &mut |_| {},
+        )
+        .await?;
+        Ok(Self {
+            year,
+            channels,
+            messages,
+        })
+    }
+
+    pub fn channel(&self, id: &str) -> Option<&Channel> {
+        self.channels.iter().find(|channel| channel.id == id)
+    }
+
+    // Messages posted on a local date within the year
+    pub fn messages_in_year(
+        &self,
+        tz_offset: i32,
+    ) -> impl Iterator<Item = (NaiveDate, &ChannelMessage)> + '_ {
+        self.messages.iter().filter_map(move |message| {
+            let date = local_date(&message.message.ts, tz_offset)?;
+            (date.year() == self.year).then_some((date, message))
+        })
+    }
+
+    // Messages posted by the user on a local date within the year
+    pub fn user_messages<'a>(
+        &'a self,
+        user_id: &'a str,
+        tz_offset: i32,
+    ) -> impl Iterator<Item = (NaiveDate, &'a ChannelMessage)> + 'a {
+        self.messages_in_year(tz_offset)
+            .filter(move |(_, message)| message.message.user == user_id)
+    }
+}
+
+// The calendar date a Slack timestamp falls on, for a UTC offset in seconds
+pub fn local_date(ts: &str, tz_offset: i32) -> Option<NaiveDate> {
+    let offset = FixedOffset::east_opt(tz_offset)?;
+    let utc = DateTime::from_timestamp(parse_slack_ts(ts)?, 0)?;
+    Some(utc.with_timezone(&offset).date_naive())
+}
+
+// The year in seconds, widened to cover every timezone
+pub fn year_bounds(year: i32) -> (i64, i64) {
+    let start = |year| {
+        NaiveDate::from_ymd_opt(year, 1, 1)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date| date.and_utc().timestamp())
+            .unwrap_or_default()
+    };
+    (
+        start(year) - TIMEZONE_MARGIN_SECS,
+        start(year + 1) + TIMEZONE_MARGIN_SECS,
+    )
+}
+
+async fn list_channels(slack_client: &SlackClient) -> Result<Vec<Channel>, HistoryError> {
+    let mut channels = Vec::new();
+    let mut cursor = None;
+    loop {
+        let params = ConversationsListParams {
+            cursor,
+            limit: Some(200),
+            types: Some("public_channel,private_channel,mpim,im".to_string()),
+            ..Default::default()
+        };
+        let page = match slack_client.conversations().list(params).await? {
+            ConversationsListResponse::Success(page) => page,
+            ConversationsListResponse::Error(error) => {
+                return Err(HistoryError::Slack(error.error))
+            }
+        };
+        channels.extend(page.channels);
+        if page.response_metadata.next_cursor.is_empty() {
+            return Ok(channels);
+        }
+        cursor = Some(page.response_metadata.next_cursor);
+    }
+}
+
+// A channel's messages between the bounds, with their thread replies
+async fn fetch_channel_messages(
+    slack_client: &SlackClient,
+    channel: &str,
+    (oldest, latest): (i64, i64),
+    progress: &mut FetchProgress,
+    on_progress: &mut impl FnMut(FetchProgress),
+) -> Result<Vec<ChannelMessage>, HistoryError> {
+    let mut messages = Vec::new();
+    let mut cursor = None;
+    loop {
+        let params = ConversationsHistoryParams {
+            channel: channel.to_string(),
+            cursor,
+            limit: Some(200),
+            oldest: Some(oldest.to_string()),
+            latest: Some(latest.to_string()),
+            ..Default::default()
+        };
+        let page = match slack_client.conversations().history(params).await? {
+            ConversationsMessagesResponse::Success(page) => page,
+            ConversationsMessagesResponse::Error(error) => {
+                return Err(HistoryError::Slack(error.error))
+            }
+        };
+        progress.pages_fetched += 1;
+        on_progress(*progress);
+        for message in page.messages {
+            if message.reply_count.unwrap_or(0) > 0 {
+                let (replies, pages) = fetch_replies(slack_client, channel, &message.ts).await?;
+                progress.pages_fetched += pages;
+                on_progress(*progress);
+                messages.extend(replies.into_iter().map(|reply| ChannelMessage {
+                    channel: channel.to_string(),
+                    message: reply,
+                }));
+            }
+            messages.push(ChannelMessage {
+                channel: channel.to_string(),
+                message,
+            });
+        }
+        if page.response_metadata.next_cursor.is_empty() {
+            return Ok(messages);
+        }
+        cursor = Some(page.response_metadata.next_cursor);
+    }
+}
+
+// Replies to a thread, without the parent message, and the number of pages
+// they took
+async fn fetch_replies(
+    slack_client: &SlackClient,
+    channel: &str,
+    thread_ts: &str,
+) -> Result<(Vec<MessageData>, u32), HistoryError> {
+    let mut replies = Vec::new();
+    let mut pages = 0;
+    let mut cursor = None;
+    loop {
+        let params = ConversationsRepliesParams {
+            channel: channel.to_string(),
+            ts: thread_ts.to_string(),
+            cursor,
+            limit: Some(200),
+            ..Default::default()
+        };
+        let page = match slack_client.conversations().replies(params).await? {
+            ConversationsMessagesResponse::Success(page) => page,
+            ConversationsMessagesResponse::Error(error) => {
+                return Err(HistoryError::Slack(error.error))
+            }
+        };
+        replies.extend(
+            page.messages
+                .into_iter()
+                .filter(|reply| reply.ts != thread_ts),
+        );
+        pages += 1;
+        if page.response_metadata.next_cursor.is_empty() {
+            return Ok((replies, pages));
+        }
+        cursor = Some(page.response_metadata.next_cursor);
+    }
+}
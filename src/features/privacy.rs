@@ -0,0 +1,233 @@
+// Opting out of, and back into, Slackify Wrapped. People who opt out are
+// left out of leaderboards and shown anonymously in other people's wrapped,
+// and whatever was computed about them is deleted.
+use crate::auth::Session;
+use crate::cards::CardCache;
+use crate::config::AppConfig;
+use crate::consent::{ConsentAction, ConsentChange, ConsentRegistry, ConsentSource};
+use crate::events::EventStore;
+use crate::features::summary_card::card_cache;
+use crate::jobs::queue::JobQueue;
+use crate::jobs::JobStore;
+use crate::slack::commands::{CommandResponse, SlashCommand};
+use crate::slack::signature::SlackForm;
+use chrono::Utc;
+use rocket;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{get, post, Route, State};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct ConsentStatus {
+    pub user_id: String,
+    pub opted_out: bool,
+}
+
+// Records the change. When they opt out, deletes the user's jobs, cards and
+// stored events, and other people's results that name them. Returns false
+// when nothing changed.
+pub fn change_consent(
+    registry: &ConsentRegistry,
+    jobs: &JobStore,
+    events: &EventStore,
+    cards: &CardCache,
+    team_id: &str,
+    change: &ConsentChange,
+) -> Result<bool, Status> {
+    let log = |error: &dyn std::fmt::Display| {
+        println!("Encountered error: {}", error);
+        Status::InternalServerError
+    };
+    let changed = registry.set(team_id, change).map_err(|error| log(&error))?;
+    if change.action == ConsentAction::OptOut {
+        jobs.delete_for_user(team_id, &change.user_id)
+            .map_err(|error| log(&error))?;
+        jobs.delete_naming(team_id, &change.user_id)
+            .map_err(|error| log(&error))?;
+        events
+            .delete_user(team_id, &change.user_id)
+            .map_err(|error| log(&error))?;
+        cards
+            .remove_user(&change.user_id)
+            .map_err(|error| log(&error))?;
+    }
+    Ok(changed)
+}
+
+fn own_change(session: &Session, action: ConsentAction, source: ConsentSource) -> ConsentChange {
+    ConsentChange {
+        user_id: session.user_id.0.clone(),
+        action,
+        actor: session.user_id.0.clone(),
+        source,
+        at: Utc::now(),
+    }
+}
+
+#[get("/privacy")]
+pub fn consent_route(
+    session: Session,
+    registry: &State<ConsentRegistry>,
+) -> Result<Json<ConsentStatus>, Status> {
+    match registry.is_opted_out(&session.team_id.0, &session.user_id.0) {
+        Ok(opted_out) => Ok(Json(ConsentStatus {
+            user_id: session.user_id.0,
+            opted_out,
+        })),
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[post("/privacy/opt-out")]
+pub fn opt_out_route(
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+    events: &State<EventStore>,
+    config: &State<AppConfig>,
+) -> Result<Json<ConsentStatus>, Status> {
+    let change = own_change(&session, ConsentAction::OptOut, ConsentSource::Web);
+    change_consent(
+        registry,
+        &queue.store,
+        events,
+        &card_cache(config),
+        &session.team_id.0,
+        &change,
+    )?;
+    Ok(Json(ConsentStatus {
+        user_id: change.user_id,
+        opted_out: true,
+    }))
+}
+
+#[post("/privacy/opt-in")]
+pub fn opt_in_route(
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+    events: &State<EventStore>,
+    config: &State<AppConfig>,
+) -> Result<Json<ConsentStatus>, Status> {
+    let change = own_change(&session, ConsentAction::OptIn, ConsentSource::Web);
+    change_consent(
+        registry,
+        &queue.store,
+        events,
+        &card_cache(config),
+        &session.team_id.0,
+        &change,
+    )?;
+    Ok(Json(ConsentStatus {
+        user_id: change.user_id,
+        opted_out: false,
+    }))
+}
+
+// Every consent change in the workspace, for admins
+#[get("/privacy/audit")]
+pub fn audit_route(
+    session: Session,
+    registry: &State<ConsentRegistry>,
+) -> Result<Json<Vec<ConsentChange>>, Status> {
+    session.authorize_admin()?;
+    match registry.audit_log(&session.team_id.0) {
+        Ok(changes) => Ok(Json(changes)),
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+// `/wrapped-privacy opt-out`, `/wrapped-privacy opt-in`, or anything else for
+// the current setting. Also answers the command over Socket Mode.
+pub fn privacy_command(
+    command: &SlashCommand,
+    registry: &ConsentRegistry,
+    queue: &JobQueue,
+    events: &EventStore,
+    config: &AppConfig,
+) -> CommandResponse {
+    let action = match command.text.trim() {
+        "opt-out" | "optout" => Some(ConsentAction::OptOut),
+        "opt-in" | "optin" => Some(ConsentAction::OptIn),
+        _ => None,
+    };
+    let Some(action) = action else {
+        let text = match registry.is_opted_out(&command.team_id, &command.user_id) {
+            Ok(true) => "You have opted out of Slackify Wrapped.",
+            Ok(false) => "You are included in Slackify Wrapped.",
+            Err(error) => {
+                println!("Encountered error: {}", error);
+                "Could not look up your privacy setting, please try again."
+            }
+        };
+        return CommandResponse::ephemeral(format!(
+            "{} Use `{} opt-out` or `{} opt-in` to change it.",
+            text, command.command, command.command
+        ));
+    };
+    command_consent(command, action, registry, queue, events, config)
+}
+
+// Applies a change the user asked for with a slash command, `/wrapped optout`
+// included
+pub fn command_consent(
+    command: &SlashCommand,
+    action: ConsentAction,
+    registry: &ConsentRegistry,
+    queue: &JobQueue,
+    events: &EventStore,
+    config: &AppConfig,
+) -> CommandResponse {
+    let change = ConsentChange {
+        user_id: command.user_id.clone(),
+        action,
+        actor: command.user_id.clone(),
+        source: ConsentSource::SlashCommand,
+        at: Utc::now(),
+    };
+    let result = change_consent(
+        registry,
+        &queue.store,
+        events,
+        &card_cache(config),
+        &command.team_id,
+        &change,
+    );
+    CommandResponse::ephemeral(match (result, action) {
+        (Ok(_), ConsentAction::OptOut) => {
+            "You have opted out. You are left out of leaderboards, other people's wrapped \
+                show you anonymously, and what was computed about you has been deleted."
+        }
+        (Ok(_), ConsentAction::OptIn) => "You are included in Slackify Wrapped again.",
+        (Err(_), _) => "Could not change your privacy setting, please try again.",
+    })
+}
+
+#[post("/slack/commands/privacy", data = "<command>")]
+pub fn privacy_command_route(
+    command: SlackForm<SlashCommand>,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+    events: &State<EventStore>,
+    config: &State<AppConfig>,
+) -> Json<CommandResponse> {
+    let SlackForm(command) = command;
+    Json(privacy_command(&command, registry, queue, events, config))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        consent_route,
+        opt_out_route,
+        opt_in_route,
+        audit_route,
+        privacy_command_route
+    ]
+}
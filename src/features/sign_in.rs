@@ -0,0 +1,172 @@
+use crate::auth::{random_token, tokens_match, Session, TeamId, UserId};
+use crate::config::AppConfig;
+use crate::slack::client::SlackClients;
+use crate::slack::openid::{
+    OpenidConnectTokenParams, OpenidConnectTokenResponse, OpenidConnectUserInfoResponse,
+};
+use crate::slack::users::{UsersInfoParams, UsersInfoResponse};
+use crate::slack::util::add_param_to_url;
+use crate::workspaces::resolver::resolve_token;
+use crate::workspaces::TokenStore;
+use chrono::{Datelike, Utc};
+use reqwest::Url;
+use rocket;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::response::Redirect;
+use rocket::time::Duration;
+use rocket::{get, post, Route, State};
+
+const AUTHORIZE_URL: &str = "https://slack.com/openid/connect/authorize";
+const STATE_COOKIE: &str = "slack_sign_in_state";
+const STATE_TTL_MINUTES: i64 = 10;
+
+pub fn authorize_url(config: &AppConfig, client_id: &str, state: &str) -> String {
+    let mut url = Url::parse(AUTHORIZE_URL).expect("Unable to parse URL");
+    add_param_to_url(&mut url, "response_type", &Some("code".to_string()));
+    add_param_to_url(&mut url, "scope", &Some("openid profile".to_string()));
+    add_param_to_url(&mut url, "client_id", &Some(client_id.to_string()));
+    add_param_to_url(&mut url, "redirect_uri", &config.slack.sign_in_redirect_uri);
+    add_param_to_url(&mut url, "state", &Some(state.to_string()));
+    url.to_string()
+}
+
+// Admin status is not part of the OpenID claims, so it is looked up with
+// the workspace's token. Anyone who cannot be looked up is not an admin.
+async fn is_admin(
+    config: &AppConfig,
+    store: &TokenStore,
+    clients: &SlackClients,
+    user_id: &str,
+    team_id: &str,
+) -> bool {
+    let Ok(token) = resolve_token(Some(store), Some(team_id), config.slack.token.as_deref()) else {
+        return false;
+    };
+    let params = UsersInfoParams {
+        user: user_id.to_string(),
+        ..Default::default()
+    };
+    match clients.get(&token).users().info(params).await {
+        Ok(UsersInfoResponse::Success(info)) => info.user.is_admin || info.user.is_owner,
+        _ => false,
+    }
+}
+
+#[get("/auth/slack/login")]
+pub fn login_route(
+    cookies: &CookieJar<'_>,
+    config: &State<AppConfig>,
+) -> Result<Redirect, (Status, String)> {
+    let Some(credentials) = config.oauth() else {
+        return Err((
+            Status::ServiceUnavailable,
+            "Please set slack.client_id and slack.client_secret".to_string(),
+        ));
+    };
+    let state = random_token();
+    cookies.add(
+        Cookie::build((STATE_COOKIE, state.clone()))
+            .path("/auth/slack")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .max_age(Duration::minutes(STATE_TTL_MINUTES)),
+    );
+    Ok(Redirect::to(authorize_url(
+        config,
+        &credentials.client_id,
+        &state,
+    )))
+}
+
+#[get("/auth/slack/callback?<code>&<state>&<error>")]
+pub async fn sign_in_callback_route(
+    code: Option<&str>,
+    state: Option<&str>,
+    error: Option<&str>,
+    cookies: &CookieJar<'_>,
+    store: &State<TokenStore>,
+    clients: &State<SlackClients>,
+    config: &State<AppConfig>,
+) -> Result<Redirect, (Status, String)> {
+    let expected_state = cookies
+        .get(STATE_COOKIE)
+        .map(|cookie| cookie.value().to_string());
+    cookies.remove(Cookie::build(STATE_COOKIE).path("/auth/slack"));
+
+    if let Some(error) = error {
+        return Err((Status::BadRequest, format!("Sign in cancelled: {}", error)));
+    }
+    match (expected_state, state) {
+        (Some(expected), Some(state)) if tokens_match(&expected, state) => {}
+        _ => {
+            return Err((
+                Status::Forbidden,
+                "Sign in link expired or was not started here, please try again".to_string(),
+            ))
+        }
+    }
+    let (Some(code), Some(credentials)) = (code, config.oauth()) else {
+        return Err((Status::BadRequest, "Missing OAuth code".to_string()));
+    };
+
+    let openid = clients.openid();
+    let params = OpenidConnectTokenParams {
+        client_id: credentials.client_id,
+        client_secret: credentials.client_secret,
+        code: code.to_string(),
+        redirect_uri: config.slack.sign_in_redirect_uri.clone(),
+    };
+    let token = match openid.connect_token(params).await {
+        Ok(OpenidConnectTokenResponse::Success(token)) => token,
+        Ok(OpenidConnectTokenResponse::Error(error)) => {
+            println!("Error: {:?}", error);
+            return Err((Status::BadGateway, "Slack rejected the sign in".to_string()));
+        }
+        Err(error) => {
+            println!("Error: {:?}", error);
+            return Err((Status::BadGateway, "Could not reach Slack".to_string()));
+        }
+    };
+    let user_info = match openid.connect_user_info(&token.access_token).await {
+        Ok(OpenidConnectUserInfoResponse::Success(user_info)) => user_info,
+        Ok(OpenidConnectUserInfoResponse::Error(error)) => {
+            println!("Error: {:?}", error);
+            return Err((Status::BadGateway, "Slack rejected the sign in".to_string()));
+        }
+        Err(error) => {
+            println!("Error: {:?}", error);
+            return Err((Status::BadGateway, "Could not reach Slack".to_string()));
+        }
+    };
+
+    let is_admin = is_admin(
+        config,
+        store,
+        clients,
+        &user_info.user_id,
+        &user_info.team_id,
+    )
+    .await;
+    let session = Session::new(
+        UserId(user_info.user_id),
+        TeamId(user_info.team_id),
+        is_admin,
+        Utc::now(),
+    );
+    session.store(cookies);
+    Ok(Redirect::to(format!(
+        "/wrapped/{}/{}",
+        session.user_id,
+        Utc::now().year()
+    )))
+}
+
+#[post("/auth/logout")]
+pub fn logout_route(cookies: &CookieJar<'_>) -> &'static str {
+    Session::clear(cookies);
+    "Signed out"
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![login_route, sign_in_callback_route, logout_route]
+}
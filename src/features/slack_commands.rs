@@ -0,0 +1,367 @@
+// The /wrapped slash command, how most people come across the tool. Slack
+// waits three seconds for an answer, so the command is acknowledged straight
+// away and the wrapped follows through the command's response_url.
+use crate::config::{AppConfig, PrivacyConfig};
+use crate::consent::{ConsentAction, ConsentRegistry};
+use crate::events::EventStore;
+use crate::features::dm_wrapped::wrapped_blocks;
+use crate::features::history::{HistoryError, YearHistory};
+use crate::features::privacy::command_consent;
+use crate::features::workspace_wrapped::WorkspaceWrapped;
+use crate::features::wrapped::fetch_user;
+use crate::jobs::queue::JobQueue;
+use crate::slack::blocks::{Block, ContextElement, Text};
+use crate::slack::client::{SlackClient, SlackClients};
+use crate::slack::commands::{respond, CommandResponse, SlashCommand};
+use crate::slack::signature::SlackForm;
+use crate::workspaces::resolver::resolve_token;
+use crate::workspaces::TokenStore;
+use chrono::{Datelike, NaiveDate, Utc};
+use rocket;
+use rocket::serde::json::Json;
+use rocket::{post, Route, State};
+use std::collections::HashSet;
+
+// Entries shown in each of the channel's lists
+const CHANNEL_TOP: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum WrappedCommand {
+    Own,
+    User(String),
+    // The channel the command was run in
+    Channel,
+    OptOut,
+    Help,
+}
+
+impl WrappedCommand {
+    // Mentions come as `<@U123|ada>` when the command escapes them, plain
+    // `@ada` cannot be looked up
+    pub fn parse(text: &str) -> Self {
+        match text.trim() {
+            "" => WrappedCommand::Own,
+            "channel" => WrappedCommand::Channel,
+            "optout" | "opt-out" => WrappedCommand::OptOut,
+            text => match text
+                .strip_prefix("<@")
+                .and_then(|rest| rest.strip_suffix('>'))
+            {
+                Some(mention) => {
+                    let user_id = mention.split('|').next().unwrap_or_default();
+                    WrappedCommand::User(user_id.to_string())
+                }
+                None => WrappedCommand::Help,
+            },
+        }
+    }
+}
+
+// A year at the end of the command, as in `/wrapped channel 2023`
+pub fn split_year(text: &str) -> (&str, Option<i32>) {
+    let text = text.trim();
+    let (rest, last) = text.rsplit_once(' ').unwrap_or(("", text));
+    match last.parse() {
+        Ok(year) if last.len() == 4 => (rest, Some(year)),
+        _ => (text, None),
+    }
+}
+
+// The year that just ended while January lasts, the current one after
+pub fn default_year(today: NaiveDate) -> i32 {
+    match today.month() {
+        1 => today.year() - 1,
+        _ => today.year(),
+    }
+}
+
+// What the answer sent later is about
+enum Subject {
+    User(String),
+    Channel(String),
+}
+
+fn usage(command: &str) -> String {
+    format!(
+        "Use `{0}` for your year in Slack, `{0} @someone` for theirs (admins only), \
+            `{0} channel` for this channel's, or `{0} optout` to be left out of Slackify Wrapped. \
+            Add a year, as in `{0} 2023`, for an earlier one.",
+        command
+    )
+}
+
+pub fn channel_blocks(name: Option<&str>, wrapped: &WorkspaceWrapped) -> Vec<Block> {
+    let title = name.map_or("This channel".to_string(), |name| format!("#{}", name));
+    let mut blocks = vec![
+        Block::header(format!("{}'s {} :sparkles:", title, wrapped.year)),
+        Block::section(Text::mrkdwn(format!(
+            "*{} messages* from *{} people*, and *{} reactions*.",
+            wrapped.message_count, wrapped.active_users, wrapped.reaction_count
+        ))),
+    ];
+    let mut fields = Vec::new();
+    if !wrapped.top_emoji.is_empty() {
+        let emoji: Vec<String> = wrapped
+            .top_emoji
+            .iter()
+            .take(CHANNEL_TOP)
+            .map(|(name, count)| format!(":{}: × {}", name, count))
+            .collect();
+        fields.push(Text::mrkdwn(format!(
+            "*Favourite reactions*\n{}",
+            emoji.join("\n")
+        )));
+    }
+    if !wrapped.top_reactors.is_empty() {
+        let reactors: Vec<String> = wrapped
+            .top_reactors
+            .iter()
+            .take(CHANNEL_TOP)
+            .map(|(user_id, count)| format!("<@{}> ({})", user_id, count))
+            .collect();
+        fields.push(Text::mrkdwn(format!(
+            "*Most reactions given*\n{}",
+            reactors.join("\n")
+        )));
+    }
+    if !fields.is_empty() {
+        blocks.push(Block::fields(fields));
+    }
+    blocks
+}
+
+// Reuses a fresh wrapped when there is one, or queues it, and answers once
+// it is done
+async fn user_answer(
+    slack_client: &SlackClient,
+    config: &AppConfig,
+    queue: &JobQueue,
+    (team_id, invoker): (&str, &str),
+    user_id: &str,
+    year: i32,
+    opted_out: HashSet<String>,
+) -> CommandResponse {
+    // The same rule as the web pages, only admins see other people's
+    if user_id != invoker {
+        let is_admin = fetch_user(slack_client, invoker)
+            .await
+            .is_ok_and(|user| user.is_admin || user.is_owner);
+        if !is_admin {
+            return CommandResponse::ephemeral(
+                "Only workspace admins can see other people's wrapped.",
+            );
+        }
+    }
+    let now = Utc::now();
+    let job = match queue.store.reusable_or_create(
+        team_id,
+        user_id,
+        year,
+        invoker,
+        config.cache.fresh_since(now),
+        now,
+    ) {
+        Ok((job, created)) => {
+            if created {
+                queue.submit(&job, slack_client.clone(), opted_out);
+            }
+            job
+        }
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return CommandResponse::ephemeral("Could not build the wrapped, please try again.");
+        }
+    };
+    let wrapped = match queue.result(&job.id).await {
+        Ok(wrapped) => wrapped,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return CommandResponse::ephemeral("Could not build the wrapped, please try again.");
+        }
+    };
+    let mut blocks = wrapped_blocks(&wrapped, config, team_id);
+    if user_id != invoker {
+        blocks.insert(
+            0,
+            Block::context(vec![ContextElement::Text(Text::mrkdwn(format!(
+                "The wrapped of <@{}>",
+                user_id
+            )))]),
+        );
+    }
+    CommandResponse::ephemeral(format!(
+        "{} in Slack: {} messages",
+        wrapped.year, wrapped.message_count
+    ))
+    .blocks(blocks)
+}
+
+async fn channel_answer(
+    slack_client: &SlackClient,
+    channel_id: &str,
+    year: i32,
+    privacy: &PrivacyConfig,
+    opted_out: &HashSet<String>,
+) -> CommandResponse {
+    let history = match YearHistory::fetch_channel(slack_client, channel_id, year).await {
+        Ok(history) => history,
+        Err(HistoryError::Slack(error)) if error == "channel_not_found" => {
+            return CommandResponse::ephemeral("I can only wrap up channels I have been added to.");
+        }
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return CommandResponse::ephemeral(
+                "Could not read this channel's history, please try again.",
+            );
+        }
+    };
+    let wrapped = WorkspaceWrapped::compute(&history, privacy, opted_out);
+    let name = history
+        .channel(channel_id)
+        .and_then(|channel| channel.name.as_deref());
+    CommandResponse::ephemeral(format!(
+        "This channel's {}: {} messages",
+        wrapped.year, wrapped.message_count
+    ))
+    .blocks(channel_blocks(name, &wrapped))
+}
+
+// `/wrapped`, `/wrapped @someone` and `/wrapped channel`, each with an optional
+// year, and `/wrapped optout`.
+// Also answers the command over Socket Mode.
+pub fn wrapped_command(
+    command: SlashCommand,
+    store: &TokenStore,
+    clients: &SlackClients,
+    registry: &ConsentRegistry,
+    queue: &JobQueue,
+    events: &EventStore,
+    config: &AppConfig,
+) -> CommandResponse {
+    let today = Utc::now().date_naive();
+    let (text, year) = split_year(&command.text);
+    let year = year.unwrap_or(default_year(today));
+    if year > today.year() {
+        return CommandResponse::ephemeral(usage(&command.command));
+    }
+    let subject = match WrappedCommand::parse(text) {
+        WrappedCommand::Own => Subject::User(command.user_id.clone()),
+        WrappedCommand::User(user_id) => Subject::User(user_id),
+        WrappedCommand::Channel => Subject::Channel(command.channel_id.clone()),
+        WrappedCommand::OptOut => {
+            return command_consent(
+                &command,
+                ConsentAction::OptOut,
+                registry,
+                queue,
+                events,
+                config,
+            );
+        }
+        WrappedCommand::Help => {
+            return CommandResponse::ephemeral(usage(&command.command));
+        }
+    };
+
+    let opted_out = match registry.opted_out(&command.team_id) {
+        Ok(opted_out) => opted_out,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return CommandResponse::ephemeral(
+                "Could not look up privacy settings, please try again.",
+            );
+        }
+    };
+    // Nothing is computed about people who opted out
+    if let Subject::User(user_id) = &subject {
+        if opted_out.contains(user_id) {
+            return CommandResponse::ephemeral(match *user_id == command.user_id {
+                true => "You have opted out of Slackify Wrapped.",
+                false => "They have opted out of Slackify Wrapped.",
+            });
+        }
+    }
+    let token = match resolve_token(
+        Some(store),
+        Some(&command.team_id),
+        config.slack.token.as_deref(),
+    ) {
+        Ok(token) => token,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return CommandResponse::ephemeral(
+                "Slackify Wrapped is not installed in this workspace yet.",
+            );
+        }
+    };
+
+    let acknowledgement = match subject {
+        Subject::User(_) => "Wrapping up the year, this can take a minute :hourglass_flowing_sand:",
+        Subject::Channel(_) => {
+            "Wrapping up this channel's year, this can take a minute :hourglass_flowing_sand:"
+        }
+    };
+    let slack_client = clients.get(&token);
+    let config = config.clone();
+    let waiting = queue.clone();
+    // Channels are crawled on one of the queue's workers, a person's wrapped
+    // is a job of its own and only waited for here
+    let crawls = matches!(subject, Subject::Channel(_));
+    let answer = async move {
+        let answer = match subject {
+            Subject::User(user_id) => {
+                user_answer(
+                    &slack_client,
+                    &config,
+                    &waiting,
+                    (&command.team_id, &command.user_id),
+                    &user_id,
+                    year,
+                    opted_out,
+                )
+                .await
+            }
+            Subject::Channel(channel_id) => {
+                channel_answer(
+                    &slack_client,
+                    &channel_id,
+                    year,
+                    &config.privacy,
+                    &opted_out,
+                )
+                .await
+            }
+        };
+        let answer = answer.replace_original();
+        if let Err(error) = respond(&slack_client.client, &command.response_url, &answer).await {
+            println!("Encountered error: {}", error);
+        }
+    };
+    match crawls {
+        true => queue.spawn(answer),
+        false => {
+            rocket::tokio::spawn(answer);
+        }
+    }
+    CommandResponse::ephemeral(acknowledgement)
+}
+
+#[post("/slack/commands", data = "<command>")]
+pub fn command_route(
+    command: SlackForm<SlashCommand>,
+    store: &State<TokenStore>,
+    clients: &State<SlackClients>,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+    events: &State<EventStore>,
+    config: &State<AppConfig>,
+) -> Json<CommandResponse> {
+    let SlackForm(command) = command;
+    Json(wrapped_command(
+        command, store, clients, registry, queue, events, config,
+    ))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![command_route]
+}
@@ -0,0 +1,60 @@
+// Receives the Events API, so activity is stored as it happens instead of
+// only by crawling history.
+// https://api.slack.com/apis/events-api
+use crate::consent::ConsentRegistry;
+use crate::events::{EventStore, EventStoreError};
+use crate::slack::events::{EventPayload, UrlVerificationResponse};
+use crate::slack::signature::SlackJson;
+use chrono::Utc;
+use rocket;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{post, Responder, Route, State};
+
+#[derive(Responder)]
+pub enum EventResponse {
+    Challenge(Json<UrlVerificationResponse>),
+    // An empty 200
+    Acknowledged(()),
+}
+
+// Slack expects an answer within three seconds, and retries otherwise. An
+// event that cannot be parsed is still acknowledged, a retry would not fix
+// it.
+#[post("/slack/events", data = "<payload>")]
+pub fn events_route(
+    payload: SlackJson<EventPayload>,
+    store: &State<EventStore>,
+    registry: &State<ConsentRegistry>,
+) -> Result<EventResponse, Status> {
+    let SlackJson(payload) = payload;
+    match payload {
+        EventPayload::UrlVerification { challenge } => {
+            Ok(EventResponse::Challenge(Json(UrlVerificationResponse {
+                challenge,
+            })))
+        }
+        EventPayload::EventCallback(callback) => {
+            let opted_out = registry.opted_out(&callback.team_id).map_err(|error| {
+                println!("Encountered error: {}", error);
+                Status::InternalServerError
+            })?;
+            match store.handle(&callback, &opted_out, Utc::now()) {
+                Ok(_) => Ok(EventResponse::Acknowledged(())),
+                Err(error @ EventStoreError::Json(_)) => {
+                    println!("Ignoring event {}: {}", callback.event_id, error);
+                    Ok(EventResponse::Acknowledged(()))
+                }
+                Err(error) => {
+                    println!("Encountered error: {}", error);
+                    Err(Status::InternalServerError)
+                }
+            }
+        }
+        EventPayload::Other => Ok(EventResponse::Acknowledged(())),
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![events_route]
+}
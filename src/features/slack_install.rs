@@ -0,0 +1,166 @@
+use crate::auth::{random_token, tokens_match};
+use crate::config::AppConfig;
+use crate::slack::client::SlackClients;
+use crate::slack::oauth::{OauthV2AccessParams, OauthV2AccessResponse, OauthV2AccessSuccess};
+use crate::slack::util::add_param_to_url;
+use crate::workspaces::{Installation, TokenStore};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use rocket;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::response::Redirect;
+use rocket::time::Duration;
+use rocket::{get, Route, State};
+
+const AUTHORIZE_URL: &str = "https://slack.com/oauth/v2/authorize";
+const STATE_COOKIE: &str = "slack_oauth_state";
+const STATE_TTL_MINUTES: i64 = 10;
+
+pub fn authorize_url(config: &AppConfig, client_id: &str, state: &str) -> String {
+    let mut url = Url::parse(AUTHORIZE_URL).expect("Unable to parse URL");
+    add_param_to_url(&mut url, "client_id", &Some(client_id.to_string()));
+    add_param_to_url(&mut url, "scope", &Some(config.slack.bot_scopes.clone()));
+    add_param_to_url(
+        &mut url,
+        "user_scope",
+        &Some(config.slack.user_scopes.clone()),
+    );
+    add_param_to_url(&mut url, "redirect_uri", &config.slack.install_redirect_uri);
+    add_param_to_url(&mut url, "state", &Some(state.to_string()));
+    url.to_string()
+}
+
+pub fn installation(access: OauthV2AccessSuccess, installed_at: DateTime<Utc>) -> Installation {
+    Installation {
+        team_id: access.team.id,
+        team_name: access.team.name,
+        enterprise_id: access.enterprise.map(|enterprise| enterprise.id),
+        app_id: access.app_id,
+        bot_user_id: access.bot_user_id,
+        bot_token: access.access_token,
+        bot_scopes: split_scopes(&access.scope),
+        installer_user_id: access.authed_user.id,
+        user_token: access.authed_user.access_token,
+        user_scopes: split_scopes(&access.authed_user.scope.unwrap_or_default()),
+        installed_at,
+    }
+}
+
+fn split_scopes(scopes: &str) -> Vec<String> {
+    scopes
+        .split(',')
+        .filter(|scope| !scope.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// The state is also kept in a cookie, so the callback only accepts
+// installs that were started from this browser
+#[get("/slack/install")]
+pub fn install_route(
+    cookies: &CookieJar<'_>,
+    config: &State<AppConfig>,
+) -> Result<Redirect, (Status, String)> {
+    let Some(credentials) = config.oauth() else {
+        return Err((
+            Status::ServiceUnavailable,
+            "Please set slack.client_id and slack.client_secret".to_string(),
+        ));
+    };
+    let state = random_token();
+    cookies.add(
+        Cookie::build((STATE_COOKIE, state.clone()))
+            .path("/slack/oauth")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .max_age(Duration::minutes(STATE_TTL_MINUTES)),
+    );
+    Ok(Redirect::to(authorize_url(
+        config,
+        &credentials.client_id,
+        &state,
+    )))
+}
+
+#[get("/slack/oauth/callback?<code>&<state>&<error>")]
+pub async fn oauth_callback_route(
+    code: Option<&str>,
+    state: Option<&str>,
+    error: Option<&str>,
+    cookies: &CookieJar<'_>,
+    store: &State<TokenStore>,
+    clients: &State<SlackClients>,
+    config: &State<AppConfig>,
+) -> (Status, String) {
+    let expected_state = cookies
+        .get(STATE_COOKIE)
+        .map(|cookie| cookie.value().to_string());
+    cookies.remove(Cookie::build(STATE_COOKIE).path("/slack/oauth"));
+
+    if let Some(error) = error {
+        return (
+            Status::BadRequest,
+            format!("Installation cancelled: {}", error),
+        );
+    }
+    match (expected_state, state) {
+        (Some(expected), Some(state)) if tokens_match(&expected, state) => {}
+        _ => {
+            return (
+                Status::Forbidden,
+                "Installation link expired or was not started here, please try again".to_string(),
+            )
+        }
+    }
+    let (Some(code), Some(credentials)) = (code, config.oauth()) else {
+        return (Status::BadRequest, "Missing OAuth code".to_string());
+    };
+
+    let params = OauthV2AccessParams {
+        client_id: credentials.client_id,
+        client_secret: credentials.client_secret,
+        code: code.to_string(),
+        redirect_uri: config.slack.install_redirect_uri.clone(),
+    };
+    let access = match clients.oauth().v2_access(params).await {
+        Ok(OauthV2AccessResponse::Success(access)) => access,
+        Ok(OauthV2AccessResponse::Error(error)) => {
+            println!("Error: {:?}", error);
+            return (
+                Status::BadGateway,
+                "Slack rejected the installation".to_string(),
+            );
+        }
+        Err(error) => {
+            println!("Error: {:?}", error);
+            return (Status::BadGateway, "Could not reach Slack".to_string());
+        }
+    };
+
+    let installation = installation(access, Utc::now());
+    let team = installation
+        .team_name
+        .clone()
+        .unwrap_or(installation.team_id.clone());
+    // A reinstall replaces the token, so the old one's client is not needed
+    if let Some(previous) = store.get(&installation.team_id) {
+        clients.remove(&previous.bot_token);
+    }
+    match store.save(installation) {
+        Ok(()) => (
+            Status::Ok,
+            format!("Slackify Wrapped is installed in {}", team),
+        ),
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            (
+                Status::InternalServerError,
+                "Could not save the installation".to_string(),
+            )
+        }
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![install_route, oauth_callback_route]
+}
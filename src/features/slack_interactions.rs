@@ -0,0 +1,441 @@
+// The buttons under a DM'd wrapped. "Next stat" steps through the stats one
+// card at a time, "Share" posts the card shown to the share channel, and
+// "Regenerate" computes the wrapped again. Button values carry the job the
+// wrapped was stored under, so only what was computed is shown.
+// https://api.slack.com/interactivity/handling
+use crate::api::v1::WrappedDocument;
+use crate::auth::random_token;
+use crate::config::AppConfig;
+use crate::consent::ConsentRegistry;
+use crate::features::wrapped::ANONYMOUS;
+use crate::jobs::queue::JobQueue;
+use crate::jobs::Job;
+use crate::slack::blocks::{escape_mrkdwn, Block, ContextElement, Element, Text};
+use crate::slack::chat::{
+    ChatPostMessageParams, ChatPostMessageResponse, ChatUpdateParams, ChatUpdateResponse,
+};
+use crate::slack::client::{SlackClient, SlackClients};
+use crate::slack::commands::{respond, CommandResponse};
+use crate::slack::interactions::{BlockAction, BlockActions, InteractionForm, InteractionPayload};
+use crate::slack::signature::SlackForm;
+use crate::workspaces::resolver::resolve_token;
+use crate::workspaces::TokenStore;
+use chrono::Utc;
+use rocket;
+use rocket::http::Status;
+use rocket::{post, Route, State};
+use std::collections::HashSet;
+
+pub const NEXT_STAT: &str = "wrapped_next_stat";
+pub const SHARE: &str = "wrapped_share";
+pub const REGENERATE: &str = "wrapped_regenerate";
+
+// Overview, channels, reactions, people, streaks, threads and words
+pub const STAT_PAGES: usize = 7;
+// Entries shown on each card
+const TOP: usize = 3;
+
+const GONE: &str = "This wrapped is no longer available, run `/wrapped` for a new one.";
+
+// The buttons for a message showing `page`
+pub fn wrapped_actions(job_id: &str, page: usize, share_channel: &str) -> Block {
+    let next = (page + 1) % STAT_PAGES;
+    Block::actions(vec![
+        Element::button("Next stat", NEXT_STAT).value(format!("{}:{}", job_id, next)),
+        Element::button(format!("Share to #{}", share_channel), SHARE)
+            .value(format!("{}:{}", job_id, page)),
+        Element::button("Regenerate", REGENERATE).value(job_id),
+    ])
+    .block_id("wrapped_actions")
+}
+
+// `job:page`, or a bare job ID for the overview
+fn parse_value(value: &str) -> (&str, usize) {
+    match value.split_once(':') {
+        Some((job_id, page)) => (job_id, page.parse().unwrap_or(0) % STAT_PAGES),
+        None => (value, 0),
+    }
+}
+
+fn person(user_id: &str) -> String {
+    match user_id {
+        ANONYMOUS => ANONYMOUS.to_string(),
+        user_id => format!("<@{}>", user_id),
+    }
+}
+
+// One stat of the wrapped, with a header naming it
+pub fn stat_blocks(document: &WrappedDocument, page: usize) -> Vec<Block> {
+    let lines = |lines: Vec<String>, empty: &str| match lines.is_empty() {
+        true => empty.to_string(),
+        false => lines.join("\n"),
+    };
+    let (title, body) = match page % STAT_PAGES {
+        0 => (
+            "Your year",
+            format!(
+                "*{} messages* across *{} active days*.",
+                document.message_count, document.streaks.active_days
+            ),
+        ),
+        1 => (
+            "Your channels",
+            lines(
+                document
+                    .top_channels
+                    .iter()
+                    .take(TOP)
+                    .map(|channel| {
+                        format!(
+                            "#{}: {} messages",
+                            escape_mrkdwn(&channel.name),
+                            channel.messages
+                        )
+                    })
+                    .collect(),
+                "No channels this year.",
+            ),
+        ),
+        2 => (
+            "Your reactions",
+            lines(
+                document
+                    .reactions
+                    .iter()
+                    .take(TOP)
+                    .map(|reaction| format!(":{}: × {}", reaction.name, reaction.count))
+                    .collect(),
+                "No reactions this year.",
+            ),
+        ),
+        3 => (
+            "Your people",
+            lines(
+                document
+                    .collaborators
+                    .iter()
+                    .take(TOP)
+                    .map(|collaborator| person(&collaborator.user_id))
+                    .collect(),
+                "Nobody stood out this year.",
+            ),
+        ),
+        4 => (
+            "Your streaks",
+            format!(
+                "Longest streak: *{} days*{}",
+                document
+                    .streaks
+                    .longest
+                    .as_ref()
+                    .map_or(0, |streak| streak.days),
+                document
+                    .streaks
+                    .busiest_day
+                    .as_ref()
+                    .map_or(String::new(), |day| format!(
+                        "\nBusiest day: *{}*, with {} messages",
+                        day.date.format("%B %-d"),
+                        day.message_count
+                    ))
+            ),
+        ),
+        5 => (
+            "Your threads",
+            format!(
+                "*{}* started, *{}* joined{}",
+                document.threads.threads_started,
+                document.threads.threads_replied,
+                document
+                    .threads
+                    .longest_thread
+                    .as_ref()
+                    .map_or(String::new(), |thread| match &thread.permalink {
+                        Some(permalink) => format!(
+                            "\n<{}|Longest thread>: {} replies",
+                            permalink, thread.reply_count
+                        ),
+                        None => format!("\nLongest thread: {} replies", thread.reply_count),
+                    })
+            ),
+        ),
+        _ => (
+            "Your words",
+            lines(
+                document
+                    .word_cloud
+                    .words
+                    .iter()
+                    .take(TOP)
+                    .map(|term| format!("“{}” × {}", escape_mrkdwn(&term.term), term.count))
+                    .collect(),
+                "Not enough words this year.",
+            ),
+        ),
+    };
+    vec![
+        Block::header(format!("{} in {}", title, document.year)),
+        Block::section(Text::mrkdwn(body)),
+    ]
+}
+
+// The whole message for a page, with its buttons
+fn stat_message(
+    job_id: &str,
+    document: &WrappedDocument,
+    page: usize,
+    share_channel: &str,
+) -> (String, Vec<Block>) {
+    let mut blocks = stat_blocks(document, page);
+    blocks.push(wrapped_actions(job_id, page, share_channel));
+    let text = format!(
+        "Your {} in Slack: {} messages",
+        document.year, document.message_count
+    );
+    (text, blocks)
+}
+
+async fn update(
+    slack_client: &SlackClient,
+    channel: &str,
+    ts: &str,
+    text: String,
+    blocks: Vec<Block>,
+) {
+    let params = ChatUpdateParams {
+        channel: channel.to_string(),
+        ts: ts.to_string(),
+        text,
+        blocks: Some(blocks),
+    };
+    match slack_client.chat().update(params).await {
+        Ok(ChatUpdateResponse::Success(_)) => {}
+        Ok(ChatUpdateResponse::Error(error)) => println!("Error: {:?}", error.error),
+        Err(error) => println!("Encountered error: {}", error),
+    }
+}
+
+async fn reply(slack_client: &SlackClient, actions: &BlockActions, text: &str) {
+    let Some(response_url) = &actions.response_url else {
+        return;
+    };
+    let response = CommandResponse::ephemeral(text);
+    if let Err(error) = respond(&slack_client.client, response_url, &response).await {
+        println!("Encountered error: {}", error);
+    }
+}
+
+// Computes the wrapped again and shows its overview once done
+async fn regenerate(
+    slack_client: SlackClient,
+    queue: &JobQueue,
+    previous: &Job,
+    opted_out: HashSet<String>,
+    (channel, ts): (&str, &str),
+    share_channel: &str,
+) -> Result<(), String> {
+    let job = queue
+        .store
+        .create(
+            &random_token(),
+            &previous.team_id,
+            &previous.user_id,
+            previous.year,
+            &previous.requested_by,
+            Utc::now(),
+        )
+        .map_err(|error| error.to_string())?;
+    let waiting = vec![Block::context(vec![ContextElement::Text(Text::mrkdwn(
+        "Regenerating your wrapped :hourglass_flowing_sand:",
+    ))])];
+    update(
+        &slack_client,
+        channel,
+        ts,
+        "Regenerating your wrapped".to_string(),
+        waiting,
+    )
+    .await;
+    queue.submit(&job, slack_client.clone(), opted_out);
+    let document = queue.result(&job.id).await?;
+    let (text, blocks) = stat_message(&job.id, &document, 0, share_channel);
+    update(&slack_client, channel, ts, text, blocks).await;
+    Ok(())
+}
+
+async fn act(
+    slack_client: SlackClient,
+    queue: JobQueue,
+    actions: BlockActions,
+    action: BlockAction,
+    opted_out: HashSet<String>,
+    share_channel: String,
+) {
+    let (job_id, page) = parse_value(action.value.as_deref().unwrap_or_default());
+    // Only the person the wrapped is about can use its buttons
+    let job = match queue.store.get(job_id) {
+        Ok(Some(job)) if job.team_id == actions.team.id && job.user_id == actions.user.id => job,
+        Ok(_) => return reply(&slack_client, &actions, GONE).await,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return reply(
+                &slack_client,
+                &actions,
+                "Something went wrong, please try again.",
+            )
+            .await;
+        }
+    };
+    let message = (
+        actions.container.channel_id.as_deref(),
+        actions.container.message_ts.as_deref(),
+    );
+    let (Some(channel), Some(ts)) = message else {
+        return;
+    };
+    if action.action_id == REGENERATE {
+        if let Err(error) = regenerate(
+            slack_client.clone(),
+            &queue,
+            &job,
+            opted_out,
+            (channel, ts),
+            &share_channel,
+        )
+        .await
+        {
+            println!("Could not regenerate wrapped: {}", error);
+            let blocks = vec![
+                Block::section(Text::mrkdwn("Could not regenerate your wrapped.")),
+                wrapped_actions(&job.id, 0, &share_channel),
+            ];
+            update(
+                &slack_client,
+                channel,
+                ts,
+                "Could not regenerate your wrapped".to_string(),
+                blocks,
+            )
+            .await;
+        }
+        return;
+    }
+    let document = match queue.store.result(&job.id) {
+        Ok(Some(document)) => document,
+        Ok(None) => return reply(&slack_client, &actions, GONE).await,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return reply(
+                &slack_client,
+                &actions,
+                "Something went wrong, please try again.",
+            )
+            .await;
+        }
+    };
+    match action.action_id.as_str() {
+        NEXT_STAT => {
+            let (text, blocks) = stat_message(&job.id, &document, page, &share_channel);
+            update(&slack_client, channel, ts, text, blocks).await;
+        }
+        SHARE => {
+            let mut blocks = stat_blocks(&document, page);
+            blocks.push(Block::context(vec![ContextElement::Text(Text::mrkdwn(
+                format!("Shared by <@{}> from their Slackify Wrapped", job.user_id),
+            ))]));
+            let params = ChatPostMessageParams {
+                channel: share_channel.clone(),
+                text: format!("<@{}> shared their {} in Slack", job.user_id, document.year),
+                blocks: Some(blocks),
+                unfurl_links: Some(false),
+                ..Default::default()
+            };
+            let text = match slack_client.chat().post_message(params).await {
+                Ok(ChatPostMessageResponse::Success(_)) => format!("Shared to #{}.", share_channel),
+                Ok(ChatPostMessageResponse::Error(error)) => {
+                    println!("Error: {:?}", error.error);
+                    format!("Could not share to #{}, is the app in it?", share_channel)
+                }
+                Err(error) => {
+                    println!("Encountered error: {}", error);
+                    format!("Could not share to #{}, please try again.", share_channel)
+                }
+            };
+            reply(&slack_client, &actions, &text).await;
+        }
+        _ => {}
+    }
+}
+
+// Slack only needs a 200 within three seconds, the message is updated after.
+// Also handles interactions over Socket Mode.
+pub fn interaction(
+    payload: InteractionPayload,
+    store: &TokenStore,
+    clients: &SlackClients,
+    registry: &ConsentRegistry,
+    queue: &JobQueue,
+    config: &AppConfig,
+) -> Status {
+    let InteractionPayload::BlockActions(actions) = payload else {
+        return Status::Ok;
+    };
+    let Some(action) = actions
+        .actions
+        .iter()
+        .find(|action| [NEXT_STAT, SHARE, REGENERATE].contains(&action.action_id.as_str()))
+        .cloned()
+    else {
+        return Status::Ok;
+    };
+    let token = match resolve_token(
+        Some(store),
+        Some(&actions.team.id),
+        config.slack.token.as_deref(),
+    ) {
+        Ok(token) => token,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return Status::Ok;
+        }
+    };
+    let opted_out = match registry.opted_out(&actions.team.id) {
+        Ok(opted_out) => opted_out,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return Status::InternalServerError;
+        }
+    };
+    rocket::tokio::spawn(act(
+        clients.get(&token),
+        queue.clone(),
+        actions,
+        action,
+        opted_out,
+        config.slack.share_channel.clone(),
+    ));
+    Status::Ok
+}
+
+#[post("/slack/interactions", data = "<form>")]
+pub fn interactions_route(
+    form: SlackForm<InteractionForm>,
+    store: &State<TokenStore>,
+    clients: &State<SlackClients>,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+    config: &State<AppConfig>,
+) -> Status {
+    let SlackForm(form) = form;
+    match serde_json::from_str(&form.payload) {
+        Ok(payload) => interaction(payload, store, clients, registry, queue, config),
+        Err(error) => {
+            println!("Ignoring interaction: {}", error);
+            Status::BadRequest
+        }
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![interactions_route]
+}
@@ -0,0 +1,202 @@
+use crate::auth::Session;
+use crate::consent::ConsentRegistry;
+use crate::events::EventStore;
+use crate::features::history::{StoredEvents, YearHistory};
+use crate::slack::client::SlackClient;
+use crate::slack::users::{UsersInfoParams, UsersInfoResponse};
+use chrono::{Datelike, NaiveDate, Utc, Weekday};
+use rocket;
+use rocket::http::Status;
+use rocket::{get, Route, State};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreakMode {
+    Calendar,
+    // Weekends neither extend nor break a streak
+    Workdays,
+}
+
+impl StreakMode {
+    fn counts(self, date: NaiveDate) -> bool {
+        match self {
+            StreakMode::Calendar => true,
+            StreakMode::Workdays => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+        }
+    }
+
+    // The day a streak ending on `date` has to continue on
+    fn next_day(self, date: NaiveDate) -> NaiveDate {
+        let mut next = date.succ_opt().unwrap_or(date);
+        while !self.counts(next) {
+            next = next.succ_opt().unwrap_or(next);
+        }
+        next
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Streak {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BusiestDay {
+    pub date: NaiveDate,
+    pub message_count: u32,
+    pub top_channel: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActivityStreaks {
+    pub longest: Option<Streak>,
+    pub current: Option<Streak>,
+    pub busiest_day: Option<BusiestDay>,
+    pub active_days: u32,
+}
+
+impl ActivityStreaks {
+    // Dates are taken in the user's timezone (`tz_offset` seconds east of
+    // UTC). The current streak is measured as of `today`, or the end of the
+    // year for past years.
+    pub fn compute(
+        history: &YearHistory,
+        user_id: &str,
+        tz_offset: i32,
+        mode: StreakMode,
+        today: NaiveDate,
+    ) -> Self {
+        let mut days: BTreeMap<NaiveDate, HashMap<&str, u32>> = BTreeMap::new();
+        for (date, message) in history.user_messages(user_id, tz_offset) {
+            *days
+                .entry(date)
+                .or_default()
+                .entry(message.channel.as_str())
+                .or_default() += 1;
+        }
+
+        let mut runs: Vec<Streak> = Vec::new();
+        for &date in days.keys().filter(|date| mode.counts(**date)) {
+            match runs.last_mut() {
+                Some(run) if mode.next_day(run.end) == date => {
+                    run.end = date;
+                    run.days += 1;
+                }
+                _ => runs.push(Streak {
+                    start: date,
+                    end: date,
+                    days: 1,
+                }),
+            }
+        }
+
+        let year_end = NaiveDate::from_ymd_opt(history.year, 12, 31).unwrap_or(today);
+        let as_of = today.min(year_end);
+        let current = runs
+            .last()
+            .filter(|run| run.end <= as_of && mode.next_day(run.end) >= as_of)
+            .cloned();
+        // Earliest run wins a tie
+        let longest = runs.iter().rev().max_by_key(|run| run.days).cloned();
+
+        let busiest_day = days
+            .iter()
+            .map(|(date, channels)| (date, channels.values().sum::<u32>(), channels))
+            .rev()
+            .max_by_key(|(_, count, _)| *count)
+            .map(|(date, message_count, channels)| BusiestDay {
+                date: *date,
+                message_count,
+                top_channel: channels
+                    .iter()
+                    .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+                    .map(|(channel, _)| channel.to_string())
+                    .unwrap_or_default(),
+            });
+
+        Self {
+            longest,
+            current,
+            busiest_day,
+            active_days: days.len() as u32,
+        }
+    }
+}
+
+#[get("/streaks/<user_id>?<year>&<workdays_only>")]
+pub async fn streaks_route(
+    user_id: &str,
+    year: Option<i32>,
+    workdays_only: Option<bool>,
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<String, Status> {
+    session.authorize(user_id)?;
+    registry.authorize(&session, user_id)?;
+    let today = Utc::now().date_naive();
+    let year = year.unwrap_or(today.year());
+    let mode = match workdays_only.unwrap_or(false) {
+        true => StreakMode::Workdays,
+        false => StreakMode::Calendar,
+    };
+
+    let params = UsersInfoParams {
+        user: user_id.to_string(),
+        ..Default::default()
+    };
+    let tz_offset = match slack_client.users().info(params).await {
+        Ok(UsersInfoResponse::Success(info)) => info.user.tz_offset,
+        Ok(UsersInfoResponse::Error(error)) => {
+            println!("Error: {:?}", error.error);
+            return Ok("Streaks - encountered error".to_string());
+        }
+        Err(error) => {
+            println!("Encountered error: {:?}", error);
+            return Ok("Could not get user info".to_string());
+        }
+    };
+
+    let stored = Some(StoredEvents {
+        store: events,
+        team_id: &session.team_id.0,
+    });
+    let history = match YearHistory::load(&slack_client, stored, year).await {
+        Ok(history) => history,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return Ok("Could not get message history".to_string());
+        }
+    };
+
+    let streaks = ActivityStreaks::compute(&history, user_id, tz_offset, mode, today);
+    let mut summary = format!("Active days: {}\n", streaks.active_days);
+    if let Some(longest) = streaks.longest {
+        summary += &format!(
+            "Longest streak: {} days ({} to {})\n",
+            longest.days, longest.start, longest.end
+        );
+    }
+    summary += &format!(
+        "Current streak: {} days\n",
+        streaks.current.map_or(0, |current| current.days)
+    );
+    if let Some(busiest) = streaks.busiest_day {
+        let channel = history
+            .channel(&busiest.top_channel)
+            .and_then(|channel| channel.name.clone())
+            .unwrap_or(busiest.top_channel);
+        summary += &format!(
+            "Busiest day: {} with {} messages, mostly in #{}\n",
+            busiest.date, busiest.message_count, channel
+        );
+    }
+    Ok(summary)
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![streaks_route]
+}
@@ -0,0 +1,346 @@
+use crate::api::v1::{JobDocument, WrappedDocument};
+use crate::auth::{tokens_match, Session};
+use crate::cards::standard_emoji;
+use crate::cards::{CardCache, EmojiImage, SummaryCard};
+use crate::config::AppConfig;
+use crate::consent::ConsentRegistry;
+use crate::emoji::snapshots::take_snapshot;
+use crate::emoji::EmojiStore;
+use crate::jobs::queue::JobQueue;
+use crate::jobs::Job;
+use crate::slack::client::SlackClient;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rocket;
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Json;
+use rocket::{get, Responder, Route, State};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const MAX_ALIAS_DEPTH: usize = 4;
+
+pub fn card_cache(config: &AppConfig) -> CardCache {
+    CardCache::new(&config.card_cache_dir).with_ttl(Duration::from_secs(config.cache.card_ttl_secs))
+}
+
+// Lets the card be fetched without signing in, by whoever was given its URL
+pub fn card_signature(secret: &str, team_id: &str, user_id: &str, year: i32) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}/{}/{}", team_id, user_id, year).as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// The PNG card's signed URL under `base_url`, for Slack to fetch
+pub fn card_url(config: &AppConfig, team_id: &str, user_id: &str, year: i32) -> Option<String> {
+    let base_url = config.base_url.as_deref()?;
+    let secret = config.card_secret.as_deref()?;
+    Some(format!(
+        "{}/wrapped/{}/{}/card.png?team={}&sig={}",
+        base_url.trim_end_matches('/'),
+        user_id,
+        year,
+        team_id,
+        card_signature(secret, team_id, user_id, year)
+    ))
+}
+
+// Who a card is being shown to
+struct CardAccess {
+    team_id: String,
+    // Empty for signed URLs, nobody in particular asked
+    requested_by: String,
+}
+
+// The signed in user's own card, or an admin's, unless the user opted out;
+// otherwise a signed URL is needed
+fn authorize_card(
+    config: &AppConfig,
+    registry: &ConsentRegistry,
+    session: Option<&Session>,
+    signed: Option<(&str, &str)>,
+    user_id: &str,
+    year: i32,
+) -> Result<CardAccess, Status> {
+    if let (Some((team_id, signature)), Some(secret)) = (signed, config.card_secret.as_deref()) {
+        let expected = card_signature(secret, team_id, user_id, year);
+        if tokens_match(&expected, signature) {
+            return Ok(CardAccess {
+                team_id: team_id.to_string(),
+                requested_by: String::new(),
+            });
+        }
+    }
+    match session {
+        Some(session) => {
+            session.authorize(user_id)?;
+            registry.authorize(session, user_id)?;
+            Ok(CardAccess {
+                team_id: session.team_id.0.clone(),
+                requested_by: session.user_id.0.clone(),
+            })
+        }
+        None if signed.is_some() => Err(Status::Forbidden),
+        None => Err(Status::Unauthorized),
+    }
+}
+
+// Drawn without the emoji image, which fill_emoji_image adds once the card
+// turns out not to be cached
+pub fn summary_card(
+    custom_emoji: &HashMap<String, String>,
+    wrapped: &WrappedDocument,
+) -> SummaryCard {
+    let favourite_emoji = wrapped
+        .reactions
+        .first()
+        .map(|reaction| reaction.name.clone());
+    SummaryCard {
+        user_id: wrapped.user.id.clone(),
+        name: wrapped.user.display_name.clone(),
+        year: wrapped.year,
+        emoji_url: favourite_emoji
+            .as_deref()
+            .and_then(|name| emoji_url(custom_emoji, name)),
+        favourite_emoji,
+        emoji_image: None,
+        top_channel: wrapped
+            .top_channels
+            .first()
+            .map(|channel| channel.name.clone()),
+        message_count: wrapped.message_count,
+        longest_streak: wrapped
+            .streaks
+            .longest
+            .as_ref()
+            .map_or(0, |streak| streak.days),
+    }
+}
+
+// The workspace's custom emoji from the last snapshot. Until the first one
+// is taken, one is taken here.
+async fn custom_emoji(
+    slack_client: &SlackClient,
+    emoji: &EmojiStore,
+    team_id: &str,
+) -> HashMap<String, String> {
+    match emoji.latest(team_id) {
+        Ok(Some(snapshot)) => return snapshot.emoji,
+        Ok(None) => {}
+        Err(error) => println!("Encountered error: {}", error),
+    }
+    if let Err(error) = take_snapshot(slack_client, emoji, team_id, Utc::now()).await {
+        println!("Encountered error: {}", error);
+    }
+    emoji
+        .latest(team_id)
+        .ok()
+        .flatten()
+        .map(|snapshot| snapshot.emoji)
+        .unwrap_or_default()
+}
+
+// Custom emoji follow their aliases; anything else is looked up in the
+// standard catalogue. Skin tone suffixes are dropped.
+pub fn emoji_url(custom_emoji: &HashMap<String, String>, name: &str) -> Option<String> {
+    let mut name = name.split("::").next().unwrap_or(name).to_string();
+    for _ in 0..MAX_ALIAS_DEPTH {
+        let Some(value) = custom_emoji.get(&name) else {
+            break;
+        };
+        match value.strip_prefix("alias:") {
+            Some(target) => name = target.to_string(),
+            None => return Some(value.clone()),
+        }
+    }
+    standard_emoji::image_url(&name)
+}
+
+async fn fill_emoji_image(slack_client: &SlackClient, card: &mut SummaryCard) {
+    let Some(url) = &card.emoji_url else {
+        return;
+    };
+    card.emoji_image = fetch_emoji_image(slack_client, url).await;
+}
+
+async fn fetch_emoji_image(slack_client: &SlackClient, url: &str) -> Option<EmojiImage> {
+    let response = slack_client.client.get(url).send().await.ok()?;
+    let response = response.error_for_status().ok()?;
+    let mime_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| value.starts_with("image/"))
+        .unwrap_or("image/png")
+        .to_string();
+    let bytes = response.bytes().await.ok()?.to_vec();
+    Some(EmojiImage { mime_type, bytes })
+}
+
+// A card answers with the image, or with the job computing the wrapped
+#[derive(Responder)]
+pub enum CardResponse {
+    Card((ContentType, Vec<u8>)),
+    #[response(status = 202)]
+    Pending(Json<JobDocument>),
+}
+
+enum BuiltCard {
+    Ready(SummaryCard),
+    // The wrapped is queued or being computed
+    Pending(Job),
+}
+
+// Cards are drawn from the wrapped last computed for the user, as long as it
+// is fresh. Otherwise a job is started, and returned to come back once it
+// finished.
+#[allow(clippy::too_many_arguments)]
+async fn build_card(
+    slack_client: &SlackClient,
+    config: &AppConfig,
+    registry: &ConsentRegistry,
+    queue: &JobQueue,
+    emoji: &EmojiStore,
+    access: &CardAccess,
+    user_id: &str,
+    year: i32,
+) -> Result<BuiltCard, Status> {
+    let log = |error: &dyn std::fmt::Display| {
+        println!("Encountered error: {}", error);
+        Status::InternalServerError
+    };
+    if registry
+        .opted_out_anywhere(user_id)
+        .map_err(|error| log(&error))?
+    {
+        return Err(Status::Forbidden);
+    }
+    let (job, created) = queue
+        .store
+        .reusable_or_create(
+            &access.team_id,
+            user_id,
+            year,
+            &access.requested_by,
+            config.cache.fresh_since(Utc::now()),
+            Utc::now(),
+        )
+        .map_err(|error| log(&error))?;
+    if created {
+        let opted_out = registry
+            .opted_out(&access.team_id)
+            .map_err(|error| log(&error))?;
+        queue.submit(&job, slack_client.clone(), opted_out);
+        return Ok(BuiltCard::Pending(job));
+    }
+    match queue.store.result(&job.id).map_err(|error| log(&error))? {
+        Some(wrapped) => {
+            let custom_emoji = custom_emoji(slack_client, emoji, &access.team_id).await;
+            Ok(BuiltCard::Ready(summary_card(&custom_emoji, &wrapped)))
+        }
+        None => Ok(BuiltCard::Pending(job)),
+    }
+}
+
+// Cards need signing in, or a URL signed for the user's team and year as
+// in the wrapped DMs, where Slack fetches them without a session. Until the
+// wrapped is computed they answer 202 with the job.
+#[get("/wrapped/<user_id>/<year>/card.svg?<team>&<sig>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn card_svg(
+    user_id: &str,
+    year: i32,
+    team: Option<&str>,
+    sig: Option<&str>,
+    session: Option<Session>,
+    slack_client: SlackClient,
+    config: &State<AppConfig>,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+    emoji: &State<EmojiStore>,
+) -> Result<CardResponse, Status> {
+    let signed = team.zip(sig);
+    let access = authorize_card(config, registry, session.as_ref(), signed, user_id, year)?;
+    let mut card = match build_card(
+        &slack_client,
+        config,
+        registry,
+        queue,
+        emoji,
+        &access,
+        user_id,
+        year,
+    )
+    .await?
+    {
+        BuiltCard::Ready(card) => card,
+        BuiltCard::Pending(job) => return Ok(CardResponse::Pending(Json(JobDocument::from(&job)))),
+    };
+    let cache = card_cache(config);
+    if let Some(svg) = cache.cached(&card, "svg") {
+        return Ok(CardResponse::Card((ContentType::SVG, svg)));
+    }
+    fill_emoji_image(&slack_client, &mut card).await;
+    match cache.svg(&card) {
+        Ok(svg) => Ok(CardResponse::Card((ContentType::SVG, svg.into_bytes()))),
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[get("/wrapped/<user_id>/<year>/card.png?<team>&<sig>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn card_png(
+    user_id: &str,
+    year: i32,
+    team: Option<&str>,
+    sig: Option<&str>,
+    session: Option<Session>,
+    slack_client: SlackClient,
+    config: &State<AppConfig>,
+    registry: &State<ConsentRegistry>,
+    queue: &State<JobQueue>,
+    emoji: &State<EmojiStore>,
+) -> Result<CardResponse, Status> {
+    let signed = team.zip(sig);
+    let access = authorize_card(config, registry, session.as_ref(), signed, user_id, year)?;
+    let mut card = match build_card(
+        &slack_client,
+        config,
+        registry,
+        queue,
+        emoji,
+        &access,
+        user_id,
+        year,
+    )
+    .await?
+    {
+        BuiltCard::Ready(card) => card,
+        BuiltCard::Pending(job) => return Ok(CardResponse::Pending(Json(JobDocument::from(&job)))),
+    };
+    let cache = card_cache(config);
+    if let Some(png) = cache.cached(&card, "png") {
+        return Ok(CardResponse::Card((ContentType::PNG, png)));
+    }
+    fill_emoji_image(&slack_client, &mut card).await;
+    match cache.png(&card) {
+        Ok(png) => Ok(CardResponse::Card((ContentType::PNG, png))),
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![card_svg, card_png]
+}
@@ -0,0 +1,194 @@
+use crate::auth::Session;
+use crate::consent::ConsentRegistry;
+use crate::events::EventStore;
+use crate::features::history::{StoredEvents, YearHistory};
+use crate::slack::chat::{ChatGetPermalinkParams, ChatGetPermalinkResponse};
+use crate::slack::client::SlackClient;
+use crate::slack::users::{UsersInfoParams, UsersInfoResponse};
+use crate::slack::util::parse_slack_ts;
+use chrono::{Datelike, Utc};
+use rocket;
+use rocket::http::Status;
+use rocket::{get, Route, State};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreadRef {
+    pub channel: String,
+    pub thread_ts: String,
+    pub reply_count: u32,
+    // Not in the history, see resolve_permalink
+    pub permalink: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThreadStats {
+    pub threads_started: u32,
+    pub threads_replied: u32,
+    pub longest_thread: Option<ThreadRef>,
+    // Mean time between a reply of the user and the message before it
+    pub average_reply_latency_secs: Option<f64>,
+    pub thread_messages: u32,
+    pub top_level_messages: u32,
+}
+
+impl ThreadStats {
+    pub fn compute(history: &YearHistory, user_id: &str, tz_offset: i32) -> Self {
+        // Every thread in the history, keyed by (channel, thread_ts), with
+        // its messages in posting order
+        let mut threads: BTreeMap<(&str, &str), Vec<(i64, &str)>> = BTreeMap::new();
+        for message in &history.messages {
+            let Some(thread_ts) = message.message.thread_ts.as_deref() else {
+                continue;
+            };
+            let Some(ts) = parse_slack_ts(&message.message.ts) else {
+                continue;
+            };
+            threads
+                .entry((message.channel.as_str(), thread_ts))
+                .or_default()
+                .push((ts, message.message.ts.as_str()));
+        }
+        for messages in threads.values_mut() {
+            messages.sort_by(|a, b| a.1.cmp(b.1));
+        }
+
+        let mut stats = ThreadStats::default();
+        let mut started = HashSet::new();
+        let mut replied = HashSet::new();
+        let mut latencies = Vec::new();
+        for (_, message) in history.user_messages(user_id, tz_offset) {
+            let key = match message.message.thread_ts.as_deref() {
+                Some(thread_ts) => (message.channel.as_str(), thread_ts),
+                None => {
+                    stats.top_level_messages += 1;
+                    continue;
+                }
+            };
+            if key.1 == message.message.ts {
+                // A thread parent is still a top-level message
+                stats.top_level_messages += 1;
+                started.insert(key);
+                continue;
+            }
+
+            stats.thread_messages += 1;
+            replied.insert(key);
+            let thread = &threads[&key];
+            let position = thread.iter().position(|(_, ts)| *ts == message.message.ts);
+            if let Some(position) = position.filter(|position| *position > 0) {
+                latencies.push(thread[position].0 - thread[position - 1].0);
+            }
+        }
+
+        stats.threads_started = started.len() as u32;
+        stats.threads_replied = replied.difference(&started).count() as u32;
+        stats.average_reply_latency_secs = match latencies.is_empty() {
+            true => None,
+            false => Some(latencies.iter().sum::<i64>() as f64 / latencies.len() as f64),
+        };
+        stats.longest_thread = started
+            .union(&replied)
+            .map(|key| ThreadRef {
+                channel: key.0.to_string(),
+                thread_ts: key.1.to_string(),
+                reply_count: threads[key].iter().filter(|(_, ts)| *ts != key.1).count() as u32,
+                permalink: None,
+            })
+            .max_by(|a, b| {
+                a.reply_count
+                    .cmp(&b.reply_count)
+                    .then(b.thread_ts.cmp(&a.thread_ts))
+            });
+        stats
+    }
+
+    // Left without one when Slack cannot link to the thread
+    pub async fn resolve_permalink(&mut self, slack_client: &SlackClient) {
+        let Some(longest) = &mut self.longest_thread else {
+            return;
+        };
+        let params = ChatGetPermalinkParams {
+            channel: longest.channel.clone(),
+            message_ts: longest.thread_ts.clone(),
+        };
+        if let Ok(ChatGetPermalinkResponse::Success(success)) =
+            slack_client.chat().get_permalink(params).await
+        {
+            longest.permalink = Some(success.permalink);
+        }
+    }
+
+    pub fn in_thread_ratio(&self) -> f64 {
+        match self.thread_messages + self.top_level_messages {
+            0 => 0.0,
+            total => self.thread_messages as f64 / total as f64,
+        }
+    }
+}
+
+#[get("/thread-stats/<user_id>?<year>")]
+pub async fn thread_stats_route(
+    user_id: &str,
+    year: Option<i32>,
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<String, Status> {
+    session.authorize(user_id)?;
+    registry.authorize(&session, user_id)?;
+    let year = year.unwrap_or(Utc::now().year());
+
+    let params = UsersInfoParams {
+        user: user_id.to_string(),
+        ..Default::default()
+    };
+    let tz_offset = match slack_client.users().info(params).await {
+        Ok(UsersInfoResponse::Success(info)) => info.user.tz_offset,
+        Ok(UsersInfoResponse::Error(error)) => {
+            println!("Error: {:?}", error.error);
+            return Ok("Thread Stats - encountered error".to_string());
+        }
+        Err(error) => {
+            println!("Encountered error: {:?}", error);
+            return Ok("Could not get user info".to_string());
+        }
+    };
+
+    let stored = Some(StoredEvents {
+        store: events,
+        team_id: &session.team_id.0,
+    });
+    let history = match YearHistory::load(&slack_client, stored, year).await {
+        Ok(history) => history,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return Ok("Could not get message history".to_string());
+        }
+    };
+
+    let mut stats = ThreadStats::compute(&history, user_id, tz_offset);
+    stats.resolve_permalink(&slack_client).await;
+    let mut summary = format!(
+        "Threads started: {}\nThreads replied to: {}\nIn-thread messages: {:.0}%\n",
+        stats.threads_started,
+        stats.threads_replied,
+        stats.in_thread_ratio() * 100.0
+    );
+    if let Some(latency) = stats.average_reply_latency_secs {
+        summary += &format!("Average reply time: {:.0} minutes\n", latency / 60.0);
+    }
+    if let Some(longest) = stats.longest_thread {
+        summary += &format!(
+            "Longest thread: {} replies ({})\n",
+            longest.reply_count,
+            longest.permalink.as_deref().unwrap_or("no permalink")
+        );
+    }
+    Ok(summary)
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![thread_stats_route]
+}
@@ -0,0 +1,225 @@
+use crate::auth::Session;
+use crate::consent::ConsentRegistry;
+use crate::events::EventStore;
+use crate::features::history::{StoredEvents, YearHistory};
+use crate::features::wrapped::ANONYMOUS;
+use crate::slack::client::SlackClient;
+use crate::slack::users::{UsersInfoParams, UsersInfoResponse};
+use crate::slack::util::mentioned_users;
+use chrono::{Datelike, Utc};
+use rocket;
+use rocket::http::Status;
+use rocket::{get, Route, State};
+use std::collections::{HashMap, HashSet};
+
+const MENTION_WEIGHT: u32 = 3;
+const THREAD_REPLY_WEIGHT: u32 = 2;
+const REACTION_WEIGHT: u32 = 1;
+const DIRECT_MESSAGE_WEIGHT: u32 = 1;
+
+const TOP_COLLABORATORS: usize = 10;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Interactions {
+    // Mentions in either direction
+    pub mentions: u32,
+    // Their messages in threads the user also posted in
+    pub thread_replies: u32,
+    // Reactions in either direction
+    pub reactions: u32,
+    // Messages in DMs and group DMs with them
+    pub direct_messages: u32,
+}
+
+impl Interactions {
+    pub fn score(&self) -> u32 {
+        self.mentions * MENTION_WEIGHT
+            + self.thread_replies * THREAD_REPLY_WEIGHT
+            + self.reactions * REACTION_WEIGHT
+            + self.direct_messages * DIRECT_MESSAGE_WEIGHT
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collaborator {
+    pub user_id: String,
+    pub interactions: Interactions,
+}
+
+// The people the user interacted with most during the year, best first
+pub fn top_collaborators(
+    history: &YearHistory,
+    user_id: &str,
+    tz_offset: i32,
+    limit: usize,
+) -> Vec<Collaborator> {
+    let mut interactions: HashMap<&str, Interactions> = HashMap::new();
+    let messages: Vec<_> = history
+        .messages_in_year(tz_offset)
+        .map(|(_, message)| message)
+        .collect();
+
+    let user_threads: HashSet<(&str, &str)> = messages
+        .iter()
+        .filter(|message| message.message.user == user_id)
+        .filter_map(|message| {
+            let thread_ts = message.message.thread_ts.as_deref()?;
+            Some((message.channel.as_str(), thread_ts))
+        })
+        .collect();
+
+    // Everyone who posted in each DM or group DM the user took part in
+    let mut dm_members: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for message in &messages {
+        let is_dm = history
+            .channel(&message.channel)
+            .is_some_and(|channel| channel.is_im || channel.is_mpim);
+        if is_dm && !message.message.user.is_empty() {
+            dm_members
+                .entry(message.channel.as_str())
+                .or_default()
+                .insert(message.message.user.as_str());
+        }
+    }
+    dm_members.retain(|_, members| members.contains(user_id));
+
+    for message in &messages {
+        let author = message.message.user.as_str();
+        let own_message = author == user_id;
+
+        for mentioned in mentioned_users(&message.message.text) {
+            if own_message {
+                interactions.entry(mentioned).or_default().mentions += 1;
+            } else if mentioned == user_id {
+                interactions.entry(author).or_default().mentions += 1;
+            }
+        }
+
+        for reaction in &message.message.reactions {
+            if own_message {
+                for reactor in &reaction.users {
+                    interactions.entry(reactor).or_default().reactions += 1;
+                }
+            } else if reaction.users.iter().any(|reactor| reactor == user_id) {
+                interactions.entry(author).or_default().reactions += 1;
+            }
+        }
+
+        if !own_message {
+            let in_user_thread = message
+                .message
+                .thread_ts
+                .as_deref()
+                .is_some_and(|thread_ts| {
+                    user_threads.contains(&(message.channel.as_str(), thread_ts))
+                });
+            if in_user_thread {
+                interactions.entry(author).or_default().thread_replies += 1;
+            }
+        }
+
+        if let Some(members) = dm_members.get(message.channel.as_str()) {
+            for member in members {
+                if own_message || *member == author {
+                    interactions.entry(member).or_default().direct_messages += 1;
+                }
+            }
+        }
+    }
+
+    let mut collaborators: Vec<Collaborator> = interactions
+        .into_iter()
+        .filter(|(other, _)| *other != user_id && !other.is_empty() && *other != "USLACKBOT")
+        .map(|(other, interactions)| Collaborator {
+            user_id: other.to_string(),
+            interactions,
+        })
+        .collect();
+    collaborators.sort_by(|a, b| {
+        b.interactions
+            .score()
+            .cmp(&a.interactions.score())
+            .then(a.user_id.cmp(&b.user_id))
+    });
+    collaborators.truncate(limit);
+    collaborators
+}
+
+#[get("/top-collaborators/<user_id>?<year>")]
+pub async fn top_collaborators_route(
+    user_id: &str,
+    year: Option<i32>,
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<String, Status> {
+    session.authorize(user_id)?;
+    registry.authorize(&session, user_id)?;
+    let opted_out = registry.anonymised(&session)?;
+    let year = year.unwrap_or(Utc::now().year());
+
+    let params = UsersInfoParams {
+        user: user_id.to_string(),
+        ..Default::default()
+    };
+    let tz_offset = match slack_client.users().info(params).await {
+        Ok(UsersInfoResponse::Success(info)) => info.user.tz_offset,
+        Ok(UsersInfoResponse::Error(error)) => {
+            println!("Error: {:?}", error.error);
+            return Ok("Top Collaborators - encountered error".to_string());
+        }
+        Err(error) => {
+            println!("Encountered error: {:?}", error);
+            return Ok("Could not get user info".to_string());
+        }
+    };
+
+    let stored = Some(StoredEvents {
+        store: events,
+        team_id: &session.team_id.0,
+    });
+    let history = match YearHistory::load(&slack_client, stored, year).await {
+        Ok(history) => history,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return Ok("Could not get message history".to_string());
+        }
+    };
+
+    let mut summary = String::new();
+    let collaborators = top_collaborators(&history, user_id, tz_offset, TOP_COLLABORATORS);
+    for (rank, collaborator) in collaborators.iter().enumerate() {
+        // People who opted out are not looked up at all
+        let name = if opted_out.contains(&collaborator.user_id) {
+            ANONYMOUS.to_string()
+        } else {
+            let params = UsersInfoParams {
+                user: collaborator.user_id.clone(),
+                ..Default::default()
+            };
+            match slack_client.users().info(params).await {
+                Ok(UsersInfoResponse::Success(info)) => {
+                    info.user.real_name.unwrap_or(info.user.name)
+                }
+                _ => collaborator.user_id.clone(),
+            }
+        };
+        let interactions = &collaborator.interactions;
+        summary += &format!(
+            "{}. {} - score {}: {} mentions, {} thread replies, {} reactions, {} direct messages\n",
+            rank + 1,
+            name,
+            interactions.score(),
+            interactions.mentions,
+            interactions.thread_replies,
+            interactions.reactions,
+            interactions.direct_messages
+        );
+    }
+    Ok(summary)
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![top_collaborators_route]
+}
@@ -0,0 +1,117 @@
+use crate::auth::Session;
+use crate::consent::ConsentRegistry;
+use crate::events::EventStore;
+use crate::features::history::{StoredEvents, YearHistory};
+use crate::slack::client::SlackClient;
+use crate::slack::users::{UsersInfoParams, UsersInfoResponse};
+use crate::text::stopwords::{is_stopword, Language};
+use crate::text::tfidf::{Corpus, WeightedTerm};
+use crate::text::tokenize::tokenize;
+use chrono::{Datelike, Utc};
+use rocket;
+use rocket::http::Status;
+use rocket::{get, Route, State};
+
+const WORD_CLOUD_SIZE: usize = 50;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordCloud {
+    pub words: Vec<WeightedTerm>,
+    pub bigrams: Vec<WeightedTerm>,
+}
+
+// The user's most distinctive words and bigrams compared to everyone else
+// posting in the workspace that year
+pub fn word_cloud(history: &YearHistory, user_id: &str, tz_offset: i32, limit: usize) -> WordCloud {
+    let mut words = Corpus::default();
+    let mut bigrams = Corpus::default();
+    for (_, message) in history.messages_in_year(tz_offset) {
+        let author = message.message.user.as_str();
+        if author.is_empty() {
+            continue;
+        }
+        let tokens = tokenize(&message.message.text);
+        let language = Language::detect(&tokens);
+        let kept: Vec<Option<&String>> = tokens
+            .iter()
+            .map(|token| (!is_stopword(language, token)).then_some(token))
+            .collect();
+
+        for token in kept.iter().flatten() {
+            words.add(author, token.to_string());
+        }
+        for pair in kept.windows(2) {
+            if let [Some(first), Some(second)] = pair {
+                bigrams.add(author, format!("{} {}", first, second));
+            }
+        }
+    }
+
+    WordCloud {
+        words: words.distinctive_terms(user_id, limit),
+        bigrams: bigrams.distinctive_terms(user_id, limit),
+    }
+}
+
+#[get("/word-cloud/<user_id>?<year>")]
+pub async fn word_cloud_route(
+    user_id: &str,
+    year: Option<i32>,
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<String, Status> {
+    session.authorize(user_id)?;
+    registry.authorize(&session, user_id)?;
+    let year = year.unwrap_or(Utc::now().year());
+
+    let params = UsersInfoParams {
+        user: user_id.to_string(),
+        ..Default::default()
+    };
+    let tz_offset = match slack_client.users().info(params).await {
+        Ok(UsersInfoResponse::Success(info)) => info.user.tz_offset,
+        Ok(UsersInfoResponse::Error(error)) => {
+            println!("Error: {:?}", error.error);
+            return Ok("Word Cloud - encountered error".to_string());
+        }
+        Err(error) => {
+            println!("Encountered error: {:?}", error);
+            return Ok("Could not get user info".to_string());
+        }
+    };
+
+    let stored = Some(StoredEvents {
+        store: events,
+        team_id: &session.team_id.0,
+    });
+    let history = match YearHistory::load(&slack_client, stored, year).await {
+        Ok(history) => history,
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            return Ok("Could not get message history".to_string());
+        }
+    };
+
+    let cloud = word_cloud(&history, user_id, tz_offset, WORD_CLOUD_SIZE);
+    let mut summary = String::from("Words:\n");
+    for word in cloud.words {
+        summary += &format!(
+            "{} ({}x, weight {:.2})\n",
+            word.term, word.count, word.weight
+        );
+    }
+    summary += "Phrases:\n";
+    for bigram in cloud.bigrams {
+        summary += &format!(
+            "{} ({}x, weight {:.2})\n",
+            bigram.term, bigram.count, bigram.weight
+        );
+    }
+    Ok(summary)
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![word_cloud_route]
+}
@@ -0,0 +1,239 @@
+use crate::config::PrivacyConfig;
+use crate::emoji::EmojiTimeline;
+use crate::features::history::{ChannelMessage, HistoryError, StoredEvents, YearHistory};
+use crate::features::wrapped::{display_name, fetch_user, ChannelCount, ANONYMOUS};
+use crate::mrkdwn::render::Directory;
+use crate::slack::client::SlackClient;
+use crate::slack::conversations::Channel;
+use chrono::{DateTime, Datelike};
+use std::collections::{HashMap, HashSet};
+
+const TOP_CHANNELS: usize = 10;
+const TOP_REACTORS: usize = 10;
+const TOP_EMOJI: usize = 10;
+const MOST_REACTED_MESSAGES: usize = 5;
+
+// The whole workspace's year, in UTC. Channels, emoji and people only make
+// the lists when enough people or reactions stand behind them, so a list
+// never points at one person's activity by accident. Direct messages and
+// private channels count towards the totals only, as do people who opted
+// out.
+pub struct WorkspaceWrapped {
+    pub year: i32,
+    pub message_count: u32,
+    pub reaction_count: u32,
+    pub new_channels: u32,
+    // Only known from the emoji history, see count_new_emoji
+    pub new_custom_emoji: Option<u32>,
+    pub active_users: u32,
+    pub top_channels: Vec<ChannelCount>,
+    // People by reactions added, most first
+    pub top_reactors: Vec<(String, u32)>,
+    // Emoji by reactions, most used first
+    pub top_emoji: Vec<(String, u32)>,
+    pub most_reacted_messages: Vec<ChannelMessage>,
+    pub profiles: HashMap<String, String>,
+    pub directory: Directory,
+}
+
+fn is_public(channel: Option<&Channel>) -> bool {
+    channel.is_some_and(|channel| channel.is_channel && !channel.is_private)
+}
+
+fn reaction_total(message: &ChannelMessage) -> u32 {
+    message
+        .message
+        .reactions
+        .iter()
+        .map(|reaction| reaction.count.max(0) as u32)
+        .sum()
+}
+
+// Most first, then by name so ties are stable
+fn ranked(counts: HashMap<&str, u32>, size: usize) -> Vec<(String, u32)> {
+    let mut counts: Vec<(String, u32)> = counts
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(size);
+    counts
+}
+
+impl WorkspaceWrapped {
+    pub fn compute(
+        history: &YearHistory,
+        privacy: &PrivacyConfig,
+        opted_out: &HashSet<String>,
+    ) -> Self {
+        let mut message_count = 0;
+        let mut reaction_count = 0;
+        let mut active_users = HashSet::new();
+        let mut channel_messages: HashMap<&str, u32> = HashMap::new();
+        let mut channel_authors: HashMap<&str, HashSet<&str>> = HashMap::new();
+        let mut reactors: HashMap<&str, u32> = HashMap::new();
+        let mut emoji: HashMap<&str, u32> = HashMap::new();
+        let mut emoji_users: HashMap<&str, HashSet<&str>> = HashMap::new();
+        let mut reacted = Vec::new();
+
+        for (_, message) in history.messages_in_year(0) {
+            message_count += 1;
+            let author = message.message.user.as_str();
+            if !author.is_empty() {
+                active_users.insert(author);
+            }
+            reaction_count += reaction_total(message);
+            for reaction in &message.message.reactions {
+                *emoji.entry(reaction.name.as_str()).or_default() += reaction.count.max(0) as u32;
+                for user in &reaction.users {
+                    *reactors.entry(user.as_str()).or_default() += 1;
+                    emoji_users
+                        .entry(reaction.name.as_str())
+                        .or_default()
+                        .insert(user.as_str());
+                }
+            }
+            if !is_public(history.channel(&message.channel)) {
+                continue;
+            }
+            *channel_messages
+                .entry(message.channel.as_str())
+                .or_default() += 1;
+            if !author.is_empty() {
+                channel_authors
+                    .entry(message.channel.as_str())
+                    .or_default()
+                    .insert(author);
+            }
+            if reaction_total(message) >= privacy.min_leaderboard_count
+                && !opted_out.contains(author)
+            {
+                reacted.push(message);
+            }
+        }
+
+        let min_group_size = privacy.min_group_size;
+        let mut top_channels: Vec<ChannelCount> = channel_messages
+            .into_iter()
+            .filter(|(channel, _)| {
+                channel_authors
+                    .get(channel)
+                    .is_some_and(|authors| authors.len() >= min_group_size)
+            })
+            .map(|(channel, messages)| ChannelCount {
+                channel: channel.to_string(),
+                name: history
+                    .channel(channel)
+                    .and_then(|channel| channel.name.clone())
+                    .unwrap_or(channel.to_string()),
+                messages,
+            })
+            .collect();
+        top_channels.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.name.cmp(&b.name)));
+        top_channels.truncate(TOP_CHANNELS);
+
+        reactors.retain(|user, count| {
+            *count >= privacy.min_leaderboard_count && !opted_out.contains(*user)
+        });
+        emoji.retain(|name, _| {
+            emoji_users
+                .get(name)
+                .is_some_and(|users| users.len() >= min_group_size)
+        });
+
+        reacted.sort_by(|a, b| {
+            reaction_total(b)
+                .cmp(&reaction_total(a))
+                .then(a.message.ts.cmp(&b.message.ts))
+        });
+        reacted.truncate(MOST_REACTED_MESSAGES);
+
+        let mut directory = Directory::default();
+        for channel in &history.channels {
+            if let Some(name) = &channel.name {
+                directory.channels.insert(channel.id.clone(), name.clone());
+            }
+        }
+        for user_id in opted_out {
+            directory
+                .users
+                .insert(user_id.clone(), ANONYMOUS.to_string());
+        }
+
+        Self {
+            year: history.year,
+            message_count,
+            reaction_count,
+            new_channels: history
+                .channels
+                .iter()
+                .filter(|channel| !channel.is_im && !channel.is_mpim)
+                .filter_map(|channel| DateTime::from_timestamp(channel.created, 0))
+                .filter(|created| created.year() == history.year)
+                .count() as u32,
+            new_custom_emoji: None,
+            active_users: active_users.len() as u32,
+            top_channels,
+            top_reactors: ranked(reactors, TOP_REACTORS),
+            top_emoji: ranked(emoji, TOP_EMOJI),
+            most_reacted_messages: reacted.into_iter().cloned().collect(),
+            profiles: HashMap::new(),
+            directory,
+        }
+    }
+
+    pub async fn fetch(
+        slack_client: &SlackClient,
+        stored: Option<StoredEvents<'_>>,
+        year: i32,
+        privacy: &PrivacyConfig,
+        opted_out: &HashSet<String>,
+    ) -> Result<Self, HistoryError> {
+        let history = YearHistory::load(slack_client, stored, year).await?;
+        let mut wrapped = WorkspaceWrapped::compute(&history, privacy, opted_out);
+        wrapped.resolve_profiles(slack_client).await;
+        Ok(wrapped)
+    }
+
+    // Emoji added during the year, unknown when tracking had not started by
+    // its end
+    pub fn count_new_emoji(&mut self, timeline: &EmojiTimeline) {
+        self.new_custom_emoji = timeline.added_in(self.year);
+    }
+
+    // Names for the reactors and the authors of the quoted messages. People
+    // who cannot be looked up are shown by ID.
+    pub async fn resolve_profiles(&mut self, slack_client: &SlackClient) {
+        let mut ids: Vec<String> = self
+            .top_reactors
+            .iter()
+            .map(|(user_id, _)| user_id.clone())
+            .collect();
+        for message in &self.most_reacted_messages {
+            ids.push(message.message.user.clone());
+            ids.extend(
+                crate::slack::util::mentioned_users(&message.message.text)
+                    .into_iter()
+                    .map(str::to_string),
+            );
+        }
+        for id in ids {
+            // Known already, or opted out
+            if id.is_empty() || self.directory.users.contains_key(&id) {
+                continue;
+            }
+            if let Ok(user) = fetch_user(slack_client, &id).await {
+                let name = display_name(&user).to_string();
+                self.directory.users.insert(id.clone(), name.clone());
+                self.profiles.insert(id, name);
+            }
+        }
+    }
+
+    pub fn name_of(&self, user_id: &str) -> String {
+        self.profiles
+            .get(user_id)
+            .cloned()
+            .unwrap_or(user_id.to_string())
+    }
+}
@@ -0,0 +1,305 @@
+use crate::auth::Session;
+use crate::consent::ConsentRegistry;
+use crate::events::EventStore;
+use crate::features::favourite_reaction::reactions_used;
+use crate::features::heatmap::Heatmap;
+use crate::features::history::{
+    ChannelMessage, FetchProgress, HistoryError, StoredEvents, YearHistory,
+};
+use crate::features::streaks::{ActivityStreaks, StreakMode};
+use crate::features::thread_stats::ThreadStats;
+use crate::features::top_collaborators::{top_collaborators, Collaborator};
+use crate::features::word_cloud::{word_cloud, WordCloud};
+use crate::mrkdwn::render::Directory;
+use crate::slack::client::SlackClient;
+use crate::slack::users::{User, UsersInfoParams, UsersInfoResponse};
+use crate::story;
+use chrono::{NaiveDate, Utc};
+use rocket;
+use rocket::http::Status;
+use rocket::response::content::RawHtml;
+use rocket::{get, Route, State};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+const TOP_CHANNELS: usize = 5;
+const TOP_COLLABORATORS: usize = 5;
+const TOP_REACTIONS: usize = 5;
+const WORD_CLOUD_SIZE: usize = 30;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelCount {
+    pub channel: String,
+    pub name: String,
+    pub messages: u32,
+}
+
+// Every stat of a user's year, computed from one crawl of the history
+pub struct UserWrapped {
+    pub user: User,
+    pub year: i32,
+    pub message_count: u32,
+    pub reactions_used: Vec<(String, u32)>,
+    pub top_channels: Vec<ChannelCount>,
+    pub collaborators: Vec<Collaborator>,
+    pub heatmap: Heatmap,
+    pub streaks: ActivityStreaks,
+    pub workday_streaks: ActivityStreaks,
+    pub threads: ThreadStats,
+    pub word_cloud: WordCloud,
+    pub most_reacted_message: Option<ChannelMessage>,
+    // Everyone referenced above, for display names and avatars
+    pub profiles: HashMap<String, User>,
+    pub directory: Directory,
+}
+
+// Shown instead of people who opted out
+pub const ANONYMOUS: &str = "Someone";
+
+// The steps of `UserWrapped::fetch`, in order
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrappedProgress {
+    Profile,
+    History(FetchProgress),
+    Computing,
+    Profiles,
+}
+
+#[derive(Debug)]
+pub enum WrappedError {
+    Request(reqwest::Error),
+    Slack(String),
+    History(HistoryError),
+}
+
+impl fmt::Display for WrappedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WrappedError::Request(error) => write!(f, "request failed: {}", error),
+            WrappedError::Slack(error) => write!(f, "slack returned an error: {}", error),
+            WrappedError::History(error) => write!(f, "could not get history: {}", error),
+        }
+    }
+}
+
+impl UserWrapped {
+    pub fn compute(history: &YearHistory, user: User, today: NaiveDate) -> Self {
+        let tz_offset = user.tz_offset;
+        let user_id = user.id.clone();
+
+        let mut channels: HashMap<&str, u32> = HashMap::new();
+        let mut message_count = 0;
+        let mut most_reacted: Option<(i32, &ChannelMessage)> = None;
+        for (_, message) in history.user_messages(&user_id, tz_offset) {
+            message_count += 1;
+            *channels.entry(message.channel.as_str()).or_default() += 1;
+            let reactions = message
+                .message
+                .reactions
+                .iter()
+                .map(|reaction| reaction.count)
+                .sum();
+            if reactions > 0 && most_reacted.is_none_or(|(best, _)| reactions > best) {
+                most_reacted = Some((reactions, message));
+            }
+        }
+        let mut top_channels: Vec<ChannelCount> = channels
+            .into_iter()
+            .map(|(channel, messages)| ChannelCount {
+                channel: channel.to_string(),
+                name: history
+                    .channel(channel)
+                    .and_then(|channel| channel.name.clone())
+                    .unwrap_or_else(|| "direct messages".to_string()),
+                messages,
+            })
+            .collect();
+        top_channels.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.name.cmp(&b.name)));
+        top_channels.truncate(TOP_CHANNELS);
+
+        let mut reactions = reactions_used(history, &user_id, tz_offset);
+        reactions.truncate(TOP_REACTIONS);
+
+        let mut directory = Directory::default();
+        for channel in &history.channels {
+            if let Some(name) = &channel.name {
+                directory.channels.insert(channel.id.clone(), name.clone());
+            }
+        }
+
+        Self {
+            year: history.year,
+            message_count,
+            reactions_used: reactions,
+            top_channels,
+            collaborators: top_collaborators(history, &user_id, tz_offset, TOP_COLLABORATORS),
+            heatmap: Heatmap::compute(history, &user_id, tz_offset),
+            streaks: ActivityStreaks::compute(
+                history,
+                &user_id,
+                tz_offset,
+                StreakMode::Calendar,
+                today,
+            ),
+            workday_streaks: ActivityStreaks::compute(
+                history,
+                &user_id,
+                tz_offset,
+                StreakMode::Workdays,
+                today,
+            ),
+            threads: ThreadStats::compute(history, &user_id, tz_offset),
+            word_cloud: word_cloud(history, &user_id, tz_offset, WORD_CLOUD_SIZE),
+            most_reacted_message: most_reacted.map(|(_, message)| message.clone()),
+            profiles: HashMap::from([(user_id, user.clone())]),
+            directory,
+            user,
+        }
+    }
+
+    pub async fn fetch(
+        slack_client: &SlackClient,
+        stored: Option<StoredEvents<'_>>,
+        user_id: &str,
+        year: i32,
+    ) -> Result<Self, WrappedError> {
+        Self::fetch_with_progress(slack_client, stored, user_id, year, |_| {}).await
+    }
+
+    pub async fn fetch_with_progress(
+        slack_client: &SlackClient,
+        stored: Option<StoredEvents<'_>>,
+        user_id: &str,
+        year: i32,
+        mut on_progress: impl FnMut(WrappedProgress),
+    ) -> Result<Self, WrappedError> {
+        on_progress(WrappedProgress::Profile);
+        let user = fetch_user(slack_client, user_id).await?;
+        let history = YearHistory::load_with_progress(slack_client, stored, year, |progress| {
+            on_progress(WrappedProgress::History(progress))
+        })
+        .await
+        .map_err(WrappedError::History)?;
+        on_progress(WrappedProgress::Computing);
+        let mut wrapped = UserWrapped::compute(&history, user, Utc::now().date_naive());
+        on_progress(WrappedProgress::Profiles);
+        wrapped.resolve_profiles(slack_client).await;
+        wrapped.threads.resolve_permalink(slack_client).await;
+        Ok(wrapped)
+    }
+
+    // Looks up the collaborators and anyone mentioned in the quoted message.
+    // People who cannot be looked up are shown by ID.
+    pub async fn resolve_profiles(&mut self, slack_client: &SlackClient) {
+        let mut ids: Vec<String> = self
+            .collaborators
+            .iter()
+            .map(|collaborator| collaborator.user_id.clone())
+            .collect();
+        if let Some(message) = &self.most_reacted_message {
+            ids.extend(
+                crate::slack::util::mentioned_users(&message.message.text)
+                    .into_iter()
+                    .map(str::to_string),
+            );
+        }
+        for id in ids {
+            if id == ANONYMOUS || self.profiles.contains_key(&id) {
+                continue;
+            }
+            if let Ok(user) = fetch_user(slack_client, &id).await {
+                self.profiles.insert(id, user);
+            }
+        }
+        for (id, user) in &self.profiles {
+            self.directory
+                .users
+                .insert(id.clone(), display_name(user).to_string());
+        }
+    }
+
+    // Hides the people who opted out. They keep their place among the
+    // collaborators without a name, and mentions of them show no name.
+    pub fn anonymise(&mut self, opted_out: &HashSet<String>) {
+        for collaborator in &mut self.collaborators {
+            if opted_out.contains(&collaborator.user_id) {
+                collaborator.user_id = ANONYMOUS.to_string();
+            }
+        }
+        for id in opted_out {
+            if *id == self.user.id {
+                continue;
+            }
+            self.profiles.remove(id);
+            self.directory
+                .users
+                .insert(id.clone(), ANONYMOUS.to_string());
+        }
+    }
+
+    pub fn name_of(&self, user_id: &str) -> String {
+        self.profiles
+            .get(user_id)
+            .map_or(user_id.to_string(), |user| display_name(user).to_string())
+    }
+}
+
+pub fn display_name(user: &User) -> &str {
+    [
+        user.profile.display_name.as_str(),
+        user.profile.real_name.as_str(),
+    ]
+    .into_iter()
+    .find(|name| !name.is_empty())
+    .unwrap_or(user.name.as_str())
+}
+
+pub async fn fetch_user(slack_client: &SlackClient, user_id: &str) -> Result<User, WrappedError> {
+    let params = UsersInfoParams {
+        user: user_id.to_string(),
+        ..Default::default()
+    };
+    match slack_client.users().info(params).await {
+        Ok(UsersInfoResponse::Success(info)) => Ok(info.user),
+        Ok(UsersInfoResponse::Error(error)) => Err(WrappedError::Slack(error.error)),
+        Err(error) => Err(WrappedError::Request(error)),
+    }
+}
+
+#[get("/wrapped/<user_id>/<year>")]
+pub async fn wrapped_story_route(
+    user_id: &str,
+    year: i32,
+    session: Session,
+    registry: &State<ConsentRegistry>,
+    events: &State<EventStore>,
+    slack_client: SlackClient,
+) -> Result<RawHtml<String>, Status> {
+    session.authorize(user_id)?;
+    registry.authorize(&session, user_id)?;
+    let opted_out = registry.anonymised(&session)?;
+    match UserWrapped::fetch(
+        &slack_client,
+        Some(StoredEvents {
+            store: events,
+            team_id: &session.team_id.0,
+        }),
+        user_id,
+        year,
+    )
+    .await
+    {
+        Ok(mut wrapped) => {
+            wrapped.anonymise(&opted_out);
+            Ok(RawHtml(story::render(&wrapped)))
+        }
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            Ok(RawHtml(story::render_error("Could not build your wrapped")))
+        }
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![wrapped_story_route]
+}
@@ -0,0 +1,508 @@
+// Wrapped computations that take too long to run inside a request. Jobs and
+// their results are kept in SQLite, so they survive restarts.
+pub mod queue;
+
+use crate::api::v1::WrappedDocument;
+use crate::auth::random_token;
+use crate::features::history::FetchProgress;
+use crate::features::wrapped::WrappedProgress;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS wrapped_jobs (
+        id TEXT PRIMARY KEY,
+        team_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        year INTEGER NOT NULL,
+        requested_by TEXT NOT NULL,
+        status TEXT NOT NULL,
+        stage TEXT NOT NULL,
+        channels_done INTEGER NOT NULL DEFAULT 0,
+        channels_total INTEGER NOT NULL DEFAULT 0,
+        pages_fetched INTEGER NOT NULL DEFAULT 0,
+        error TEXT,
+        result TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS wrapped_jobs_by_user
+        ON wrapped_jobs (team_id, user_id, year, created_at);
+";
+
+const COLUMNS: &str = "id, team_id, user_id, year, requested_by, status, stage, \
+    channels_done, channels_total, pages_fetched, error, created_at, updated_at";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+// What a running job is doing, see `WrappedProgress`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStage {
+    Waiting,
+    Profile,
+    History,
+    Computing,
+    Profiles,
+    Done,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub id: String,
+    pub team_id: String,
+    pub user_id: String,
+    pub year: i32,
+    // The signed in user who asked for it, who may be an admin
+    pub requested_by: String,
+    pub status: JobStatus,
+    pub stage: JobStage,
+    pub progress: FetchProgress,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum JobStoreError {
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for JobStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobStoreError::Io(error) => write!(f, "job store error: {}", error),
+            JobStoreError::Sqlite(error) => write!(f, "job store error: {}", error),
+            JobStoreError::Json(error) => write!(f, "invalid job result: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for JobStoreError {
+    fn from(error: io::Error) -> Self {
+        JobStoreError::Io(error)
+    }
+}
+
+impl From<rusqlite::Error> for JobStoreError {
+    fn from(error: rusqlite::Error) -> Self {
+        JobStoreError::Sqlite(error)
+    }
+}
+
+impl From<serde_json::Error> for JobStoreError {
+    fn from(error: serde_json::Error) -> Self {
+        JobStoreError::Json(error)
+    }
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(status: &str) -> Option<Self> {
+        [
+            JobStatus::Queued,
+            JobStatus::Running,
+            JobStatus::Succeeded,
+            JobStatus::Failed,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == status)
+    }
+}
+
+impl JobStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStage::Waiting => "waiting",
+            JobStage::Profile => "profile",
+            JobStage::History => "history",
+            JobStage::Computing => "computing",
+            JobStage::Profiles => "profiles",
+            JobStage::Done => "done",
+        }
+    }
+
+    fn parse(stage: &str) -> Option<Self> {
+        [
+            JobStage::Waiting,
+            JobStage::Profile,
+            JobStage::History,
+            JobStage::Computing,
+            JobStage::Profiles,
+            JobStage::Done,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == stage)
+    }
+}
+
+impl From<WrappedProgress> for JobStage {
+    fn from(progress: WrappedProgress) -> Self {
+        match progress {
+            WrappedProgress::Profile => JobStage::Profile,
+            WrappedProgress::History(_) => JobStage::History,
+            WrappedProgress::Computing => JobStage::Computing,
+            WrappedProgress::Profiles => JobStage::Profiles,
+        }
+    }
+}
+
+impl Job {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, JobStatus::Succeeded | JobStatus::Failed)
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let text_error = |index, value: String| {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                format!("unknown value {}", value).into(),
+            )
+        };
+        let status: String = row.get(5)?;
+        let stage: String = row.get(6)?;
+        Ok(Self {
+            id: row.get(0)?,
+            team_id: row.get(1)?,
+            user_id: row.get(2)?,
+            year: row.get(3)?,
+            requested_by: row.get(4)?,
+            status: JobStatus::parse(&status).ok_or_else(|| text_error(5, status.clone()))?,
+            stage: JobStage::parse(&stage).ok_or_else(|| text_error(6, stage.clone()))?,
+            progress: FetchProgress {
+                channels_done: row.get(7)?,
+                channels_total: row.get(8)?,
+                pages_fetched: row.get(9)?,
+            },
+            error: row.get(10)?,
+            created_at: row.get(11)?,
+            updated_at: row.get(12)?,
+        })
+    }
+}
+
+pub struct JobStore {
+    connection: Mutex<Connection>,
+}
+
+impl JobStore {
+    // Jobs that were running when the server stopped are queued again
+    pub fn open(path: &Path) -> Result<Self, JobStoreError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        connection.execute(
+            "UPDATE wrapped_jobs SET status = ?1, stage = ?2 WHERE status = ?3",
+            params![
+                JobStatus::Queued.as_str(),
+                JobStage::Waiting.as_str(),
+                JobStatus::Running.as_str()
+            ],
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn create(
+        &self,
+        id: &str,
+        team_id: &str,
+        user_id: &str,
+        year: i32,
+        requested_by: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Job, JobStoreError> {
+        let job = Job {
+            id: id.to_string(),
+            team_id: team_id.to_string(),
+            user_id: user_id.to_string(),
+            year,
+            requested_by: requested_by.to_string(),
+            status: JobStatus::Queued,
+            stage: JobStage::Waiting,
+            progress: FetchProgress::default(),
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO wrapped_jobs
+                (id, team_id, user_id, year, requested_by, status, stage, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+            params![
+                job.id,
+                job.team_id,
+                job.user_id,
+                job.year,
+                job.requested_by,
+                job.status.as_str(),
+                job.stage.as_str(),
+                now
+            ],
+        )?;
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Job>, JobStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let job = connection
+            .query_row(
+                &format!("SELECT {} FROM wrapped_jobs WHERE id = ?1", COLUMNS),
+                [id],
+                Job::from_row,
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    // A job for the same wrapped that is still going, or that succeeded
+    // after `fresh_since`, so it does not need computing again
+    pub fn reusable(
+        &self,
+        team_id: &str,
+        user_id: &str,
+        year: i32,
+        fresh_since: DateTime<Utc>,
+    ) -> Result<Option<Job>, JobStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let job = connection
+            .query_row(
+                &format!(
+                    "SELECT {} FROM wrapped_jobs
+                    WHERE team_id = ?1 AND user_id = ?2 AND year = ?3
+                        AND (status IN (?4, ?5) OR (status = ?6 AND updated_at >= ?7))
+                    ORDER BY created_at DESC LIMIT 1",
+                    COLUMNS
+                ),
+                params![
+                    team_id,
+                    user_id,
+                    year,
+                    JobStatus::Queued.as_str(),
+                    JobStatus::Running.as_str(),
+                    JobStatus::Succeeded.as_str(),
+                    fresh_since
+                ],
+                Job::from_row,
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    // The reusable job for the wrapped, see `reusable`, or a new queued one.
+    // The flag is true for a new job, which still needs submitting.
+    pub fn reusable_or_create(
+        &self,
+        team_id: &str,
+        user_id: &str,
+        year: i32,
+        requested_by: &str,
+        fresh_since: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<(Job, bool), JobStoreError> {
+        match self.reusable(team_id, user_id, year, fresh_since)? {
+            Some(job) => Ok((job, false)),
+            None => {
+                let id = random_token();
+                let job = self.create(&id, team_id, user_id, year, requested_by, now)?;
+                Ok((job, true))
+            }
+        }
+    }
+
+    // Queued jobs, oldest first
+    pub fn queued(&self) -> Result<Vec<Job>, JobStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {} FROM wrapped_jobs WHERE status = ?1 ORDER BY created_at",
+            COLUMNS
+        ))?;
+        let jobs = statement
+            .query_map([JobStatus::Queued.as_str()], Job::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
+    pub fn start(&self, id: &str, now: DateTime<Utc>) -> Result<(), JobStoreError> {
+        self.connection.lock().unwrap().execute(
+            "UPDATE wrapped_jobs SET status = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, JobStatus::Running.as_str(), now],
+        )?;
+        Ok(())
+    }
+
+    pub fn progress(
+        &self,
+        id: &str,
+        progress: WrappedProgress,
+        now: DateTime<Utc>,
+    ) -> Result<(), JobStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let stage = JobStage::from(progress).as_str();
+        match progress {
+            WrappedProgress::History(fetched) => connection.execute(
+                "UPDATE wrapped_jobs SET stage = ?2, channels_done = ?3, channels_total = ?4,
+                    pages_fetched = ?5, updated_at = ?6 WHERE id = ?1",
+                params![
+                    id,
+                    stage,
+                    fetched.channels_done,
+                    fetched.channels_total,
+                    fetched.pages_fetched,
+                    now
+                ],
+            )?,
+            _ => connection.execute(
+                "UPDATE wrapped_jobs SET stage = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, stage, now],
+            )?,
+        };
+        Ok(())
+    }
+
+    pub fn succeed(
+        &self,
+        id: &str,
+        result: &WrappedDocument,
+        now: DateTime<Utc>,
+    ) -> Result<(), JobStoreError> {
+        let result = serde_json::to_string(result)?;
+        self.connection.lock().unwrap().execute(
+            "UPDATE wrapped_jobs SET status = ?2, stage = ?3, result = ?4, updated_at = ?5
+                WHERE id = ?1",
+            params![
+                id,
+                JobStatus::Succeeded.as_str(),
+                JobStage::Done.as_str(),
+                result,
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn fail(&self, id: &str, error: &str, now: DateTime<Utc>) -> Result<(), JobStoreError> {
+        self.connection.lock().unwrap().execute(
+            "UPDATE wrapped_jobs SET status = ?2, stage = ?3, error = ?4, updated_at = ?5
+                WHERE id = ?1",
+            params![
+                id,
+                JobStatus::Failed.as_str(),
+                JobStage::Done.as_str(),
+                error,
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    // Jobs about the user or that they asked for, oldest first
+    pub fn for_user(&self, team_id: &str, user_id: &str) -> Result<Vec<Job>, JobStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {} FROM wrapped_jobs
+                WHERE team_id = ?1 AND (user_id = ?2 OR requested_by = ?2)
+                ORDER BY created_at",
+            COLUMNS
+        ))?;
+        let jobs = statement
+            .query_map([team_id, user_id], Job::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
+    // Forgets every job about the user, with the results, returning how many
+    // there were. Jobs they asked for about other people belong to those
+    // people, and are kept without saying who asked.
+    pub fn delete_for_user(&self, team_id: &str, user_id: &str) -> Result<usize, JobStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let deleted = transaction.execute(
+            "DELETE FROM wrapped_jobs WHERE team_id = ?1 AND user_id = ?2",
+            [team_id, user_id],
+        )?;
+        transaction.execute(
+            "UPDATE wrapped_jobs SET requested_by = '' WHERE team_id = ?1 AND requested_by = ?2",
+            [team_id, user_id],
+        )?;
+        transaction.commit()?;
+        Ok(deleted)
+    }
+
+    // Forgets other people's results that list the user as a collaborator,
+    // returning how many there were. They are computed again, with the user
+    // shown anonymously, the next time they are asked for.
+    pub fn delete_naming(&self, team_id: &str, user_id: &str) -> Result<usize, JobStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let results = {
+            let mut statement = transaction.prepare(
+                "SELECT id, result FROM wrapped_jobs
+                    WHERE team_id = ?1 AND user_id != ?2 AND result IS NOT NULL",
+            )?;
+            let results = statement
+                .query_map([team_id, user_id], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            results
+        };
+        let mut deleted = 0;
+        for (id, result) in results {
+            let result: WrappedDocument = serde_json::from_str(&result)?;
+            if result
+                .collaborators
+                .iter()
+                .any(|collaborator| collaborator.user_id == user_id)
+            {
+                deleted += transaction.execute("DELETE FROM wrapped_jobs WHERE id = ?1", [id])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(deleted)
+    }
+
+    // The wrapped computed by a job that succeeded
+    pub fn result(&self, id: &str) -> Result<Option<WrappedDocument>, JobStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let result: Option<Option<String>> = connection
+            .query_row(
+                "SELECT result FROM wrapped_jobs WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match result.flatten() {
+            Some(result) => Ok(Some(serde_json::from_str(&result)?)),
+            None => Ok(None),
+        }
+    }
+}
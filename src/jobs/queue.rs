@@ -0,0 +1,297 @@
+// Runs jobs in the background, a few at a time so a burst of requests does
+// not crawl Slack for everyone at once
+use super::{Job, JobStage, JobStatus, JobStore, JobStoreError};
+use crate::api::v1::WrappedDocument;
+use crate::config::AppConfig;
+use crate::consent::ConsentRegistry;
+use crate::events::EventStore;
+use crate::features::history::{FetchProgress, StoredEvents};
+use crate::features::wrapped::{UserWrapped, WrappedProgress};
+use crate::slack::client::{SlackClient, SlackClients};
+use crate::workspaces::resolver::resolve_token;
+use crate::workspaces::TokenStore;
+use chrono::Utc;
+use rocket::fairing::AdHoc;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::tokio::sync::{broadcast, Semaphore};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+// Events kept for subscribers that fall behind
+const EVENT_BACKLOG: usize = 64;
+
+// What happens to a job while it runs, as sent to subscribers
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum JobEvent {
+    Progress {
+        stage: JobStage,
+        channels_done: u32,
+        channels_total: u32,
+        pages_fetched: u32,
+        /// Guessed from the time the channels so far took
+        estimated_remaining_secs: Option<u64>,
+    },
+    RateLimited {
+        /// How long Slack asked to wait before the next request
+        retry_after_secs: u64,
+    },
+    Finished {
+        status: JobStatus,
+        error: Option<String>,
+    },
+}
+
+impl JobEvent {
+    // The event's name on an event stream
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Progress { .. } => "progress",
+            JobEvent::RateLimited { .. } => "rate_limited",
+            JobEvent::Finished { .. } => "finished",
+        }
+    }
+
+    // Where the job is now, for subscribers that join late
+    pub fn snapshot(job: &Job) -> Self {
+        match job.is_finished() {
+            true => JobEvent::Finished {
+                status: job.status,
+                error: job.error.clone(),
+            },
+            false => JobEvent::progress(job.stage, job.progress, None),
+        }
+    }
+
+    fn progress(stage: JobStage, progress: FetchProgress, remaining: Option<Duration>) -> Self {
+        JobEvent::Progress {
+            stage,
+            channels_done: progress.channels_done,
+            channels_total: progress.channels_total,
+            pages_fetched: progress.pages_fetched,
+            estimated_remaining_secs: remaining.map(|remaining| remaining.as_secs()),
+        }
+    }
+}
+
+// Clones share the stores, workers and subscribers
+#[derive(Clone)]
+pub struct JobQueue {
+    pub store: Arc<JobStore>,
+    // Read instead of crawling for the time it covers
+    history: Option<Arc<EventStore>>,
+    workers: Arc<Semaphore>,
+    // Senders for the jobs submitted and not finished yet
+    events: Arc<Mutex<HashMap<String, broadcast::Sender<JobEvent>>>>,
+}
+
+impl JobQueue {
+    pub fn new(store: JobStore, workers: usize) -> Self {
+        Self {
+            store: Arc::new(store),
+            history: None,
+            workers: Arc::new(Semaphore::new(workers)),
+            events: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn with_events(mut self, history: EventStore) -> Self {
+        self.history = Some(Arc::new(history));
+        self
+    }
+
+    // Starts the job once a worker is free. Must be called from within the
+    // Rocket runtime.
+    // People in opted_out are shown anonymously in the result.
+    pub fn submit(&self, job: &Job, slack_client: SlackClient, opted_out: HashSet<String>) {
+        let (sender, _) = broadcast::channel(EVENT_BACKLOG);
+        self.events
+            .lock()
+            .unwrap()
+            .insert(job.id.clone(), sender.clone());
+        let store = self.store.clone();
+        let history = self.history.clone();
+        let workers = self.workers.clone();
+        let events = self.events.clone();
+        let job = job.clone();
+        rocket::tokio::spawn(async move {
+            if let Ok(_permit) = workers.acquire_owned().await {
+                let stored = history.as_deref().map(|store| StoredEvents {
+                    store,
+                    team_id: &job.team_id,
+                });
+                run(&store, stored, &job, slack_client, &opted_out, &sender).await;
+            }
+            events.lock().unwrap().remove(&job.id);
+        });
+    }
+
+    // Events of a job that is waiting or running in this process
+    pub fn subscribe(&self, job_id: &str) -> Option<broadcast::Receiver<JobEvent>> {
+        let events = self.events.lock().unwrap();
+        events.get(job_id).map(broadcast::Sender::subscribe)
+    }
+
+    // Waits for the job to finish and reads its wrapped, or why it has none
+    pub async fn result(&self, job_id: &str) -> Result<WrappedDocument, String> {
+        if let Some(mut events) = self.subscribe(job_id) {
+            loop {
+                match events.recv().await {
+                    Ok(JobEvent::Finished { .. }) | Err(RecvError::Closed) => break,
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                }
+            }
+        }
+        match self.store.get(job_id) {
+            Ok(Some(job)) if job.status == JobStatus::Succeeded => self
+                .store
+                .result(job_id)
+                .map_err(|error| error.to_string())?
+                .ok_or("the job has no result".to_string()),
+            Ok(Some(job)) if !job.is_finished() => Err("the job is not running".to_string()),
+            Ok(Some(job)) => Err(job.error.unwrap_or_default()),
+            Ok(None) => Err("the job was deleted".to_string()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    // Runs other work, like sending many wrapped at once, once a worker is
+    // free. Must be called from within the Rocket runtime.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let workers = self.workers.clone();
+        rocket::tokio::spawn(async move {
+            if let Ok(_permit) = workers.acquire_owned().await {
+                task.await;
+            }
+        });
+    }
+
+    // The stored events of a team, when the queue has them
+    pub fn stored_events<'a>(&'a self, team_id: &'a str) -> Option<StoredEvents<'a>> {
+        self.history
+            .as_deref()
+            .map(|store| StoredEvents { store, team_id })
+    }
+}
+
+// How long the remaining channels should take, going by the ones done so far
+pub fn estimate_remaining(elapsed: Duration, progress: FetchProgress) -> Option<Duration> {
+    if progress.channels_done == 0 || progress.channels_done > progress.channels_total {
+        return None;
+    }
+    let remaining = progress.channels_total - progress.channels_done;
+    Some(elapsed / progress.channels_done * remaining)
+}
+
+async fn run(
+    store: &JobStore,
+    stored: Option<StoredEvents<'_>>,
+    job: &Job,
+    slack_client: SlackClient,
+    opted_out: &HashSet<String>,
+    events: &broadcast::Sender<JobEvent>,
+) {
+    let log = |result: Result<(), JobStoreError>| {
+        if let Err(error) = result {
+            println!("Encountered error: {}", error);
+        }
+    };
+    // Sending only fails when nobody is listening
+    let waits = events.clone();
+    let slack_client = slack_client.observing_waits(Arc::new(move |secs| {
+        let _ = waits.send(JobEvent::RateLimited {
+            retry_after_secs: secs,
+        });
+    }));
+
+    log(store.start(&job.id, Utc::now()));
+    let mut history_started = None;
+    let mut fetched = job.progress;
+    let wrapped = UserWrapped::fetch_with_progress(
+        &slack_client,
+        stored,
+        &job.user_id,
+        job.year,
+        |progress| {
+            log(store.progress(&job.id, progress, Utc::now()));
+            let mut remaining = None;
+            if let WrappedProgress::History(progress) = progress {
+                let started = *history_started.get_or_insert_with(Instant::now);
+                remaining = estimate_remaining(started.elapsed(), progress);
+                fetched = progress;
+            }
+            let event = JobEvent::progress(progress.into(), fetched, remaining);
+            let _ = events.send(event);
+        },
+    )
+    .await;
+    let finished = match wrapped {
+        Ok(mut wrapped) => {
+            wrapped.anonymise(opted_out);
+            let document = WrappedDocument::new(&wrapped, Utc::now());
+            log(store.succeed(&job.id, &document, Utc::now()));
+            JobEvent::Finished {
+                status: JobStatus::Succeeded,
+                error: None,
+            }
+        }
+        Err(error) => {
+            println!("Encountered error: {}", error);
+            log(store.fail(&job.id, &error.to_string(), Utc::now()));
+            JobEvent::Finished {
+                status: JobStatus::Failed,
+                error: Some(error.to_string()),
+            }
+        }
+    };
+    let _ = events.send(finished);
+}
+
+// Picks up the jobs left queued by the previous run, once the server is up
+pub fn resume() -> AdHoc {
+    AdHoc::on_liftoff("Resume wrapped jobs", |rocket| {
+        Box::pin(async move {
+            let (Some(queue), Some(clients)) =
+                (rocket.state::<JobQueue>(), rocket.state::<SlackClients>())
+            else {
+                return;
+            };
+            let store = rocket.state::<TokenStore>();
+            let registry = rocket.state::<ConsentRegistry>();
+            let fallback = rocket
+                .state::<AppConfig>()
+                .and_then(|config| config.slack.token.as_deref());
+            let jobs = match queue.store.queued() {
+                Ok(jobs) => jobs,
+                Err(error) => {
+                    println!("Encountered error: {}", error);
+                    return;
+                }
+            };
+            for job in jobs {
+                // Better not to run than to name people who opted out
+                let opted_out = match registry.map(|registry| registry.opted_out(&job.team_id)) {
+                    Some(Ok(opted_out)) => opted_out,
+                    None => HashSet::new(),
+                    Some(Err(error)) => {
+                        let _ = queue.store.fail(&job.id, &error.to_string(), Utc::now());
+                        continue;
+                    }
+                };
+                match resolve_token(store, Some(&job.team_id), fallback) {
+                    Ok(token) => queue.submit(&job, clients.get(&token), opted_out),
+                    Err(error) => {
+                        let _ = queue.store.fail(&job.id, &error.to_string(), Utc::now());
+                    }
+                }
+            }
+        })
+    })
+}
@@ -3,11 +3,53 @@ extern crate rocket;
 #[cfg(test)]
 mod tests;
 
+pub mod api;
+pub mod auth;
+pub mod cards;
+pub mod config;
+pub mod consent;
+pub mod emoji;
+pub mod events;
+pub mod jobs;
+pub mod mrkdwn;
+pub mod redact;
 pub mod slack;
+pub mod story;
+pub mod text;
+pub mod workspaces;
+
+use auth::random_token;
+use config::{AppConfig, EventsTransport};
+use consent::ConsentRegistry;
+use emoji::EmojiStore;
+use events::EventStore;
+use jobs::queue::{self, JobQueue};
+use jobs::JobStore;
+use slack::client::SlackClients;
+use std::process;
+use workspaces::TokenStore;
 
 mod features {
+    pub mod dm_wrapped;
     pub mod emoji_contributor;
+    pub mod emoji_timeline;
+    pub mod emoji_usage;
     pub mod favourite_reaction;
+    pub mod heatmap;
+    pub mod history;
+    pub mod privacy;
+    pub mod sign_in;
+    pub mod slack_commands;
+    pub mod slack_events;
+    pub mod slack_install;
+    pub mod slack_interactions;
+    pub mod streaks;
+    pub mod summary_card;
+    pub mod thread_stats;
+    pub mod top_collaborators;
+    pub mod word_cloud;
+    pub mod workspace_wrapped;
+    pub mod wrapped;
 }
 
 #[get("/health")]
@@ -23,8 +65,112 @@ fn version() -> String {
 
 #[launch]
 fn rocket() -> _ {
-    rocket::build()
+    let figment = AppConfig::figment();
+    let mut config = AppConfig::from_figment(&figment).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    if config.card_secret.is_none() {
+        config.card_secret = Some(random_token());
+    }
+    let store = TokenStore::open(&config.token_store_path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let clients = SlackClients::new(&config.http, &config.rate_limit).unwrap_or_else(|error| {
+        eprintln!("Could not build the HTTP client: {}", error);
+        process::exit(1);
+    });
+    let jobs = JobStore::open(&config.database_path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    // The workers read stored events through a connection of their own
+    let history = EventStore::open(&config.database_path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let queue = JobQueue::new(jobs, config.jobs.workers).with_events(history);
+    let consent = ConsentRegistry::open(&config.database_path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let events = EventStore::open(&config.database_path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let emoji = EmojiStore::open(&config.database_path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let toggles = config.features.clone();
+    let transport = config.events.transport;
+
+    let mut rocket = rocket::custom(figment)
+        .manage(store)
+        .manage(clients)
+        .manage(queue)
+        .manage(consent)
+        .manage(events)
+        .manage(emoji)
+        .manage(config)
+        .attach(queue::resume())
         .mount("/", routes![version, health])
         .mount("/", features::emoji_contributor::routes())
         .mount("/", features::favourite_reaction::routes())
+        .mount("/", features::sign_in::routes())
+        .mount("/", features::privacy::routes());
+    let optional = [
+        (toggles.api, "/api/v1", api::v1::routes()),
+        (
+            toggles.dm_wrapped,
+            "/",
+            [
+                features::dm_wrapped::routes(),
+                features::slack_interactions::routes(),
+            ]
+            .concat(),
+        ),
+        (toggles.install, "/", features::slack_install::routes()),
+        (toggles.commands, "/", features::slack_commands::routes()),
+        (
+            toggles.events && transport == EventsTransport::Http,
+            "/",
+            features::slack_events::routes(),
+        ),
+        (
+            toggles.emoji,
+            "/",
+            [
+                features::emoji_timeline::routes(),
+                features::emoji_usage::routes(),
+            ]
+            .concat(),
+        ),
+        (toggles.cards, "/", features::summary_card::routes()),
+        (toggles.story, "/", features::wrapped::routes()),
+        (
+            toggles.text_reports,
+            "/",
+            [
+                features::streaks::routes(),
+                features::thread_stats::routes(),
+                features::top_collaborators::routes(),
+                features::word_cloud::routes(),
+            ]
+            .concat(),
+        ),
+    ];
+    for (enabled, base, routes) in optional {
+        if enabled {
+            rocket = rocket.mount(base, routes);
+        }
+    }
+    if toggles.events && transport == EventsTransport::Socket {
+        rocket = rocket.attach(events::socket::connect());
+    }
+    if toggles.emoji {
+        rocket = rocket.attach(emoji::snapshots::schedule());
+    }
+    rocket
 }
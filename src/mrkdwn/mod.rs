@@ -0,0 +1,37 @@
+// Slack's mrkdwn message format: https://api.slack.com/reference/surfaces/formatting
+pub mod parser;
+pub mod render;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Text(String),
+    Bold(Vec<Node>),
+    Italic(Vec<Node>),
+    Strike(Vec<Node>),
+    Code(String),
+    Preformatted(String),
+    Quote(Vec<Node>),
+    LineBreak,
+    Link {
+        url: String,
+        label: Option<String>,
+    },
+    UserMention {
+        id: String,
+        label: Option<String>,
+    },
+    ChannelMention {
+        id: String,
+        label: Option<String>,
+    },
+    UsergroupMention {
+        id: String,
+        label: Option<String>,
+    },
+    // <!here>, <!channel>, <!everyone>, <!date^...>
+    Special {
+        command: String,
+        label: Option<String>,
+    },
+    Emoji(String),
+}
@@ -0,0 +1,201 @@
+use super::Node;
+
+// Parses message text as returned by the Web API, where `&`, `<` and `>`
+// are escaped as HTML entities.
+pub fn parse(text: &str) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let after_open = &rest[start + 3..];
+        let Some(end) = after_open.find("```") else {
+            break;
+        };
+        let before = &rest[..start];
+        parse_lines(before.strip_suffix('\n').unwrap_or(before), &mut nodes);
+        nodes.push(Node::Preformatted(decode_entities(
+            after_open[..end].trim_matches('\n'),
+        )));
+        let after = &after_open[end + 3..];
+        rest = after.strip_prefix('\n').unwrap_or(after);
+    }
+    parse_lines(rest, &mut nodes);
+    merge_text(nodes)
+}
+
+fn parse_lines(text: &str, nodes: &mut Vec<Node>) {
+    if text.is_empty() {
+        return;
+    }
+    let mut quote: Vec<&str> = Vec::new();
+    let lines: Vec<&str> = text.split('\n').collect();
+    for (index, line) in lines.iter().enumerate() {
+        let quoted = line
+            .strip_prefix("&gt;")
+            .or_else(|| line.strip_prefix('>'))
+            .map(|line| line.strip_prefix(' ').unwrap_or(line));
+        match quoted {
+            Some(line) => quote.push(line),
+            None => {
+                if !quote.is_empty() {
+                    nodes.push(Node::Quote(merge_text(parse_inline_lines(&quote))));
+                    quote.clear();
+                }
+                nodes.extend(parse_inline(line));
+                if index + 1 < lines.len() {
+                    nodes.push(Node::LineBreak);
+                }
+            }
+        }
+    }
+    if !quote.is_empty() {
+        nodes.push(Node::Quote(merge_text(parse_inline_lines(&quote))));
+    }
+}
+
+fn parse_inline_lines(lines: &[&str]) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            nodes.push(Node::LineBreak);
+        }
+        nodes.extend(parse_inline(line));
+    }
+    nodes
+}
+
+fn parse_inline(text: &str) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut plain = String::new();
+    let mut index = 0;
+    while index < text.len() {
+        let rest = &text[index..];
+        let previous = text[..index].chars().next_back();
+        if let Some((node, consumed)) = parse_token(rest, previous) {
+            if !plain.is_empty() {
+                nodes.push(Node::Text(decode_entities(&plain)));
+                plain.clear();
+            }
+            nodes.push(node);
+            index += consumed;
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            plain.push(c);
+            index += c.len_utf8();
+        }
+    }
+    if !plain.is_empty() {
+        nodes.push(Node::Text(decode_entities(&plain)));
+    }
+    nodes
+}
+
+// A token at the start of `text`, with the number of bytes it spans
+fn parse_token(text: &str, previous: Option<char>) -> Option<(Node, usize)> {
+    match text.chars().next()? {
+        '<' => {
+            let end = text.find('>')?;
+            Some((parse_angle(&text[1..end])?, end + 1))
+        }
+        '`' => {
+            let end = text[1..].find('`')? + 1;
+            (end > 1).then(|| (Node::Code(decode_entities(&text[1..end])), end + 1))
+        }
+        ':' => {
+            if previous.is_some_and(|c| c.is_alphanumeric()) {
+                return None;
+            }
+            let end = text[1..].find(':')? + 1;
+            let name = &text[1..end];
+            let is_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '\''));
+            is_name.then(|| (Node::Emoji(name.to_string()), end + 1))
+        }
+        marker @ ('*' | '_' | '~') => {
+            // Formatting only opens at a word boundary and must close before
+            // the next word character
+            if previous.is_some_and(|c| c.is_alphanumeric()) {
+                return None;
+            }
+            let inner_start = 1;
+            if text[inner_start..].starts_with(char::is_whitespace) {
+                return None;
+            }
+            let mut search = inner_start;
+            loop {
+                let end = search + text[search..].find(marker)?;
+                let after = text[end + 1..].chars().next();
+                let before = text[..end].chars().next_back();
+                if end > inner_start
+                    && !before.is_some_and(char::is_whitespace)
+                    && !after.is_some_and(|c| c.is_alphanumeric())
+                {
+                    let inner = merge_text(parse_inline(&text[inner_start..end]));
+                    let node = match marker {
+                        '*' => Node::Bold(inner),
+                        '_' => Node::Italic(inner),
+                        _ => Node::Strike(inner),
+                    };
+                    return Some((node, end + 1));
+                }
+                search = end + 1;
+            }
+        }
+        _ => None,
+    }
+}
+
+fn parse_angle(content: &str) -> Option<Node> {
+    if content.is_empty() {
+        return None;
+    }
+    let (target, label) = match content.split_once('|') {
+        Some((target, label)) => (target, Some(decode_entities(label))),
+        None => (content, None),
+    };
+    let node = if let Some(id) = target.strip_prefix('@') {
+        Node::UserMention {
+            id: id.to_string(),
+            label,
+        }
+    } else if let Some(id) = target.strip_prefix('#') {
+        Node::ChannelMention {
+            id: id.to_string(),
+            label,
+        }
+    } else if let Some(id) = target.strip_prefix("!subteam^") {
+        Node::UsergroupMention {
+            id: id.to_string(),
+            label,
+        }
+    } else if let Some(command) = target.strip_prefix('!') {
+        Node::Special {
+            command: command.to_string(),
+            label,
+        }
+    } else {
+        Node::Link {
+            url: decode_entities(target),
+            label,
+        }
+    };
+    Some(node)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn merge_text(nodes: Vec<Node>) -> Vec<Node> {
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match (merged.last_mut(), node) {
+            (Some(Node::Text(previous)), Node::Text(text)) => previous.push_str(&text),
+            (_, node) => merged.push(node),
+        }
+    }
+    merged
+}
@@ -0,0 +1,161 @@
+use super::Node;
+use std::collections::HashMap;
+
+// Display names used to resolve mentions, keyed by Slack ID
+#[derive(Clone, Debug, Default)]
+pub struct Directory {
+    pub users: HashMap<String, String>,
+    pub channels: HashMap<String, String>,
+    pub usergroups: HashMap<String, String>,
+}
+
+impl Directory {
+    fn user(&self, id: &str, label: &Option<String>) -> String {
+        let name = self
+            .users
+            .get(id)
+            .or(label.as_ref())
+            .map_or(id, |name| name);
+        format!("@{}", name)
+    }
+
+    fn channel(&self, id: &str, label: &Option<String>) -> String {
+        let name = self
+            .channels
+            .get(id)
+            .or(label.as_ref())
+            .map_or(id, |name| name);
+        format!("#{}", name)
+    }
+
+    fn usergroup(&self, id: &str, label: &Option<String>) -> String {
+        let name = self.usergroups.get(id).map(|handle| format!("@{}", handle));
+        name.or(label.clone()).unwrap_or_else(|| format!("@{}", id))
+    }
+}
+
+fn special(command: &str, label: &Option<String>) -> String {
+    match command {
+        "here" | "channel" | "everyone" => format!("@{}", command),
+        // <!date^1392734382^{date_short}|Feb 18, 2014>
+        _ => label.clone().unwrap_or_default(),
+    }
+}
+
+pub fn to_plain_text(nodes: &[Node], directory: &Directory) -> String {
+    let mut text = String::new();
+    // Quotes and preformatted text sit on lines of their own
+    let mut after_block = false;
+    for node in nodes {
+        let is_block = matches!(node, Node::Quote(_) | Node::Preformatted(_));
+        if (is_block || after_block)
+            && !matches!(node, Node::LineBreak)
+            && !text.is_empty()
+            && !text.ends_with('\n')
+        {
+            text.push('\n');
+        }
+        after_block = is_block;
+        match node {
+            Node::Text(value) | Node::Code(value) | Node::Preformatted(value) => {
+                text.push_str(value)
+            }
+            Node::Bold(inner) | Node::Italic(inner) | Node::Strike(inner) => {
+                text.push_str(&to_plain_text(inner, directory))
+            }
+            Node::Quote(inner) => {
+                let quoted = to_plain_text(inner, directory);
+                let lines: Vec<String> = quoted.lines().map(|line| format!("> {}", line)).collect();
+                text.push_str(&lines.join("\n"));
+            }
+            Node::LineBreak => text.push('\n'),
+            Node::Link { url, label } => text.push_str(label.as_ref().unwrap_or(url)),
+            Node::UserMention { id, label } => text.push_str(&directory.user(id, label)),
+            Node::ChannelMention { id, label } => text.push_str(&directory.channel(id, label)),
+            Node::UsergroupMention { id, label } => text.push_str(&directory.usergroup(id, label)),
+            Node::Special { command, label } => text.push_str(&special(command, label)),
+            Node::Emoji(name) => text.push_str(&format!(":{}:", name)),
+        }
+    }
+    text
+}
+
+// Everything from the message is escaped, and only http(s) and mailto links
+// become anchors, so the output is safe to embed in a page.
+pub fn to_html(nodes: &[Node], directory: &Directory) -> String {
+    let mut html = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(value) => html.push_str(&escape_html(value)),
+            Node::Bold(inner) => html.push_str(&wrap("strong", inner, directory)),
+            Node::Italic(inner) => html.push_str(&wrap("em", inner, directory)),
+            Node::Strike(inner) => html.push_str(&wrap("del", inner, directory)),
+            Node::Quote(inner) => html.push_str(&wrap("blockquote", inner, directory)),
+            Node::Code(value) => html.push_str(&format!("<code>{}</code>", escape_html(value))),
+            Node::Preformatted(value) => {
+                html.push_str(&format!("<pre>{}</pre>", escape_html(value)))
+            }
+            Node::LineBreak => html.push_str("<br>"),
+            Node::Link { url, label } => {
+                let text = escape_html(label.as_ref().unwrap_or(url));
+                let lower = url.to_ascii_lowercase();
+                if ["http://", "https://", "mailto:"]
+                    .iter()
+                    .any(|scheme| lower.starts_with(scheme))
+                {
+                    html.push_str(&format!(
+                        "<a href=\"{}\" rel=\"noopener noreferrer nofollow\">{}</a>",
+                        escape_html(url),
+                        text
+                    ));
+                } else {
+                    html.push_str(&text);
+                }
+            }
+            Node::UserMention { id, label } => {
+                html.push_str(&mention("user", &directory.user(id, label)))
+            }
+            Node::ChannelMention { id, label } => {
+                html.push_str(&mention("channel", &directory.channel(id, label)))
+            }
+            Node::UsergroupMention { id, label } => {
+                html.push_str(&mention("usergroup", &directory.usergroup(id, label)))
+            }
+            Node::Special { command, label } => {
+                html.push_str(&mention("special", &special(command, label)))
+            }
+            Node::Emoji(name) => html.push_str(&format!(
+                "<span class=\"emoji\">:{}:</span>",
+                escape_html(name)
+            )),
+        }
+    }
+    html
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn wrap(tag: &str, inner: &[Node], directory: &Directory) -> String {
+    format!("<{}>{}</{}>", tag, to_html(inner, directory), tag)
+}
+
+fn mention(class: &str, text: &str) -> String {
+    format!(
+        "<span class=\"mention {}\">{}</span>",
+        class,
+        escape_html(text)
+    )
+}
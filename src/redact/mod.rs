@@ -0,0 +1,263 @@
+// Makes Slack data safe to hand to analysts or check in as fixtures. User IDs
+// become pseudonyms, keyed so they cannot be reversed without the key but
+// stay the same across a dataset. Message text is scrubbed of email
+// addresses and phone numbers, or replaced outright, and file URLs are
+// dropped.
+use crate::features::history::{ChannelMessage, YearHistory};
+use crate::slack::conversations::Channel;
+use crate::slack::reactions::{FileData, MessageData, Reaction};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+
+// Hex digits of the HMAC kept in a pseudonym, plenty for a workspace
+const PSEUDONYM_LENGTH: usize = 10;
+// Fewer digits than this are more likely dates or amounts than phone numbers
+const MIN_PHONE_DIGITS: usize = 9;
+
+pub const EMAIL_PLACEHOLDER: &str = "[email]";
+pub const PHONE_PLACEHOLDER: &str = "[phone]";
+
+// What happens to message text and file previews
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextRedaction {
+    // Kept, without email addresses or phone numbers, mentioning pseudonyms
+    #[default]
+    Scrub,
+    Strip,
+    // Every character but whitespace becomes an x, so lengths and word
+    // counts survive
+    Placeholder,
+}
+
+pub struct Redactor {
+    key: Vec<u8>,
+    text: TextRedaction,
+}
+
+impl Redactor {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            text: TextRedaction::default(),
+        }
+    }
+
+    pub fn with_text(mut self, text: TextRedaction) -> Self {
+        self.text = text;
+        self
+    }
+
+    // Keeps the ID's first letter, so users (U, W) and bots (B) can still be
+    // told apart
+    pub fn pseudonym(&self, id: &str) -> String {
+        if id.is_empty() {
+            return String::new();
+        }
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(id.as_bytes());
+        let digest: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        let prefix = id.chars().next().filter(char::is_ascii_uppercase);
+        format!("{}{}", prefix.unwrap_or('U'), &digest[..PSEUDONYM_LENGTH])
+    }
+
+    pub fn text(&self, text: &str) -> String {
+        match self.text {
+            TextRedaction::Scrub => scrub(&self.mentions(text)),
+            TextRedaction::Strip => String::new(),
+            TextRedaction::Placeholder => text
+                .chars()
+                .map(|c| if c.is_whitespace() { c } else { 'x' })
+                .collect(),
+        }
+    }
+
+    // `<@U123|ada>` becomes `<@U9F8E7D6C5B>`, without the name
+    fn mentions(&self, text: &str) -> String {
+        let mut redacted = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("<@") {
+            let after = &rest[start + 2..];
+            let Some(end) = after.find('>') else {
+                break;
+            };
+            let id = after[..end].split('|').next().unwrap_or_default();
+            redacted.push_str(&rest[..start]);
+            redacted.push_str(&format!("<@{}>", self.pseudonym(id)));
+            rest = &after[end + 1..];
+        }
+        redacted.push_str(rest);
+        redacted
+    }
+
+    fn users(&self, users: &[String]) -> Vec<String> {
+        users.iter().map(|user| self.pseudonym(user)).collect()
+    }
+
+    pub fn reaction(&self, reaction: &Reaction) -> Reaction {
+        Reaction {
+            users: self.users(&reaction.users),
+            ..reaction.clone()
+        }
+    }
+
+    pub fn message(&self, message: &MessageData) -> MessageData {
+        MessageData {
+            text: self.text(&message.text),
+            user: self.pseudonym(&message.user),
+            reply_users: message
+                .reply_users
+                .as_deref()
+                .map(|users| self.users(users)),
+            reactions: message
+                .reactions
+                .iter()
+                .map(|reaction| self.reaction(reaction))
+                .collect(),
+            permalink: None,
+            ..message.clone()
+        }
+    }
+
+    // Shares are dropped too, they quote the messages the file was shared in
+    pub fn file(&self, file: &FileData) -> FileData {
+        FileData {
+            name: scrub(&file.name),
+            title: scrub(&file.title),
+            user: self.pseudonym(&file.user),
+            username: String::new(),
+            url_private: String::new(),
+            url_private_download: String::new(),
+            permalink: String::new(),
+            permalink_public: String::new(),
+            edit_link: String::new(),
+            preview: self.text(&file.preview),
+            preview_highlight: String::new(),
+            shares: HashMap::new(),
+            ..file.clone()
+        }
+    }
+
+    pub fn channel(&self, channel: &Channel) -> Channel {
+        Channel {
+            creator: channel.creator.as_deref().map(|user| self.pseudonym(user)),
+            user: channel.user.as_deref().map(|user| self.pseudonym(user)),
+            ..channel.clone()
+        }
+    }
+
+    pub fn history(&self, history: &YearHistory) -> YearHistory {
+        YearHistory {
+            year: history.year,
+            channels: history
+                .channels
+                .iter()
+                .map(|channel| self.channel(channel))
+                .collect(),
+            messages: history
+                .messages
+                .iter()
+                .map(|message| ChannelMessage {
+                    channel: message.channel.clone(),
+                    message: self.message(&message.message),
+                })
+                .collect(),
+        }
+    }
+}
+
+// Replaces email addresses and phone numbers, leaving the rest as it was
+pub fn scrub(text: &str) -> String {
+    scrub_phone_numbers(&scrub_emails(text))
+}
+
+fn is_local_part(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._%+-".contains(c)
+}
+
+fn is_domain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ".-".contains(c)
+}
+
+fn scrub_emails(text: &str) -> String {
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('@') {
+        let local_start = rest[..at]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_local_part(*c))
+            .last()
+            .map_or(at, |(i, _)| i);
+        let domain = &rest[at + 1..];
+        let domain_length = domain.find(|c: char| !is_domain(c)).unwrap_or(domain.len());
+        // A sentence may end right after the address
+        let domain = domain[..domain_length].trim_end_matches(['.', '-']);
+        if local_start == at || !domain.contains('.') {
+            scrubbed.push_str(&rest[..=at]);
+            rest = &rest[at + 1..];
+            continue;
+        }
+        scrubbed.push_str(&rest[..local_start]);
+        scrubbed.push_str(EMAIL_PLACEHOLDER);
+        rest = &rest[at + 1 + domain.len()..];
+    }
+    scrubbed.push_str(rest);
+    scrubbed
+}
+
+fn is_phone(c: char) -> bool {
+    c.is_ascii_digit() || " +-().".contains(c)
+}
+
+fn scrub_phone_numbers(text: &str) -> String {
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    let mut previous = None;
+    while let Some((start, c)) = chars.next() {
+        let starts_number = (c.is_ascii_digit() || c == '+' || c == '(')
+            && !previous.is_some_and(|p: char| p.is_alphanumeric());
+        if !starts_number {
+            scrubbed.push(c);
+            previous = Some(c);
+            continue;
+        }
+        // The longest run of phone characters, ending on a digit
+        let mut end = start + c.len_utf8();
+        let mut last_digit = c.is_ascii_digit().then_some(end);
+        let mut digits = c.is_ascii_digit() as usize;
+        while let Some(&(i, next)) = chars.peek() {
+            if !is_phone(next) {
+                break;
+            }
+            chars.next();
+            end = i + next.len_utf8();
+            if next.is_ascii_digit() {
+                digits += 1;
+                last_digit = Some(end);
+            }
+        }
+        // Digits running into letters are an ID or a code
+        let followed_by_word = |last_digit: usize| {
+            text[last_digit..]
+                .chars()
+                .next()
+                .is_some_and(char::is_alphanumeric)
+        };
+        match last_digit {
+            Some(last_digit) if digits >= MIN_PHONE_DIGITS && !followed_by_word(last_digit) => {
+                scrubbed.push_str(PHONE_PLACEHOLDER);
+                scrubbed.push_str(&text[last_digit..end]);
+            }
+            _ => scrubbed.push_str(&text[start..end]),
+        }
+        previous = text[..end].chars().last();
+    }
+    scrubbed
+}
@@ -0,0 +1,58 @@
+use crate::slack::util::{send, RateLimiter};
+use reqwest::Client;
+use reqwest::Error;
+use serde::Deserialize;
+use serde_json::Value;
+
+// Called with an app-level token (xapp-), not a workspace's bot token
+pub struct AppsApi<'a> {
+    pub client: &'a Client,
+    pub token: &'a str,
+    pub rate_limit: &'a RateLimiter,
+}
+
+impl AppsApi<'_> {
+    // https://api.slack.com/methods/apps.connections.open
+    pub async fn connections_open(&self) -> Result<AppsConnectionsOpenResponse, Error> {
+        const URL: &str = "https://slack.com/api/apps.connections.open";
+
+        let request = self
+            .client
+            .post(URL)
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => {
+                        AppsConnectionsOpenResponse::Success(serde_json::from_value(value).unwrap())
+                    }
+                    false => {
+                        AppsConnectionsOpenResponse::Error(serde_json::from_value(value).unwrap())
+                    }
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppsError {
+    pub ok: bool,
+    pub error: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppsConnectionsOpenSuccess {
+    pub ok: bool,
+    // A WebSocket URL, good for one connection
+    pub url: String,
+}
+
+#[derive(Debug)]
+pub enum AppsConnectionsOpenResponse {
+    Success(AppsConnectionsOpenSuccess),
+    Error(AppsError),
+}
@@ -0,0 +1,208 @@
+// Typed Block Kit blocks: https://api.slack.com/reference/block-kit/blocks
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text {
+    PlainText {
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        emoji: Option<bool>,
+    },
+    Mrkdwn {
+        text: String,
+    },
+}
+
+impl Text {
+    pub fn plain(text: impl Into<String>) -> Self {
+        Text::PlainText {
+            text: text.into(),
+            emoji: Some(true),
+        }
+    }
+
+    pub fn mrkdwn(text: impl Into<String>) -> Self {
+        Text::Mrkdwn { text: text.into() }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonStyle {
+    Primary,
+    Danger,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Button {
+        text: Text,
+        action_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        style: Option<ButtonStyle>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    Image {
+        image_url: String,
+        alt_text: String,
+    },
+}
+
+impl Element {
+    pub fn button(text: impl Into<String>, action_id: impl Into<String>) -> Self {
+        Element::Button {
+            text: Text::plain(text),
+            action_id: action_id.into(),
+            value: None,
+            style: None,
+            url: None,
+        }
+    }
+
+    pub fn image(image_url: impl Into<String>, alt_text: impl Into<String>) -> Self {
+        Element::Image {
+            image_url: image_url.into(),
+            alt_text: alt_text.into(),
+        }
+    }
+
+    pub fn value(mut self, new_value: impl Into<String>) -> Self {
+        if let Element::Button { value, .. } = &mut self {
+            *value = Some(new_value.into());
+        }
+        self
+    }
+
+    pub fn style(mut self, new_style: ButtonStyle) -> Self {
+        if let Element::Button { style, .. } = &mut self {
+            *style = Some(new_style);
+        }
+        self
+    }
+
+    pub fn url(mut self, new_url: impl Into<String>) -> Self {
+        if let Element::Button { url, .. } = &mut self {
+            *url = Some(new_url.into());
+        }
+        self
+    }
+}
+
+// Context blocks mix text objects and images
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ContextElement {
+    Text(Text),
+    Image(Element),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Header {
+        text: Text,
+    },
+    Section {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<Text>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fields: Option<Vec<Text>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        accessory: Option<Element>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+    },
+    Context {
+        elements: Vec<ContextElement>,
+    },
+    Image {
+        image_url: String,
+        alt_text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<Text>,
+    },
+    Divider {},
+    Actions {
+        elements: Vec<Element>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+    },
+}
+
+impl Block {
+    pub fn header(text: impl Into<String>) -> Self {
+        Block::Header {
+            text: Text::plain(text),
+        }
+    }
+
+    pub fn section(text: Text) -> Self {
+        Block::Section {
+            text: Some(text),
+            fields: None,
+            accessory: None,
+            block_id: None,
+        }
+    }
+
+    pub fn fields(fields: Vec<Text>) -> Self {
+        Block::Section {
+            text: None,
+            fields: Some(fields),
+            accessory: None,
+            block_id: None,
+        }
+    }
+
+    pub fn context(elements: Vec<ContextElement>) -> Self {
+        Block::Context { elements }
+    }
+
+    pub fn image(image_url: impl Into<String>, alt_text: impl Into<String>) -> Self {
+        Block::Image {
+            image_url: image_url.into(),
+            alt_text: alt_text.into(),
+            title: None,
+        }
+    }
+
+    pub fn divider() -> Self {
+        Block::Divider {}
+    }
+
+    pub fn actions(elements: Vec<Element>) -> Self {
+        Block::Actions {
+            elements,
+            block_id: None,
+        }
+    }
+
+    pub fn accessory(mut self, element: Element) -> Self {
+        if let Block::Section { accessory, .. } = &mut self {
+            *accessory = Some(element);
+        }
+        self
+    }
+
+    pub fn block_id(mut self, id: impl Into<String>) -> Self {
+        match &mut self {
+            Block::Section { block_id, .. } | Block::Actions { block_id, .. } => {
+                *block_id = Some(id.into())
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+// Text from users must not be read as mrkdwn control characters
+pub fn escape_mrkdwn(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,169 @@
+use crate::slack::blocks::Block;
+use crate::slack::reactions::MessageData;
+use crate::slack::util::{add_param_to_url, send, RateLimiter};
+use reqwest::Client;
+use reqwest::Error;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub struct ChatApi<'a> {
+    pub client: &'a Client,
+    pub token: &'a str,
+    pub rate_limit: &'a RateLimiter,
+}
+
+impl ChatApi<'_> {
+    // https://api.slack.com/methods/chat.postMessage
+    pub async fn post_message(
+        &self,
+        params: ChatPostMessageParams,
+    ) -> Result<ChatPostMessageResponse, Error> {
+        const URL: &str = "https://slack.com/api/chat.postMessage";
+
+        let request = self
+            .client
+            .post(URL)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .json(&params);
+        let response = send(request, self.rate_limit).await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => {
+                        ChatPostMessageResponse::Success(serde_json::from_value(value).unwrap())
+                    }
+                    false => ChatPostMessageResponse::Error(serde_json::from_value(value).unwrap()),
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    // https://api.slack.com/methods/chat.update
+    pub async fn update(&self, params: ChatUpdateParams) -> Result<ChatUpdateResponse, Error> {
+        const URL: &str = "https://slack.com/api/chat.update";
+
+        let request = self
+            .client
+            .post(URL)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .json(&params);
+        let response = send(request, self.rate_limit).await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => ChatUpdateResponse::Success(serde_json::from_value(value).unwrap()),
+                    false => ChatUpdateResponse::Error(serde_json::from_value(value).unwrap()),
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    // https://api.slack.com/methods/chat.getPermalink
+    pub async fn get_permalink(
+        &self,
+        params: ChatGetPermalinkParams,
+    ) -> Result<ChatGetPermalinkResponse, Error> {
+        const URL: &str = "https://slack.com/api/chat.getPermalink";
+        let mut url = Url::parse(URL).expect("Unable to parse URL");
+
+        add_param_to_url(&mut url, "channel", &Some(params.channel));
+        add_param_to_url(&mut url, "message_ts", &Some(params.message_ts));
+
+        let request = self
+            .client
+            .get(url.as_ref())
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => {
+                        ChatGetPermalinkResponse::Success(serde_json::from_value(value).unwrap())
+                    }
+                    false => {
+                        ChatGetPermalinkResponse::Error(serde_json::from_value(value).unwrap())
+                    }
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatError {
+    pub ok: bool,
+    pub error: String,
+}
+
+pub struct ChatGetPermalinkParams {
+    pub channel: String,
+    pub message_ts: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatGetPermalinkSuccess {
+    pub ok: bool,
+    pub channel: String,
+    pub permalink: String,
+}
+
+pub enum ChatGetPermalinkResponse {
+    Success(ChatGetPermalinkSuccess),
+    Error(ChatError),
+}
+
+// `text` is the notification and accessibility fallback when blocks are set
+#[derive(Debug, Default, Serialize)]
+pub struct ChatPostMessageParams {
+    pub channel: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<Vec<Block>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_ts: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unfurl_links: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatPostMessageSuccess {
+    pub ok: bool,
+    pub channel: String,
+    pub ts: String,
+    pub message: MessageData,
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum ChatPostMessageResponse {
+    Success(ChatPostMessageSuccess),
+    Error(ChatError),
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ChatUpdateParams {
+    pub channel: String,
+    pub ts: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<Vec<Block>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatUpdateSuccess {
+    pub ok: bool,
+    pub channel: String,
+    pub ts: String,
+    pub text: String,
+}
+
+pub enum ChatUpdateResponse {
+    Success(ChatUpdateSuccess),
+    Error(ChatError),
+}
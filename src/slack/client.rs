@@ -1,29 +1,146 @@
-use super::{emoji::EmojiAPI, reactions::ReactionsApi};
+use super::util::{RateLimiter, WaitObserver};
+use super::{
+    apps::AppsApi, chat::ChatApi, conversations::ConversationsApi, emoji::EmojiAPI,
+    oauth::OauthApi, openid::OpenidApi, reactions::ReactionsApi, users::UsersApi,
+};
+use crate::config::{HttpConfig, RateLimitConfig};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
+// A workspace token with the HTTP client to use it on. Clones are cheap and
+// share the connection pool, so one client can serve many requests.
+#[derive(Clone)]
 pub struct SlackClient {
-    pub token: String,
+    pub token: Arc<str>,
     pub client: reqwest::Client,
+    pub rate_limit: Arc<RateLimiter>,
 }
 
 impl SlackClient {
+    // A standalone client with default settings. The server shares clients
+    // through `SlackClients` instead.
     pub fn new(token: &str) -> Self {
         Self {
-            token: token.to_string(),
+            token: token.into(),
             client: reqwest::Client::new(),
+            rate_limit: Arc::new(RateLimiter::default()),
         }
     }
 
-    pub fn emoji(&self) -> EmojiAPI {
+    // The same client, telling `on_wait` whenever a request waits for
+    // Slack's rate limit
+    pub fn observing_waits(&self, on_wait: WaitObserver) -> Self {
+        Self {
+            rate_limit: Arc::new(RateLimiter {
+                policy: self.rate_limit.policy.clone(),
+                on_wait: Some(on_wait),
+            }),
+            ..self.clone()
+        }
+    }
+
+    pub fn apps(&self) -> AppsApi<'_> {
+        AppsApi {
+            client: &self.client,
+            token: &self.token,
+            rate_limit: &self.rate_limit,
+        }
+    }
+
+    pub fn chat(&self) -> ChatApi<'_> {
+        ChatApi {
+            client: &self.client,
+            token: &self.token,
+            rate_limit: &self.rate_limit,
+        }
+    }
+
+    pub fn conversations(&self) -> ConversationsApi<'_> {
+        ConversationsApi {
+            client: &self.client,
+            token: &self.token,
+            rate_limit: &self.rate_limit,
+        }
+    }
+
+    pub fn emoji(&self) -> EmojiAPI<'_> {
         EmojiAPI {
-            client: self.client.clone(),
-            token: self.token.clone(),
+            client: &self.client,
+            token: &self.token,
+            rate_limit: &self.rate_limit,
         }
     }
 
-    pub fn reactions(&self) -> ReactionsApi {
+    pub fn reactions(&self) -> ReactionsApi<'_> {
         ReactionsApi {
-            client: self.client.clone(),
-            token: self.token.clone(),
+            client: &self.client,
+            token: &self.token,
+            rate_limit: &self.rate_limit,
+        }
+    }
+
+    pub fn users(&self) -> UsersApi<'_> {
+        UsersApi {
+            client: &self.client,
+            token: &self.token,
+            rate_limit: &self.rate_limit,
         }
     }
 }
+
+// Every workspace's client, built on one connection pool. Kept in Rocket's
+// managed state for the lifetime of the server, clones share the clients for
+// background tasks.
+#[derive(Clone)]
+pub struct SlackClients {
+    http: reqwest::Client,
+    rate_limit: Arc<RateLimiter>,
+    clients: Arc<RwLock<HashMap<Arc<str>, SlackClient>>>,
+}
+
+impl SlackClients {
+    pub fn new(http: &HttpConfig, rate_limit: &RateLimitConfig) -> Result<Self, reqwest::Error> {
+        let http = reqwest::Client::builder()
+            .user_agent(&http.user_agent)
+            .timeout(Duration::from_secs(http.timeout_secs))
+            .connect_timeout(Duration::from_secs(http.connect_timeout_secs))
+            .pool_idle_timeout(Duration::from_secs(http.pool_idle_timeout_secs))
+            .pool_max_idle_per_host(http.pool_max_idle_per_host)
+            .build()?;
+        Ok(Self {
+            http,
+            rate_limit: Arc::new(RateLimiter::new(rate_limit.clone())),
+            clients: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    pub fn get(&self, token: &str) -> SlackClient {
+        if let Some(client) = self.clients.read().unwrap().get(token) {
+            return client.clone();
+        }
+        self.clients
+            .write()
+            .unwrap()
+            .entry(token.into())
+            .or_insert_with(|| SlackClient {
+                token: token.into(),
+                client: self.http.clone(),
+                rate_limit: self.rate_limit.clone(),
+            })
+            .clone()
+    }
+
+    // Forgets a token, for example when a workspace reinstalls the app
+    pub fn remove(&self, token: &str) {
+        self.clients.write().unwrap().remove(token);
+    }
+
+    pub fn oauth(&self) -> OauthApi<'_> {
+        OauthApi { client: &self.http }
+    }
+
+    pub fn openid(&self) -> OpenidApi<'_> {
+        OpenidApi { client: &self.http }
+    }
+}
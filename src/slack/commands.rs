@@ -0,0 +1,68 @@
+use crate::slack::blocks::Block;
+use serde::{Deserialize, Serialize};
+
+// What Slack posts when someone runs a slash command
+// https://api.slack.com/interactivity/slash-commands#app_command_handling
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SlashCommand {
+    pub team_id: String,
+    pub channel_id: String,
+    pub user_id: String,
+    pub command: String,
+    pub text: String,
+    pub response_url: String,
+    pub trigger_id: String,
+}
+
+// The answer to a slash command, straight away or later through its
+// response_url
+#[derive(Debug, Serialize)]
+pub struct CommandResponse {
+    // "ephemeral" to show it to the user who ran the command only
+    pub response_type: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<Vec<Block>>,
+    // Replaces the acknowledgement, for answers sent later
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replace_original: Option<bool>,
+}
+
+impl CommandResponse {
+    pub fn ephemeral(text: impl Into<String>) -> Self {
+        Self {
+            response_type: "ephemeral".to_string(),
+            text: text.into(),
+            blocks: None,
+            replace_original: None,
+        }
+    }
+
+    pub fn blocks(mut self, blocks: Vec<Block>) -> Self {
+        self.blocks = Some(blocks);
+        self
+    }
+
+    pub fn replace_original(mut self) -> Self {
+        self.replace_original = Some(true);
+        self
+    }
+}
+
+// Answers a command after the three seconds Slack waits for. A response_url
+// takes up to five answers within thirty minutes.
+// https://api.slack.com/interactivity/handling#message_responses
+pub async fn respond(
+    client: &reqwest::Client,
+    response_url: &str,
+    response: &CommandResponse,
+) -> Result<(), reqwest::Error> {
+    client
+        .post(response_url)
+        .json(response)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use crate::slack::reactions::MessageData;
+use crate::slack::util::{add_param_to_url, send, RateLimiter};
+use reqwest::Client;
+use reqwest::Error;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub struct ConversationsApi<'a> {
+    pub client: &'a Client,
+    pub token: &'a str,
+    pub rate_limit: &'a RateLimiter,
+}
+
+impl ConversationsApi<'_> {
+    // https://api.slack.com/methods/conversations.list
+    pub async fn list(
+        &self,
+        params: ConversationsListParams,
+    ) -> Result<ConversationsListResponse, Error> {
+        const URL: &str = "https://slack.com/api/conversations.list";
+        let mut url = Url::parse(URL).expect("Unable to parse URL");
+
+        add_param_to_url(&mut url, "cursor", &params.cursor);
+        add_param_to_url(
+            &mut url,
+            "exclude_archived",
+            &params.exclude_archived.map(|v| v.to_string()),
+        );
+        add_param_to_url(&mut url, "limit", &params.limit.map(|v| v.to_string()));
+        add_param_to_url(&mut url, "team_id", &params.team_id);
+        add_param_to_url(&mut url, "types", &params.types);
+
+        let request = self
+            .client
+            .get(url.as_ref())
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => {
+                        ConversationsListResponse::Success(serde_json::from_value(value).unwrap())
+                    }
+                    false => {
+                        ConversationsListResponse::Error(serde_json::from_value(value).unwrap())
+                    }
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    // https://api.slack.com/methods/conversations.history
+    pub async fn history(
+        &self,
+        params: ConversationsHistoryParams,
+    ) -> Result<ConversationsMessagesResponse, Error> {
+        const URL: &str = "https://slack.com/api/conversations.history";
+        let mut url = Url::parse(URL).expect("Unable to parse URL");
+
+        add_param_to_url(&mut url, "channel", &Some(params.channel));
+        add_param_to_url(&mut url, "cursor", &params.cursor);
+        add_param_to_url(
+            &mut url,
+            "inclusive",
+            &params.inclusive.map(|v| v.to_string()),
+        );
+        add_param_to_url(&mut url, "latest", &params.latest);
+        add_param_to_url(&mut url, "limit", &params.limit.map(|v| v.to_string()));
+        add_param_to_url(&mut url, "oldest", &params.oldest);
+
+        self.get_messages(url).await
+    }
+
+    // https://api.slack.com/methods/conversations.replies
+    pub async fn replies(
+        &self,
+        params: ConversationsRepliesParams,
+    ) -> Result<ConversationsMessagesResponse, Error> {
+        const URL: &str = "https://slack.com/api/conversations.replies";
+        let mut url = Url::parse(URL).expect("Unable to parse URL");
+
+        add_param_to_url(&mut url, "channel", &Some(params.channel));
+        add_param_to_url(&mut url, "ts", &Some(params.ts));
+        add_param_to_url(&mut url, "cursor", &params.cursor);
+        add_param_to_url(
+            &mut url,
+            "inclusive",
+            &params.inclusive.map(|v| v.to_string()),
+        );
+        add_param_to_url(&mut url, "latest", &params.latest);
+        add_param_to_url(&mut url, "limit", &params.limit.map(|v| v.to_string()));
+        add_param_to_url(&mut url, "oldest", &params.oldest);
+
+        self.get_messages(url).await
+    }
+
+    async fn get_messages(&self, url: Url) -> Result<ConversationsMessagesResponse, Error> {
+        let request = self
+            .client
+            .get(url.as_ref())
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => ConversationsMessagesResponse::Success(
+                        serde_json::from_value(value).unwrap(),
+                    ),
+                    false => {
+                        ConversationsMessagesResponse::Error(serde_json::from_value(value).unwrap())
+                    }
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConversationsError {
+    pub ok: bool,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ConversationsResponseMetadata {
+    #[serde(default)]
+    pub next_cursor: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Channel {
+    pub id: String,
+    pub name: Option<String>, // Absent for direct messages
+    #[serde(default)]
+    pub is_channel: bool,
+    #[serde(default)]
+    pub is_group: bool,
+    #[serde(default)]
+    pub is_im: bool,
+    #[serde(default)]
+    pub is_mpim: bool,
+    #[serde(default)]
+    pub is_private: bool,
+    #[serde(default)]
+    pub is_archived: bool,
+    #[serde(default)]
+    pub created: i64,
+    pub creator: Option<String>,
+    pub user: Option<String>, // The other participant of a direct message
+}
+
+#[derive(Default)]
+pub struct ConversationsListParams {
+    pub cursor: Option<String>,
+    pub exclude_archived: Option<bool>,
+    pub limit: Option<i32>,
+    pub team_id: Option<String>, // Only relevant for org_level apps
+    pub types: Option<String>,   // Comma separated: public_channel,private_channel,mpim,im
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConversationsListSuccess {
+    pub ok: bool,
+    pub channels: Vec<Channel>,
+    #[serde(default)]
+    pub response_metadata: ConversationsResponseMetadata,
+}
+
+pub enum ConversationsListResponse {
+    Success(ConversationsListSuccess),
+    Error(ConversationsError),
+}
+
+#[derive(Default)]
+pub struct ConversationsHistoryParams {
+    pub channel: String,
+    pub cursor: Option<String>,
+    pub inclusive: Option<bool>,
+    pub latest: Option<String>,
+    pub limit: Option<i32>,
+    pub oldest: Option<String>,
+}
+
+#[derive(Default)]
+pub struct ConversationsRepliesParams {
+    pub channel: String,
+    pub ts: String,
+    pub cursor: Option<String>,
+    pub inclusive: Option<bool>,
+    pub latest: Option<String>,
+    pub limit: Option<i32>,
+    pub oldest: Option<String>,
+}
+
+// conversations.history and conversations.replies share a response shape
+#[derive(Debug, Deserialize)]
+pub struct ConversationsMessagesSuccess {
+    pub ok: bool,
+    pub messages: Vec<MessageData>,
+    #[serde(default)]
+    pub has_more: bool,
+    #[serde(default)]
+    pub response_metadata: ConversationsResponseMetadata,
+}
+
+pub enum ConversationsMessagesResponse {
+    Success(ConversationsMessagesSuccess),
+    Error(ConversationsError),
+}
@@ -1,3 +1,4 @@
+use crate::slack::util::{send, RateLimiter};
 use reqwest::Client;
 use reqwest::Error;
 use reqwest::Url;
@@ -17,12 +18,13 @@ impl EmojiListParams {
     }
 }
 
-pub struct EmojiAPI {
-    pub client: Client,
-    pub token: String,
+pub struct EmojiAPI<'a> {
+    pub client: &'a Client,
+    pub token: &'a str,
+    pub rate_limit: &'a RateLimiter,
 }
 
-impl EmojiAPI {
+impl EmojiAPI<'_> {
     // https://api.slack.com/methods/emoji.list
     pub async fn list(&self, params: Option<EmojiListParams>) -> Result<EmojiListResponse, Error> {
         const URL: &str = "https://slack.com/api/emoji.list";
@@ -31,14 +33,13 @@ impl EmojiAPI {
         url.query_pairs_mut()
             .append_pair("include_categories", &params.include_categories.to_string());
 
-        let response = self
+        let request = self
             .client
             .get(url.as_ref())
-            .header("Authorization", format!("Bearer {}", self.token))
-            .send()
-            .await?;
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
 
-        return match response.error_for_status() {
+        match response.error_for_status() {
             Ok(response) => response.json::<Value>().await.map(|value| {
                 match value.get("ok").unwrap().as_bool().unwrap() {
                     true => EmojiListResponse::Success(serde_json::from_value(value).unwrap()),
@@ -46,7 +47,7 @@ impl EmojiAPI {
                 }
             }),
             Err(error) => Err(error),
-        };
+        }
     }
 }
 
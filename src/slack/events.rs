@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// What Slack posts to the events endpoint
+// https://api.slack.com/apis/events-api#callback-field
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventPayload {
+    // Sent once when the request URL is set, to be answered with the challenge
+    UrlVerification {
+        challenge: String,
+    },
+    EventCallback(EventCallback),
+    // Such as app_rate_limited
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventCallback {
+    pub team_id: String,
+    // The same when Slack retries a delivery
+    pub event_id: String,
+    #[serde(default)]
+    pub event_time: i64,
+    // Kept as it came, events we do not handle are not parsed
+    pub event: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UrlVerificationResponse {
+    pub challenge: String,
+}
+
+// The events we ingest. Everything else is acknowledged and dropped.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SlackEvent {
+    Message(MessageEvent),
+    ReactionAdded(ReactionEvent),
+    ReactionRemoved(ReactionEvent),
+    EmojiChanged(EmojiChangedEvent),
+    ChannelCreated {
+        channel: CreatedChannel,
+    },
+    ChannelRename {
+        channel: RenamedChannel,
+    },
+    MemberJoinedChannel(MemberJoinedEvent),
+    #[serde(other)]
+    Other,
+}
+
+// https://api.slack.com/events/message, with its message_changed and
+// message_deleted subtypes
+#[derive(Debug, Deserialize)]
+pub struct MessageEvent {
+    pub channel: String,
+    // channel, group, im or mpim
+    #[serde(default)]
+    pub channel_type: String,
+    pub subtype: Option<String>,
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub text: String,
+    pub ts: String,
+    pub thread_ts: Option<String>,
+    // The message after the edit, for message_changed
+    pub message: Option<ChangedMessage>,
+    // For message_deleted
+    pub deleted_ts: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangedMessage {
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub text: String,
+    pub ts: String,
+}
+
+// https://api.slack.com/events/reaction_added
+#[derive(Debug, Deserialize)]
+pub struct ReactionEvent {
+    pub user: String,
+    pub reaction: String,
+    pub item: ReactionItem,
+    pub event_ts: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReactionItem {
+    // message or file
+    pub r#type: String,
+    pub channel: Option<String>,
+    pub ts: Option<String>,
+}
+
+// https://api.slack.com/events/emoji_changed
+#[derive(Debug, Deserialize)]
+pub struct EmojiChangedEvent {
+    // add, remove or rename
+    pub subtype: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub names: Vec<String>,
+    pub old_name: Option<String>,
+    pub new_name: Option<String>,
+    pub value: Option<String>,
+    pub event_ts: String,
+}
+
+// https://api.slack.com/events/channel_created
+#[derive(Debug, Deserialize)]
+pub struct CreatedChannel {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub created: i64,
+    pub creator: Option<String>,
+}
+
+// https://api.slack.com/events/channel_rename
+#[derive(Debug, Deserialize)]
+pub struct RenamedChannel {
+    pub id: String,
+    pub name: String,
+}
+
+// https://api.slack.com/events/member_joined_channel
+#[derive(Debug, Deserialize)]
+pub struct MemberJoinedEvent {
+    pub user: String,
+    pub channel: String,
+    // C for public channels, G for private ones
+    #[serde(default)]
+    pub channel_type: String,
+    pub inviter: Option<String>,
+    pub event_ts: String,
+}
@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+// Slack posts interactions as a form with the JSON in one field
+// https://api.slack.com/interactivity/handling#payloads
+#[derive(Debug, Deserialize)]
+pub struct InteractionForm {
+    pub payload: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InteractionPayload {
+    BlockActions(BlockActions),
+    // Shortcuts, modal submissions and the like
+    #[serde(other)]
+    Other,
+}
+
+// Someone used an interactive element of a message
+// https://api.slack.com/reference/interaction-payloads/block-actions
+#[derive(Debug, Deserialize)]
+pub struct BlockActions {
+    pub team: InteractionTeam,
+    pub user: InteractionUser,
+    pub container: InteractionContainer,
+    // For answering the user without touching the message
+    pub response_url: Option<String>,
+    pub actions: Vec<BlockAction>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InteractionTeam {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InteractionUser {
+    pub id: String,
+}
+
+// Where the element was, a message for the actions we send
+#[derive(Debug, Deserialize)]
+pub struct InteractionContainer {
+    pub r#type: String,
+    pub message_ts: Option<String>,
+    pub channel_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlockAction {
+    pub action_id: String,
+    pub block_id: Option<String>,
+    // What the button was given when the message was built
+    pub value: Option<String>,
+}
@@ -1,5 +1,16 @@
 pub mod client;
+pub mod signature;
 pub mod util;
 
+pub mod apps;
+pub mod blocks;
+pub mod chat;
+pub mod commands;
+pub mod conversations;
 pub mod emoji;
+pub mod events;
+pub mod interactions;
+pub mod oauth;
+pub mod openid;
 pub mod reactions;
+pub mod users;
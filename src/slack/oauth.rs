@@ -0,0 +1,81 @@
+use reqwest::Client;
+use reqwest::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Exchanging a code needs the app's credentials rather than a token
+pub struct OauthApi<'a> {
+    pub client: &'a Client,
+}
+
+impl OauthApi<'_> {
+    // https://api.slack.com/methods/oauth.v2.access
+    pub async fn v2_access(
+        &self,
+        params: OauthV2AccessParams,
+    ) -> Result<OauthV2AccessResponse, Error> {
+        const URL: &str = "https://slack.com/api/oauth.v2.access";
+
+        let response = self.client.post(URL).form(&params).send().await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => OauthV2AccessResponse::Success(serde_json::from_value(value).unwrap()),
+                    false => OauthV2AccessResponse::Error(serde_json::from_value(value).unwrap()),
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OauthError {
+    pub ok: bool,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OauthV2AccessParams {
+    pub client_id: String,
+    pub client_secret: String,
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_uri: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OauthTeam {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+// The installing user, with their own token when user scopes were requested
+#[derive(Clone, Debug, Deserialize)]
+pub struct OauthAuthedUser {
+    pub id: String,
+    pub scope: Option<String>,
+    pub access_token: Option<String>,
+    pub token_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OauthV2AccessSuccess {
+    pub ok: bool,
+    pub app_id: String,
+    pub access_token: String, // Bot token
+    pub token_type: String,
+    #[serde(default)]
+    pub scope: String, // Comma separated
+    pub bot_user_id: Option<String>,
+    pub team: OauthTeam,
+    pub enterprise: Option<OauthTeam>,
+    pub authed_user: OauthAuthedUser,
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum OauthV2AccessResponse {
+    Success(OauthV2AccessSuccess),
+    Error(OauthError),
+}
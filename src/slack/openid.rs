@@ -0,0 +1,111 @@
+use reqwest::Client;
+use reqwest::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Sign in with Slack. The code is exchanged with the app's credentials, and
+// the resulting token only identifies the person who signed in.
+pub struct OpenidApi<'a> {
+    pub client: &'a Client,
+}
+
+impl OpenidApi<'_> {
+    // https://api.slack.com/methods/openid.connect.token
+    pub async fn connect_token(
+        &self,
+        params: OpenidConnectTokenParams,
+    ) -> Result<OpenidConnectTokenResponse, Error> {
+        const URL: &str = "https://slack.com/api/openid.connect.token";
+
+        let response = self.client.post(URL).form(&params).send().await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => {
+                        OpenidConnectTokenResponse::Success(serde_json::from_value(value).unwrap())
+                    }
+                    false => {
+                        OpenidConnectTokenResponse::Error(serde_json::from_value(value).unwrap())
+                    }
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    // https://api.slack.com/methods/openid.connect.userInfo
+    pub async fn connect_user_info(
+        &self,
+        access_token: &str,
+    ) -> Result<OpenidConnectUserInfoResponse, Error> {
+        const URL: &str = "https://slack.com/api/openid.connect.userInfo";
+
+        let response = self
+            .client
+            .post(URL)
+            .header("Authorization", format!("Bearer {}", access_token))
+            .send()
+            .await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => OpenidConnectUserInfoResponse::Success(
+                        serde_json::from_value(value).unwrap(),
+                    ),
+                    false => {
+                        OpenidConnectUserInfoResponse::Error(serde_json::from_value(value).unwrap())
+                    }
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenidError {
+    pub ok: bool,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OpenidConnectTokenParams {
+    pub client_id: String,
+    pub client_secret: String,
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_uri: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenidConnectTokenSuccess {
+    pub ok: bool,
+    pub access_token: String,
+    pub token_type: String,
+    pub id_token: String,
+}
+
+pub enum OpenidConnectTokenResponse {
+    Success(OpenidConnectTokenSuccess),
+    Error(OpenidError),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenidConnectUserInfoSuccess {
+    pub ok: bool,
+    pub sub: String,
+    #[serde(rename = "https://slack.com/user_id")]
+    pub user_id: String,
+    #[serde(rename = "https://slack.com/team_id")]
+    pub team_id: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub picture: Option<String>,
+}
+
+pub enum OpenidConnectUserInfoResponse {
+    Success(OpenidConnectUserInfoSuccess),
+    Error(OpenidError),
+}
@@ -1,17 +1,18 @@
-use crate::slack::util::add_param_to_url;
+use crate::slack::util::{add_param_to_url, send, RateLimiter};
 use reqwest::Client;
 use reqwest::Error;
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
-pub struct ReactionsApi {
-    pub client: Client,
-    pub token: String,
+pub struct ReactionsApi<'a> {
+    pub client: &'a Client,
+    pub token: &'a str,
+    pub rate_limit: &'a RateLimiter,
 }
 
-impl ReactionsApi {
+impl ReactionsApi<'_> {
     // https://api.slack.com/methods/reactions.add
     pub async fn add(&self, params: ReactionsAddParams) -> Result<ReactionsAddResponse, Error> {
         const URL: &str = "https://slack.com/api/reactions.add";
@@ -21,14 +22,13 @@ impl ReactionsApi {
             .append_pair("name", &params.name)
             .append_pair("timestamp", &params.timestamp);
 
-        let response = self
+        let request = self
             .client
             .post(url.as_ref())
-            .header("Authorization", format!("Bearer {}", self.token))
-            .send()
-            .await?;
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
 
-        return match response.error_for_status() {
+        match response.error_for_status() {
             Ok(response) => response.json::<Value>().await.map(|value| {
                 match value.get("ok").unwrap().as_bool().unwrap() {
                     true => ReactionsAddResponse::Success(serde_json::from_value(value).unwrap()),
@@ -36,7 +36,7 @@ impl ReactionsApi {
                 }
             }),
             Err(error) => Err(error),
-        };
+        }
     }
 
     // https://api.slack.com/methods/reactions.get
@@ -50,14 +50,13 @@ impl ReactionsApi {
         add_param_to_url(&mut url, "full", &params.full.map(|v| v.to_string()));
         add_param_to_url(&mut url, "timestamp", &params.timestamp);
 
-        let response = self
+        let request = self
             .client
             .get(url.as_ref())
-            .header("Authorization", format!("Bearer {}", self.token))
-            .send()
-            .await?;
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
 
-        return match response.error_for_status() {
+        match response.error_for_status() {
             Ok(response) => response.json::<Value>().await.map(|value| {
                 match value.get("ok").unwrap().as_bool().unwrap() {
                     false => ReactionsGetResponse::Error(serde_json::from_value(value).unwrap()),
@@ -100,7 +99,7 @@ impl ReactionsApi {
                 }
             }),
             Err(error) => Err(error),
-        };
+        }
     }
 
     // https://api.slack.com/methods/reactions.list
@@ -116,14 +115,13 @@ impl ReactionsApi {
         add_param_to_url(&mut url, "team_id", &params.team_id);
         add_param_to_url(&mut url, "user", &params.user);
 
-        let response = self
+        let request = self
             .client
             .get(url.as_ref())
-            .header("Authorization", format!("Bearer {}", self.token))
-            .send()
-            .await?;
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
 
-        return match response.error_for_status() {
+        match response.error_for_status() {
             Ok(response) => response.json::<Value>().await.map(|value| {
                 match value.get("ok").unwrap().as_bool().unwrap() {
                     false => ReactionsListResponse::Error(serde_json::from_value(value).unwrap()),
@@ -181,18 +179,16 @@ impl ReactionsApi {
                         ReactionsListResponse::Success(ReactionsListSuccess {
                             ok: true,
                             items: reactions_list_items,
-                            response_metadata: match serde_json::from_value(
+                            response_metadata: serde_json::from_value(
                                 value.get("response_metadata").unwrap().clone(),
-                            ) {
-                                Ok(metadata) => metadata,
-                                Err(_) => ReactionsListResponseMetadata::default(),
-                            },
+                            )
+                            .unwrap_or_default(),
                         })
                     }
                 }
             }),
             Err(error) => Err(error),
-        };
+        }
     }
 
     // https://api.slack.com/methods/reactions.remove
@@ -209,14 +205,13 @@ impl ReactionsApi {
         add_param_to_url(&mut url, "name", &Some(params.name));
         add_param_to_url(&mut url, "timestamp", &params.timestamp);
 
-        let response = self
+        let request = self
             .client
             .post(url.as_ref())
-            .header("Authorization", format!("Bearer {}", self.token))
-            .send()
-            .await?;
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
 
-        return match response.error_for_status() {
+        match response.error_for_status() {
             Ok(response) => response.json::<Value>().await.map(|value| {
                 match value.get("ok").unwrap().as_bool().unwrap() {
                     true => {
@@ -226,7 +221,7 @@ impl ReactionsApi {
                 }
             }),
             Err(error) => Err(error),
-        };
+        }
     }
 }
 
@@ -260,25 +255,34 @@ pub struct ReactionsGetParams {
     pub timestamp: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Reaction {
     pub name: String,
     pub users: Vec<String>,
     pub count: i32,
 }
 
-#[derive(Debug, Deserialize)]
+// Shared by reactions.* and conversations.* responses. The latter omit the
+// permalink and, for bot or system messages, the user.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MessageData {
     pub r#type: String,
+    pub subtype: Option<String>,
+    #[serde(default)]
     pub text: String,
+    #[serde(default)]
     pub user: String,
     pub ts: String,
     pub team: Option<String>,
+    pub thread_ts: Option<String>,
+    pub reply_count: Option<i32>,
+    pub reply_users: Option<Vec<String>>,
+    #[serde(default)]
     pub reactions: Vec<Reaction>,
-    pub permalink: String,
+    pub permalink: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FileData {
     pub id: String,
     pub created: i32,
@@ -349,11 +353,13 @@ pub enum ReactionsGetSuccess {
     },
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum ReactionsGetResponse {
     Success(ReactionsGetSuccess),
     Error(ReactionsError),
 }
 
+#[derive(Default)]
 pub struct ReactionsListParams {
     pub count: Option<i32>,
     pub cursor: Option<String>,
@@ -364,20 +370,6 @@ pub struct ReactionsListParams {
     pub user: Option<String>,
 }
 
-impl Default for ReactionsListParams {
-    fn default() -> Self {
-        Self {
-            count: None,
-            cursor: None,
-            full: None,
-            limit: None,
-            page: None,
-            team_id: None,
-            user: None,
-        }
-    }
-}
-
 #[derive(Debug, Deserialize)]
 pub enum ReactionsListItem {
     ReactionsListMessageItem {
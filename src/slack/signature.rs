@@ -0,0 +1,152 @@
+// Requests Slack sends to us (slash commands and events) are signed
+// with the app's signing secret.
+// https://api.slack.com/authentication/verifying-requests-from-slack
+use crate::auth::tokens_match;
+use crate::config::AppConfig;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rocket::data::{self, Data, FromData, ToByteUnit};
+use rocket::http::Status;
+use rocket::request::Request;
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+use std::fmt;
+
+pub const SIGNATURE_HEADER: &str = "X-Slack-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Slack-Request-Timestamp";
+
+// Older requests may be replays
+const MAX_AGE_SECS: i64 = 5 * 60;
+const MAX_BODY_KIB: u64 = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum SignatureError {
+    NotConfigured,
+    Missing,
+    Expired,
+    Mismatch,
+    TooLarge,
+    Body(String),
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::NotConfigured => write!(f, "slack.signing_secret is not set"),
+            SignatureError::Missing => write!(f, "request is not signed"),
+            SignatureError::Expired => write!(f, "request timestamp is too old"),
+            SignatureError::Mismatch => write!(f, "request signature does not match"),
+            SignatureError::TooLarge => write!(f, "request body is too large"),
+            SignatureError::Body(error) => write!(f, "invalid request body: {}", error),
+        }
+    }
+}
+
+pub fn sign(signing_secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(signing_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("v0:{}:", timestamp).as_bytes());
+    mac.update(body);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("v0={}", digest)
+}
+
+pub fn verify(
+    signing_secret: &str,
+    timestamp: &str,
+    signature: &str,
+    body: &[u8],
+    now: i64,
+) -> Result<(), SignatureError> {
+    let sent_at: i64 = timestamp.parse().map_err(|_| SignatureError::Missing)?;
+    if (now - sent_at).abs() > MAX_AGE_SECS {
+        return Err(SignatureError::Expired);
+    }
+    match tokens_match(&sign(signing_secret, timestamp, body), signature) {
+        true => Ok(()),
+        false => Err(SignatureError::Mismatch),
+    }
+}
+
+// The request body, once its signature has been checked
+pub async fn read_signed<'r>(
+    request: &'r Request<'_>,
+    data: Data<'r>,
+) -> Result<Vec<u8>, (Status, SignatureError)> {
+    let Some(signing_secret) = request
+        .rocket()
+        .state::<AppConfig>()
+        .and_then(|config| config.slack.signing_secret.as_deref())
+    else {
+        return Err((Status::ServiceUnavailable, SignatureError::NotConfigured));
+    };
+    let headers = request.headers();
+    let (Some(timestamp), Some(signature)) = (
+        headers.get_one(TIMESTAMP_HEADER),
+        headers.get_one(SIGNATURE_HEADER),
+    ) else {
+        return Err((Status::Unauthorized, SignatureError::Missing));
+    };
+    let body = match data.open(MAX_BODY_KIB.kibibytes()).into_bytes().await {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        Ok(_) => return Err((Status::PayloadTooLarge, SignatureError::TooLarge)),
+        Err(error) => return Err((Status::BadRequest, SignatureError::Body(error.to_string()))),
+    };
+    verify(
+        signing_secret,
+        timestamp,
+        signature,
+        &body,
+        Utc::now().timestamp(),
+    )
+    .map_err(|error| (Status::Unauthorized, error))?;
+    Ok(body)
+}
+
+// A form Slack sent, such as a slash command, checked against the signing
+// secret before it is parsed
+pub struct SlackForm<T>(pub T);
+
+// The same for JSON, which the Events API sends
+pub struct SlackJson<T>(pub T);
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for SlackForm<T> {
+    type Error = SignatureError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let body = match read_signed(request, data).await {
+            Ok(body) => body,
+            Err(error) => return data::Outcome::Error(error),
+        };
+        match serde_urlencoded::from_bytes(&body) {
+            Ok(form) => data::Outcome::Success(SlackForm(form)),
+            Err(error) => {
+                data::Outcome::Error((Status::BadRequest, SignatureError::Body(error.to_string())))
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for SlackJson<T> {
+    type Error = SignatureError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let body = match read_signed(request, data).await {
+            Ok(body) => body,
+            Err(error) => return data::Outcome::Error(error),
+        };
+        match serde_json::from_slice(&body) {
+            Ok(value) => data::Outcome::Success(SlackJson(value)),
+            Err(error) => {
+                data::Outcome::Error((Status::BadRequest, SignatureError::Body(error.to_string())))
+            }
+        }
+    }
+}
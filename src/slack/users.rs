@@ -0,0 +1,157 @@
+use crate::slack::util::{add_param_to_url, send, RateLimiter};
+use reqwest::Client;
+use reqwest::Error;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+
+pub struct UsersApi<'a> {
+    pub client: &'a Client,
+    pub token: &'a str,
+    pub rate_limit: &'a RateLimiter,
+}
+
+impl UsersApi<'_> {
+    // https://api.slack.com/methods/users.info
+    pub async fn info(&self, params: UsersInfoParams) -> Result<UsersInfoResponse, Error> {
+        const URL: &str = "https://slack.com/api/users.info";
+        let mut url = Url::parse(URL).expect("Unable to parse URL");
+
+        add_param_to_url(&mut url, "user", &Some(params.user));
+        add_param_to_url(
+            &mut url,
+            "include_locale",
+            &params.include_locale.map(|v| v.to_string()),
+        );
+
+        let request = self
+            .client
+            .get(url.as_ref())
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => UsersInfoResponse::Success(serde_json::from_value(value).unwrap()),
+                    false => UsersInfoResponse::Error(serde_json::from_value(value).unwrap()),
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    // https://api.slack.com/methods/users.list
+    pub async fn list(&self, params: UsersListParams) -> Result<UsersListResponse, Error> {
+        const URL: &str = "https://slack.com/api/users.list";
+        let mut url = Url::parse(URL).expect("Unable to parse URL");
+
+        add_param_to_url(&mut url, "cursor", &params.cursor);
+        add_param_to_url(
+            &mut url,
+            "include_locale",
+            &params.include_locale.map(|v| v.to_string()),
+        );
+        add_param_to_url(&mut url, "limit", &params.limit.map(|v| v.to_string()));
+        add_param_to_url(&mut url, "team_id", &params.team_id);
+
+        let request = self
+            .client
+            .get(url.as_ref())
+            .header("Authorization", format!("Bearer {}", self.token));
+        let response = send(request, self.rate_limit).await?;
+
+        match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.map(|value| {
+                match value.get("ok").unwrap().as_bool().unwrap() {
+                    true => UsersListResponse::Success(serde_json::from_value(value).unwrap()),
+                    false => UsersListResponse::Error(serde_json::from_value(value).unwrap()),
+                }
+            }),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsersError {
+    pub ok: bool,
+    pub error: String,
+}
+
+#[derive(Default)]
+pub struct UsersInfoParams {
+    pub user: String,
+    pub include_locale: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UserProfile {
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub real_name: String,
+    pub image_72: Option<String>,
+    pub image_192: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub team_id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub deleted: bool,
+    pub real_name: Option<String>,
+    pub tz: Option<String>,
+    pub tz_label: Option<String>,
+    #[serde(default)]
+    pub tz_offset: i32, // Seconds east of UTC
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default)]
+    pub is_owner: bool,
+    #[serde(default)]
+    pub is_bot: bool,
+    #[serde(default)]
+    pub profile: UserProfile,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsersInfoSuccess {
+    pub ok: bool,
+    pub user: User,
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum UsersInfoResponse {
+    Success(UsersInfoSuccess),
+    Error(UsersError),
+}
+
+#[derive(Default)]
+pub struct UsersListParams {
+    pub cursor: Option<String>,
+    pub include_locale: Option<bool>,
+    pub limit: Option<i32>,
+    pub team_id: Option<String>, // Only relevant for org_level apps
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UsersResponseMetadata {
+    #[serde(default)]
+    pub next_cursor: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsersListSuccess {
+    pub ok: bool,
+    pub members: Vec<User>,
+    #[serde(default)]
+    pub response_metadata: UsersResponseMetadata,
+}
+
+pub enum UsersListResponse {
+    Success(UsersListSuccess),
+    Error(UsersError),
+}
@@ -1,7 +1,86 @@
-use reqwest::Url;
+use crate::config::RateLimitConfig;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Error, RequestBuilder, Response, StatusCode, Url};
+use rocket::tokio::time::sleep;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub fn add_param_to_url(url: &mut Url, name: &str, value: &Option<String>) {
     if let Some(val) = value {
         url.query_pairs_mut().append_pair(name, val);
     }
 }
+
+// Slack timestamps look like "1700000000.000100": seconds since the epoch,
+// with a per-channel sequence number after the dot.
+pub fn parse_slack_ts(ts: &str) -> Option<i64> {
+    ts.split('.').next()?.parse().ok()
+}
+
+// User IDs mentioned in message text as <@U123> or <@U123|name>
+pub fn mentioned_users(text: &str) -> Vec<&str> {
+    text.split("<@")
+        .skip(1)
+        .filter_map(|rest| {
+            let end = rest.find(['>', '|'])?;
+            Some(&rest[..end])
+        })
+        .collect()
+}
+
+// Told how many seconds a request is about to wait for Slack's rate limit
+pub type WaitObserver = Arc<dyn Fn(u64) + Send + Sync>;
+
+// The rate limit policy of a client, and who to tell about waits
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    pub policy: RateLimitConfig,
+    pub on_wait: Option<WaitObserver>,
+}
+
+impl RateLimiter {
+    pub fn new(policy: RateLimitConfig) -> Self {
+        Self {
+            policy,
+            on_wait: None,
+        }
+    }
+}
+
+// Sends the request, waiting out 429 Too Many Requests answers for as long
+// as Slack asks in `Retry-After`, within the rate limit policy. The last
+// 429 is returned when the policy gives up.
+pub async fn send(request: RequestBuilder, limiter: &RateLimiter) -> Result<Response, Error> {
+    let policy = &limiter.policy;
+    let mut retries = 0;
+    loop {
+        // Streaming bodies cannot be sent twice
+        let Some(attempt) = request.try_clone() else {
+            return request.send().await;
+        };
+        let response = attempt.send().await?;
+        if response.status() != StatusCode::TOO_MANY_REQUESTS || retries >= policy.max_retries {
+            return Ok(response);
+        }
+        let wait = retry_after(&response).unwrap_or(1);
+        if wait > policy.max_wait_secs {
+            return Ok(response);
+        }
+        if let Some(on_wait) = &limiter.on_wait {
+            on_wait(wait);
+        }
+        sleep(Duration::from_secs(wait)).await;
+        retries += 1;
+    }
+}
+
+pub fn retry_after(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
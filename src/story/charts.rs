@@ -0,0 +1,116 @@
+// Inline SVG charts, so pages render without any scripts or external assets
+use crate::features::heatmap::Heatmap;
+use crate::mrkdwn::render::escape_html;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+// Horizontal bars, one per (label, value)
+pub fn bar_chart(bars: &[(String, u32)]) -> String {
+    const WIDTH: u32 = 360;
+    const LABEL_WIDTH: u32 = 120;
+    const BAR_HEIGHT: u32 = 28;
+    let max = bars
+        .iter()
+        .map(|(_, value)| *value)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let height = bars.len() as u32 * BAR_HEIGHT;
+
+    let mut svg = format!(
+        "<svg viewBox=\"0 0 {} {}\" width=\"100%\" role=\"img\">",
+        WIDTH, height
+    );
+    for (index, (label, value)) in bars.iter().enumerate() {
+        let y = index as u32 * BAR_HEIGHT;
+        let width = (WIDTH - LABEL_WIDTH - 40) * value / max;
+        svg += &format!(
+            "<text x=\"0\" y=\"{}\">{}</text>\
+             <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"4\" fill=\"#ecb22e\"/>\
+             <text x=\"{}\" y=\"{}\">{}</text>",
+            y + 18,
+            escape_html(&truncate(label, 16)),
+            LABEL_WIDTH,
+            y + 4,
+            width,
+            BAR_HEIGHT - 8,
+            LABEL_WIDTH + width + 6,
+            y + 18,
+            value
+        );
+    }
+    svg + "</svg>"
+}
+
+// Weekday by hour grid, shaded by message count
+pub fn heatmap(heatmap: &Heatmap) -> String {
+    const CELL: u32 = 14;
+    const LEFT: u32 = 32;
+    const TOP: u32 = 16;
+    let max = heatmap.max().max(1) as f64;
+
+    let mut svg = format!(
+        "<svg viewBox=\"0 0 {} {}\" width=\"100%\" role=\"img\">",
+        LEFT + 24 * CELL,
+        TOP + 7 * CELL
+    );
+    for hour in (0..24).step_by(6) {
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\">{:02}h</text>",
+            LEFT + hour * CELL,
+            TOP - 4,
+            hour
+        );
+    }
+    for (day, hours) in heatmap.counts.iter().enumerate() {
+        let y = TOP + day as u32 * CELL;
+        svg += &format!(
+            "<text x=\"0\" y=\"{}\">{}</text>",
+            y + CELL - 3,
+            WEEKDAYS[day]
+        );
+        for (hour, count) in hours.iter().enumerate() {
+            svg += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"2\" \
+                 fill=\"#ecb22e\" fill-opacity=\"{:.2}\"><title>{} {:02}:00 - {} messages</title></rect>",
+                LEFT + hour as u32 * CELL,
+                y,
+                CELL - 2,
+                CELL - 2,
+                0.08 + 0.92 * *count as f64 / max,
+                WEEKDAYS[day],
+                hour,
+                count
+            );
+        }
+    }
+    svg + "</svg>"
+}
+
+// A round avatar showing the person's initials
+pub fn initials_avatar(name: &str) -> String {
+    let initials: String = name
+        .split_whitespace()
+        .filter_map(|word| word.chars().next())
+        .take(2)
+        .flat_map(char::to_uppercase)
+        .collect();
+    // Stable colour per name
+    let hue = name.bytes().fold(0u32, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as u32)
+    }) % 360;
+    format!(
+        "<svg viewBox=\"0 0 40 40\" width=\"40\" height=\"40\" aria-hidden=\"true\">\
+         <circle cx=\"20\" cy=\"20\" r=\"20\" fill=\"hsl({}, 55%, 45%)\"/>\
+         <text x=\"20\" y=\"25\" text-anchor=\"middle\" style=\"font-size:14px\">{}</text></svg>",
+        hue,
+        escape_html(&initials)
+    )
+}
+
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    match text.chars().count() > max_chars {
+        true => text.chars().take(max_chars - 1).chain(['…']).collect(),
+        false => text.to_string(),
+    }
+}
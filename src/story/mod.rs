@@ -0,0 +1,202 @@
+// Server-rendered HTML "story" of a user's wrapped: a row of cards with
+// embedded CSS and inline SVG charts, and no external assets.
+pub mod charts;
+
+use crate::features::streaks::Streak;
+use crate::features::wrapped::UserWrapped;
+use crate::mrkdwn::parser::parse;
+use crate::mrkdwn::render::{escape_html, to_html};
+
+const PAGE_TEMPLATE: &str = include_str!("templates/page.html");
+const CARD_TEMPLATE: &str = include_str!("templates/card.html");
+const STYLE: &str = include_str!("templates/story.css");
+
+// Replaces each {{name}} in the template in a single pass. Values are
+// inserted as-is, so callers escape anything that came from Slack.
+pub fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut page = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        page.push_str(&rest[..start]);
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => page.push_str(value),
+            None => page.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    page.push_str(rest);
+    page
+}
+
+pub fn render(wrapped: &UserWrapped) -> String {
+    let name = wrapped.name_of(&wrapped.user.id);
+    let cards = [
+        intro_card(wrapped, &name),
+        reactions_card(wrapped),
+        channels_card(wrapped),
+        collaborators_card(wrapped),
+        heatmap_card(wrapped),
+        streaks_card(wrapped),
+        most_reacted_card(wrapped),
+    ];
+    fill(
+        PAGE_TEMPLATE,
+        &[
+            (
+                "title",
+                &escape_html(&format!("{}'s {} wrapped", name, wrapped.year)),
+            ),
+            ("style", STYLE),
+            ("cards", &cards.concat()),
+        ],
+    )
+}
+
+pub fn render_error(message: &str) -> String {
+    fill(
+        PAGE_TEMPLATE,
+        &[
+            ("title", "Slackify Wrapped"),
+            ("style", STYLE),
+            (
+                "cards",
+                &card(
+                    "error",
+                    "Oh no",
+                    &format!("<p>{}</p>", escape_html(message)),
+                ),
+            ),
+        ],
+    )
+}
+
+fn card(class: &str, heading: &str, body: &str) -> String {
+    fill(
+        CARD_TEMPLATE,
+        &[("class", class), ("heading", heading), ("body", body)],
+    )
+}
+
+fn intro_card(wrapped: &UserWrapped, name: &str) -> String {
+    card(
+        "intro",
+        &format!("{}, this was your {}", escape_html(name), wrapped.year),
+        &format!(
+            "<p class=\"big\">{}</p><p>messages sent, across {} active days.</p>",
+            wrapped.message_count, wrapped.streaks.active_days
+        ),
+    )
+}
+
+fn reactions_card(wrapped: &UserWrapped) -> String {
+    let Some((favourite, count)) = wrapped.reactions_used.first() else {
+        return card(
+            "reactions",
+            "Favourite reaction",
+            "<p class=\"muted\">You kept your reactions to yourself this year.</p>",
+        );
+    };
+    let others: String = wrapped
+        .reactions_used
+        .iter()
+        .skip(1)
+        .map(|(name, count)| format!("<li>:{}: &times; {}</li>", escape_html(name), count))
+        .collect();
+    card(
+        "reactions",
+        "Favourite reaction",
+        &format!(
+            "<p class=\"big\">:{}:</p><p>used {} times</p><ol>{}</ol>",
+            escape_html(favourite),
+            count,
+            others
+        ),
+    )
+}
+
+fn channels_card(wrapped: &UserWrapped) -> String {
+    let bars: Vec<(String, u32)> = wrapped
+        .top_channels
+        .iter()
+        .map(|channel| (channel.name.clone(), channel.messages))
+        .collect();
+    card("channels", "Top channels", &charts::bar_chart(&bars))
+}
+
+fn collaborators_card(wrapped: &UserWrapped) -> String {
+    let people: String = wrapped
+        .collaborators
+        .iter()
+        .map(|collaborator| {
+            let name = wrapped.name_of(&collaborator.user_id);
+            format!(
+                "<li>{}<span>{}</span></li>",
+                charts::initials_avatar(&name),
+                escape_html(&name)
+            )
+        })
+        .collect();
+    card(
+        "collaborators",
+        "Your people",
+        &format!("<ol>{}</ol>", people),
+    )
+}
+
+fn heatmap_card(wrapped: &UserWrapped) -> String {
+    card(
+        "heatmap",
+        "When you were around",
+        &charts::heatmap(&wrapped.heatmap),
+    )
+}
+
+fn streaks_card(wrapped: &UserWrapped) -> String {
+    let streak_days = |streak: &Option<Streak>| streak.as_ref().map_or(0, |streak| streak.days);
+    let mut body = format!(
+        "<p class=\"big\">{} days</p><p>longest streak</p>\
+         <ul><li>{} workdays in a row</li><li>{} days and counting</li>",
+        streak_days(&wrapped.streaks.longest),
+        streak_days(&wrapped.workday_streaks.longest),
+        streak_days(&wrapped.streaks.current)
+    );
+    if let Some(busiest) = &wrapped.streaks.busiest_day {
+        let channel = wrapped
+            .directory
+            .channels
+            .get(&busiest.top_channel)
+            .unwrap_or(&busiest.top_channel);
+        body += &format!(
+            "<li>Busiest day: {} with {} messages, mostly in #{}</li>",
+            busiest.date.format("%B %-d"),
+            busiest.message_count,
+            escape_html(channel)
+        );
+    }
+    card("streaks", "Streaks", &(body + "</ul>"))
+}
+
+fn most_reacted_card(wrapped: &UserWrapped) -> String {
+    let Some(message) = &wrapped.most_reacted_message else {
+        return String::new();
+    };
+    let reactions: String = message
+        .message
+        .reactions
+        .iter()
+        .map(|reaction| format!(":{}: {} ", escape_html(&reaction.name), reaction.count))
+        .collect();
+    card(
+        "most-reacted",
+        "Your most-reacted message",
+        &format!(
+            "<blockquote class=\"quote\">{}</blockquote><p class=\"muted\">{}</p>",
+            to_html(&parse(&message.message.text), &wrapped.directory),
+            reactions
+        ),
+    )
+}
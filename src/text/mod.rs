@@ -0,0 +1,3 @@
+pub mod stopwords;
+pub mod tfidf;
+pub mod tokenize;
@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Language {
+    English,
+    French,
+    German,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::English,
+        Language::French,
+        Language::German,
+        Language::Spanish,
+    ];
+
+    pub fn stopwords(self) -> &'static HashSet<&'static str> {
+        static LISTS: OnceLock<[HashSet<&'static str>; 4]> = OnceLock::new();
+        let lists = LISTS.get_or_init(|| {
+            [ENGLISH, FRENCH, GERMAN, SPANISH].map(|words| words.iter().copied().collect())
+        });
+        &lists[self as usize]
+    }
+
+    // The language whose stopwords make up most of the tokens, defaulting to
+    // English when nothing matches
+    pub fn detect<S: AsRef<str>>(tokens: &[S]) -> Language {
+        Language::ALL
+            .into_iter()
+            .map(|language| {
+                let stopwords = language.stopwords();
+                let hits = tokens
+                    .iter()
+                    .filter(|token| stopwords.contains(token.as_ref()))
+                    .count();
+                (language, hits)
+            })
+            .rev()
+            .max_by_key(|(_, hits)| *hits)
+            .filter(|(_, hits)| *hits > 0)
+            .map_or(Language::English, |(language, _)| language)
+    }
+}
+
+// Slack conversations mix English into everything, so its stopwords are
+// always removed alongside those of the detected language.
+pub fn is_stopword(language: Language, word: &str) -> bool {
+    Language::English.stopwords().contains(word) || language.stopwords().contains(word)
+}
+
+const ENGLISH: &[&str] = &[
+    "a",
+    "about",
+    "above",
+    "after",
+    "again",
+    "against",
+    "all",
+    "also",
+    "am",
+    "an",
+    "and",
+    "any",
+    "are",
+    "aren't",
+    "as",
+    "at",
+    "be",
+    "because",
+    "been",
+    "before",
+    "being",
+    "below",
+    "between",
+    "both",
+    "but",
+    "by",
+    "can",
+    "can't",
+    "cannot",
+    "could",
+    "couldn't",
+    "did",
+    "didn't",
+    "do",
+    "does",
+    "doesn't",
+    "doing",
+    "don't",
+    "down",
+    "during",
+    "each",
+    "few",
+    "for",
+    "from",
+    "further",
+    "get",
+    "got",
+    "had",
+    "hadn't",
+    "has",
+    "hasn't",
+    "have",
+    "haven't",
+    "having",
+    "he",
+    "her",
+    "here",
+    "hers",
+    "herself",
+    "him",
+    "himself",
+    "his",
+    "how",
+    "i",
+    "i'd",
+    "i'll",
+    "i'm",
+    "i've",
+    "if",
+    "in",
+    "into",
+    "is",
+    "isn't",
+    "it",
+    "it's",
+    "its",
+    "itself",
+    "just",
+    "let's",
+    "like",
+    "me",
+    "more",
+    "most",
+    "my",
+    "myself",
+    "no",
+    "nor",
+    "not",
+    "now",
+    "of",
+    "off",
+    "ok",
+    "okay",
+    "on",
+    "once",
+    "one",
+    "only",
+    "or",
+    "other",
+    "our",
+    "ours",
+    "ourselves",
+    "out",
+    "over",
+    "own",
+    "same",
+    "she",
+    "should",
+    "shouldn't",
+    "so",
+    "some",
+    "such",
+    "than",
+    "that",
+    "that's",
+    "the",
+    "their",
+    "theirs",
+    "them",
+    "themselves",
+    "then",
+    "there",
+    "there's",
+    "these",
+    "they",
+    "they're",
+    "this",
+    "those",
+    "through",
+    "to",
+    "too",
+    "under",
+    "until",
+    "up",
+    "us",
+    "very",
+    "was",
+    "wasn't",
+    "we",
+    "we're",
+    "were",
+    "weren't",
+    "what",
+    "what's",
+    "when",
+    "where",
+    "which",
+    "while",
+    "who",
+    "whom",
+    "why",
+    "will",
+    "with",
+    "won't",
+    "would",
+    "wouldn't",
+    "yeah",
+    "yes",
+    "you",
+    "you're",
+    "your",
+    "yours",
+    "yourself",
+    "yourselves",
+];
+
+const FRENCH: &[&str] = &[
+    "au", "aux", "avec", "ce", "ces", "cette", "dans", "de", "des", "du", "elle", "en", "est",
+    "et", "eux", "il", "ils", "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me", "mes",
+    "moi", "mon", "ne", "nos", "notre", "nous", "on", "ou", "oui", "par", "pas", "pour", "qu",
+    "que", "qui", "sa", "se", "ses", "son", "sont", "sur", "ta", "te", "tes", "toi", "ton", "tu",
+    "un", "une", "vos", "votre", "vous", "c'est", "j'ai", "été", "être", "avoir", "fait", "très",
+];
+
+const GERMAN: &[&str] = &[
+    "aber", "als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "bist", "da", "das",
+    "dass", "dein", "dem", "den", "der", "des", "dich", "die", "dir", "doch", "du", "ein", "eine",
+    "einem", "einen", "einer", "es", "für", "hat", "hatte", "ich", "ihr", "im", "in", "ist", "ja",
+    "kann", "mein", "mich", "mir", "mit", "nach", "nicht", "noch", "nur", "oder", "schon", "sein",
+    "sich", "sie", "sind", "so", "und", "uns", "vom", "von", "vor", "war", "was", "wenn", "wie",
+    "wir", "wird", "zu", "zum", "zur",
+];
+
+const SPANISH: &[&str] = &[
+    "al", "como", "con", "cuando", "de", "del", "el", "ella", "ellos", "en", "era", "es", "esa",
+    "ese", "eso", "esta", "está", "este", "esto", "fue", "ha", "hay", "la", "las", "le", "les",
+    "lo", "los", "más", "me", "mi", "muy", "no", "nos", "o", "para", "pero", "por", "que", "qué",
+    "se", "si", "sí", "sin", "sobre", "su", "sus", "también", "te", "tu", "un", "una", "uno", "y",
+    "ya", "yo",
+];
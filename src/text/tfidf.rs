@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeightedTerm {
+    pub term: String,
+    pub count: u32,
+    // Relative to the most distinctive term, which has a weight of 1
+    pub weight: f64,
+}
+
+// Term counts per document. Terms are weighted TF-IDF style, so those
+// common to every document (the workspace's jargon) rank below the ones
+// that set a document apart.
+#[derive(Default)]
+pub struct Corpus {
+    documents: HashMap<String, HashMap<String, u32>>,
+}
+
+impl Corpus {
+    pub fn add(&mut self, document: &str, term: String) {
+        *self
+            .documents
+            .entry(document.to_string())
+            .or_default()
+            .entry(term)
+            .or_default() += 1;
+    }
+
+    pub fn distinctive_terms(&self, document: &str, limit: usize) -> Vec<WeightedTerm> {
+        let Some(terms) = self.documents.get(document) else {
+            return Vec::new();
+        };
+        let total: u32 = terms.values().sum();
+        let documents = self.documents.len() as f64;
+
+        let mut scored: Vec<(&String, u32, f64)> = terms
+            .iter()
+            .map(|(term, count)| {
+                let frequency = self
+                    .documents
+                    .values()
+                    .filter(|terms| terms.contains_key(term))
+                    .count() as f64;
+                let idf = ((1.0 + documents) / (1.0 + frequency)).ln() + 1.0;
+                (term, *count, *count as f64 / total as f64 * idf)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.2.total_cmp(&a.2).then(a.0.cmp(b.0)));
+        scored.truncate(limit);
+
+        let top = scored.first().map_or(1.0, |(_, _, score)| *score);
+        scored
+            .into_iter()
+            .map(|(term, count, score)| WeightedTerm {
+                term: term.clone(),
+                count,
+                weight: score / top,
+            })
+            .collect()
+    }
+}
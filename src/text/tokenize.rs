@@ -0,0 +1,63 @@
+// Turns Slack message text into lowercase word tokens, dropping markup that
+// would otherwise dominate word counts: code, mentions, links and emoji.
+
+pub fn strip_markup(text: &str) -> String {
+    let text = remove_delimited(text, "```", "```");
+    let text = remove_delimited(&text, "`", "`");
+    // <@U123>, <#C123|name>, <!here>, <https://...|label>
+    let text = remove_delimited(&text, "<", ">");
+    let text = remove_emoji_codes(&text);
+    text.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    strip_markup(text)
+        .split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '’'))
+        .map(|token| token.trim_matches(|c| c == '\'' || c == '’').to_lowercase())
+        .filter(|token| token.chars().count() > 1)
+        .filter(|token| !token.chars().all(|c| c.is_numeric()))
+        .collect()
+}
+
+fn remove_delimited(text: &str, open: &str, close: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(open) {
+        let after_open = &rest[start + open.len()..];
+        match after_open.find(close) {
+            Some(end) => {
+                result.push_str(&rest[..start]);
+                result.push(' ');
+                rest = &after_open[end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// :emoji_name: and :skin-tone-2: style codes
+fn remove_emoji_codes(text: &str) -> String {
+    let is_emoji_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '\'');
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        let after_colon = &rest[start + 1..];
+        let name_len = after_colon
+            .find(|c: char| !is_emoji_char(c))
+            .unwrap_or(after_colon.len());
+        if name_len > 0 && after_colon[name_len..].starts_with(':') {
+            result.push_str(&rest[..start]);
+            result.push(' ');
+            rest = &after_colon[name_len + 1..];
+        } else {
+            result.push_str(&rest[..=start]);
+            rest = after_colon;
+        }
+    }
+    result.push_str(rest);
+    result
+}
@@ -0,0 +1,139 @@
+// Workspaces that installed the app, and the tokens they granted
+pub mod resolver;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Installation {
+    pub team_id: String,
+    pub team_name: Option<String>,
+    pub enterprise_id: Option<String>,
+    pub app_id: String,
+    pub bot_user_id: Option<String>,
+    pub bot_token: String,
+    pub bot_scopes: Vec<String>,
+    pub installer_user_id: String,
+    pub user_token: Option<String>,
+    pub user_scopes: Vec<String>,
+    pub installed_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(error) => write!(f, "token store error: {}", error),
+            StoreError::Json(error) => write!(f, "invalid token store: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(error: io::Error) -> Self {
+        StoreError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(error: serde_json::Error) -> Self {
+        StoreError::Json(error)
+    }
+}
+
+// Installations keyed by team ID, kept in a JSON file. Reinstalling replaces
+// the previous tokens of the team. Clones share the installations, so one
+// made for a background task sees the workspaces installed since.
+#[derive(Clone)]
+pub struct TokenStore {
+    pub path: PathBuf,
+    installations: Arc<Mutex<HashMap<String, Installation>>>,
+}
+
+impl TokenStore {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let path = path.into();
+        let installations = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self {
+            path,
+            installations: Arc::new(Mutex::new(installations)),
+        })
+    }
+
+    pub fn get(&self, team_id: &str) -> Option<Installation> {
+        self.installations.lock().unwrap().get(team_id).cloned()
+    }
+
+    // The installation to use when a request does not name a team
+    pub fn only(&self) -> Option<Installation> {
+        let installations = self.installations.lock().unwrap();
+        match installations.len() {
+            1 => installations.values().next().cloned(),
+            _ => None,
+        }
+    }
+
+    pub fn all(&self) -> Vec<Installation> {
+        self.installations
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn save(&self, installation: Installation) -> Result<(), StoreError> {
+        let mut installations = self.installations.lock().unwrap();
+        installations.insert(installation.team_id.clone(), installation);
+        write_private(&self.path, &serde_json::to_vec_pretty(&*installations)?)
+    }
+
+    // Drops the user token and installer ID of the team's installation if
+    // the user installed it. The bot token stays, the workspace still uses
+    // it. Returns how many user tokens were removed.
+    pub fn forget_user(&self, team_id: &str, user_id: &str) -> Result<usize, StoreError> {
+        let Some(mut installation) = self
+            .get(team_id)
+            .filter(|installation| installation.installer_user_id == user_id)
+        else {
+            return Ok(0);
+        };
+        let removed = installation.user_token.take().map_or(0, |_| 1);
+        installation.user_scopes.clear();
+        installation.installer_user_id.clear();
+        self.save(installation)?;
+        Ok(removed)
+    }
+}
+
+// Written to a temporary file first so a crash never leaves half a store,
+// and readable only by the owner since it holds tokens
+fn write_private(path: &PathBuf, contents: &[u8]) -> Result<(), StoreError> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, contents)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&temporary, fs::Permissions::from_mode(0o600))?;
+    }
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+// Picks the Slack token for a request, so routes can take a `SlackClient`
+// instead of reading the environment.
+//
+// Signed in people always get their own workspace. Otherwise the workspace
+// is named by a `team` query parameter or an `X-Slack-Team-Id` header.
+// Without one, the only installed workspace is used. The configured
+// `slack.token` is the fallback for requests that name no workspace, for
+// single-workspace deployments. A workspace that is named but not installed
+// never gets it, as the token belongs to another workspace.
+use crate::auth::Session;
+use crate::config::AppConfig;
+use crate::slack::client::{SlackClient, SlackClients};
+use crate::workspaces::TokenStore;
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use std::fmt;
+
+pub const TEAM_HEADER: &str = "X-Slack-Team-Id";
+
+#[derive(Debug)]
+pub enum TokenError {
+    UnknownTeam(String),
+    NotInstalled,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::UnknownTeam(team) => write!(f, "no installation for team {}", team),
+            TokenError::NotInstalled => write!(f, "no workspace has installed the app"),
+        }
+    }
+}
+
+pub fn resolve_token(
+    store: Option<&TokenStore>,
+    team: Option<&str>,
+    fallback: Option<&str>,
+) -> Result<String, TokenError> {
+    if let Some(team) = team {
+        return match store.and_then(|store| store.get(team)) {
+            Some(installation) => Ok(installation.bot_token),
+            None => Err(TokenError::UnknownTeam(team.to_string())),
+        };
+    }
+    if let Some(installation) = store.and_then(TokenStore::only) {
+        return Ok(installation.bot_token);
+    }
+    fallback.map(str::to_string).ok_or(TokenError::NotInstalled)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SlackClient {
+    type Error = TokenError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let session = Session::from_cookies(request.cookies(), Utc::now());
+        let team = match &session {
+            Some(session) => Some(session.team_id.0.as_str()),
+            None => request
+                .query_value::<&str>("team")
+                .and_then(Result::ok)
+                .or_else(|| request.headers().get_one(TEAM_HEADER)),
+        };
+        let store = request.rocket().state::<TokenStore>();
+        let fallback = request
+            .rocket()
+            .state::<AppConfig>()
+            .and_then(|config| config.slack.token.as_deref());
+        match resolve_token(store, team, fallback) {
+            Ok(token) => Outcome::Success(match request.rocket().state::<SlackClients>() {
+                Some(clients) => clients.get(&token),
+                None => SlackClient::new(&token),
+            }),
+            Err(error) => {
+                println!("Encountered error: {}", error);
+                Outcome::Error((Status::Unauthorized, error))
+            }
+        }
+    }
+}